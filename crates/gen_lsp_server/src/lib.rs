@@ -68,8 +68,10 @@ use crossbeam_channel::{Receiver, Sender};
 use languageserver_types::{
     notification::{Exit, Initialized},
     request::{Initialize, Shutdown},
-    InitializeParams, InitializeResult, ServerCapabilities,
+    InitializeParams,
 };
+use serde::Serialize;
+use serde_json::json;
 
 pub type Result<T> = ::std::result::Result<T, failure::Error>;
 pub use crate::{
@@ -82,9 +84,14 @@ pub use crate::{
 /// function to create corresponding `sender` and `receiver` pair.
 ///
 ///`server` should use `handle_shutdown` function to handle the `Shutdown`
-/// request.
+/// request. `server` is called once the client has sent the `initialized`
+/// notification, so it may register dynamic capabilities right away.
+///
+/// `caps` is usually a `ServerCapabilities`, but any value serializing to the
+/// capabilities object will do, for capabilities `languageserver_types`
+/// doesn't know about yet.
 pub fn run_server(
-    caps: ServerCapabilities,
+    caps: impl Serialize,
     receiver: Receiver<RawMessage>,
     sender: Sender<RawMessage>,
     server: impl FnOnce(InitializeParams, &Receiver<RawMessage>, &Sender<RawMessage>) -> Result<()>,
@@ -119,7 +126,7 @@ pub fn handle_shutdown(req: RawRequest, sender: &Sender<RawMessage>) -> Option<R
 fn initialize(
     receiver: &Receiver<RawMessage>,
     sender: &Sender<RawMessage>,
-    caps: ServerCapabilities,
+    caps: impl Serialize,
 ) -> Result<InitializeParams> {
     let (id, params) = match receiver.recv() {
         Some(RawMessage::Request(req)) => match req.cast::<Initialize>() {
@@ -128,7 +135,11 @@ fn initialize(
         },
         msg => bail!("expected initialize request, got {:?}", msg),
     };
-    let resp = RawResponse::ok::<Initialize>(id, &InitializeResult { capabilities: caps });
+    let resp = RawResponse {
+        id,
+        result: Some(json!({ "capabilities": caps })),
+        error: None,
+    };
     sender.send(RawMessage::Response(resp));
     match receiver.recv() {
         Some(RawMessage::Notification(n)) => {
@@ -55,6 +55,7 @@ pub enum ErrorCode {
     ServerNotInitialized = -32002,
     UnknownErrorCode = -32001,
     RequestCancelled = -32800,
+    ContentModified = -32801,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -161,6 +162,11 @@ impl RawNotification {
         }
         Ok(from_value(self.params).unwrap())
     }
+    /// Notifications starting with `$/` are protocol implementation dependent:
+    /// a server is free to ignore them if it doesn't know what they mean.
+    pub fn is_implementation_dependent(&self) -> bool {
+        self.method.starts_with("$/")
+    }
 }
 
 fn read_msg_text(inp: &mut impl BufRead) -> Result<Option<String>> {
@@ -1,20 +1,24 @@
 mod reference_completion;
+mod dot_completion;
 
 use ra_editor::find_node_at_offset;
-use ra_text_edit::AtomTextEdit;
+use ra_text_edit::{AtomTextEdit, TextEdit};
 use ra_syntax::{
     algo::visit::{visitor_ctx, VisitorCtx},
-    ast,
+    ast::{self, NameOwner},
     AstNode,
+    SourceFileNode,
     SyntaxNodeRef,
+    TextRange,
+    SyntaxKind::*,
 };
 use ra_db::SyntaxDatabase;
-use rustc_hash::{FxHashMap};
-use hir::source_binder;
+use rustc_hash::{FxHashMap, FxHashSet};
+use hir::{self, source_binder, DefId, FnScopes, Path};
 
 use crate::{
     db,
-    Cancelable, FilePosition
+    Cancelable, FileId, FilePosition
 };
 
 #[derive(Debug)]
@@ -25,6 +29,71 @@ pub struct CompletionItem {
     pub lookup: Option<String>,
     /// What is inserted, defaults to label
     pub snippet: Option<String>,
+    /// What the item is, for clients which render or insert kinds differently
+    pub kind: Option<CompletionItemKind>,
+    /// How well the item fits the context, items with higher relevance
+    /// should be shown first
+    pub relevance: u32,
+    /// Additional information shown next to the label, like the path of an
+    /// item which is not imported yet
+    pub detail: Option<String>,
+    /// Edit applied when the item is accepted, besides inserting it, like
+    /// adding a `use`
+    pub additional_edit: Option<TextEdit>,
+    /// The definition of the item, whose docs are looked up only when the
+    /// client resolves the item
+    pub source: Option<(FileId, TextRange)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionItemKind {
+    Keyword,
+    /// A template, like `pd` for a debug print, rather than a name
+    Snippet,
+    Function,
+}
+
+impl CompletionItem {
+    pub(crate) fn new(label: impl Into<String>) -> CompletionItem {
+        CompletionItem {
+            label: label.into(),
+            lookup: None,
+            snippet: None,
+            kind: None,
+            relevance: 0,
+            detail: None,
+            additional_edit: None,
+            source: None,
+        }
+    }
+    pub(crate) fn lookup_by(mut self, lookup: impl Into<String>) -> CompletionItem {
+        self.lookup = Some(lookup.into());
+        self
+    }
+    pub(crate) fn snippet(mut self, snippet: impl Into<String>) -> CompletionItem {
+        self.snippet = Some(snippet.into());
+        self
+    }
+    pub(crate) fn kind(mut self, kind: CompletionItemKind) -> CompletionItem {
+        self.kind = Some(kind);
+        self
+    }
+    pub(crate) fn relevance(mut self, relevance: u32) -> CompletionItem {
+        self.relevance = relevance;
+        self
+    }
+    pub(crate) fn detail(mut self, detail: Option<String>) -> CompletionItem {
+        self.detail = detail;
+        self
+    }
+    pub(crate) fn additional_edit(mut self, edit: TextEdit) -> CompletionItem {
+        self.additional_edit = Some(edit);
+        self
+    }
+    pub(crate) fn source(mut self, file_id: FileId, range: TextRange) -> CompletionItem {
+        self.source = Some((file_id, range));
+        self
+    }
 }
 
 pub(crate) fn completions(
@@ -45,7 +114,10 @@ pub(crate) fn completions(
     // First, let's try to complete a reference to some declaration.
     if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
         has_completions = true;
-        reference_completion::completions(&mut res, db, &module, &file, name_ref)?;
+        reference_completion::completions(&mut res, db, &module, name_ref)?;
+        dot_completion::completions(&mut res, db, &module, &file, name_ref)?;
+        rank_by_expected_type(&mut res, db, &module, &file, name_ref)?;
+        complete_struct_lit_fields(&mut res, db, &module, name_ref)?;
         // special case, `trait T { fn foo(i_am_a_name_ref) {} }`
         if is_node::<ast::Param>(name_ref.syntax()) {
             param_completions(name_ref.syntax(), &mut res);
@@ -63,6 +135,176 @@ pub(crate) fn completions(
     Ok(res)
 }
 
+/// Bumps relevance of locals whose declared type matches the type expected
+/// at the completion position (currently, the type of a call argument).
+fn rank_by_expected_type(
+    acc: &mut [CompletionItem],
+    db: &db::RootDatabase,
+    module: &hir::Module,
+    file: &SourceFileNode,
+    name_ref: ast::NameRef,
+) -> Cancelable<()> {
+    let expected = match expected_type(db, module, name_ref)? {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let fn_def = match name_ref.syntax().ancestors().find_map(ast::FnDef::cast) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let scopes = FnScopes::new(fn_def);
+    let mut matching = FxHashSet::default();
+    for entry in scopes
+        .scope_chain(name_ref.syntax())
+        .flat_map(|scope| scopes.entries(scope).iter())
+    {
+        let pat = entry.ptr().resolve(file);
+        let type_ref = match declared_type(pat.borrowed()) {
+            Some(it) => it,
+            None => continue,
+        };
+        if resolve_type(db, module, type_ref)? == Some(expected) {
+            matching.insert(entry.name().to_string());
+        }
+    }
+    for item in acc.iter_mut() {
+        let name = item.lookup.as_ref().unwrap_or(&item.label);
+        if item.snippet.is_none() && matching.contains(name) {
+            item.relevance += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Inside `Foo { <|> }`, offers the fields of `Foo` which are not set yet,
+/// ranked above everything else, and `..Default::default()`.
+fn complete_struct_lit_fields(
+    acc: &mut Vec<CompletionItem>,
+    db: &db::RootDatabase,
+    module: &hir::Module,
+    name_ref: ast::NameRef,
+) -> Cancelable<()> {
+    let field = match name_ref.syntax().parent().and_then(ast::NamedField::cast) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let field_list = match field.syntax().parent().and_then(ast::NamedFieldList::cast) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let struct_lit = match field_list.syntax().parent().and_then(ast::StructLit::cast) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let path = match struct_lit
+        .syntax()
+        .children()
+        .find_map(ast::Path::cast)
+        .and_then(Path::from_ast)
+    {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let def_id = match module.resolve_path(db, path)? {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let (_, syntax) = def_id.source(db);
+    let struct_def = match ast::StructDef::cast(syntax.borrowed()) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let written = field_list
+        .syntax()
+        .children()
+        .filter(|&it| it != field.syntax())
+        .filter_map(ast::NamedField::cast)
+        .filter_map(|it| it.syntax().children().find_map(ast::NameRef::cast))
+        .map(|it| it.text())
+        .collect::<FxHashSet<_>>();
+    for field_def in struct_def.named_fields() {
+        let name = match field_def.name() {
+            Some(it) => it.text(),
+            None => continue,
+        };
+        if written.contains(&name) {
+            continue;
+        }
+        let detail = field_def
+            .type_ref()
+            .map(|it| it.syntax().text().to_string());
+        acc.push(
+            CompletionItem::new(name.as_str())
+                .relevance(2)
+                .detail(detail),
+        );
+    }
+    if field_list.syntax().children().all(|it| it.kind() != DOTDOT) {
+        acc.push(
+            CompletionItem::new("..Default::default()")
+                .lookup_by("Default")
+                .relevance(1),
+        );
+    }
+    Ok(())
+}
+
+fn expected_type(
+    db: &db::RootDatabase,
+    module: &hir::Module,
+    name_ref: ast::NameRef,
+) -> Cancelable<Option<DefId>> {
+    let arg = ctry!(name_ref
+        .syntax()
+        .ancestors()
+        .find(|it| it.parent().map(|p| p.kind()) == Some(ARG_LIST)));
+    let arg_list = ctry!(arg.parent().and_then(ast::ArgList::cast));
+    let idx = ctry!(arg_list.args().position(|it| it.syntax() == arg));
+    let call = ctry!(arg_list.syntax().parent().and_then(ast::CallExpr::cast));
+    let path = match call.expr() {
+        Some(ast::Expr::PathExpr(it)) => ctry!(it.path()),
+        _ => return Ok(None),
+    };
+    let path = ctry!(Path::from_ast(path));
+    let def_id = ctry!(module.resolve_path(db, path)?);
+    let (file_id, syntax) = def_id.source(db);
+    let fn_def = ctry!(ast::FnDef::cast(syntax.borrowed()));
+    let param = ctry!(fn_def.param_list().and_then(|it| it.params().nth(idx)));
+    // The parameter type is written in the scope of the callee.
+    let fn_module = ctry!(source_binder::module_from_child_node(
+        db,
+        file_id,
+        fn_def.syntax()
+    )?);
+    resolve_type(db, &fn_module, ctry!(param.type_ref()))
+}
+
+fn declared_type<'a>(pat: SyntaxNodeRef<'a>) -> Option<ast::TypeRef<'a>> {
+    let parent = pat.parent()?;
+    if let Some(stmt) = ast::LetStmt::cast(parent) {
+        stmt.type_ref()
+    } else if let Some(param) = ast::Param::cast(parent) {
+        param.type_ref()
+    } else {
+        None
+    }
+}
+
+/// Resolves a path type, like `Foo` or `foo::Bar`, to its definition. Other
+/// types are not supported yet.
+fn resolve_type(
+    db: &db::RootDatabase,
+    module: &hir::Module,
+    type_ref: ast::TypeRef,
+) -> Cancelable<Option<DefId>> {
+    let path = match type_ref {
+        ast::TypeRef::PathType(it) => ctry!(it.syntax().children().find_map(ast::Path::cast)),
+        _ => return Ok(None),
+    };
+    let path = ctry!(Path::from_ast(path));
+    module.resolve_path(db, path)
+}
+
 fn param_completions(ctx: SyntaxNodeRef, acc: &mut Vec<CompletionItem>) {
     let mut params = FxHashMap::default();
     for node in ctx.ancestors() {
@@ -81,13 +323,7 @@ fn param_completions(ctx: SyntaxNodeRef, acc: &mut Vec<CompletionItem>) {
                 Some((label, lookup))
             }
         })
-        .for_each(|(label, lookup)| {
-            acc.push(CompletionItem {
-                label,
-                lookup: Some(lookup),
-                snippet: None,
-            })
-        });
+        .for_each(|(label, lookup)| acc.push(CompletionItem::new(label).lookup_by(lookup)));
 
     fn process<'a, N: ast::FnDefOwner<'a>>(
         node: N,
@@ -114,7 +350,7 @@ fn is_node<'a, N: AstNode<'a>>(node: SyntaxNodeRef<'a>) -> bool {
 mod tests {
     use test_utils::assert_eq_dbg;
 
-    use crate::mock_analysis::single_file_with_position;
+    use crate::mock_analysis::{analysis_and_position, single_file_with_position};
 
     use super::*;
 
@@ -140,6 +376,18 @@ mod tests {
         assert_eq_dbg(expected_completions, &completions);
     }
 
+    fn check_keyword_completion(code: &str, expected_keywords: &str) {
+        let (analysis, position) = single_file_with_position(code);
+        let keywords = completions(&analysis.imp.db, position)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.kind == Some(CompletionItemKind::Keyword))
+            .map(|c| c.label)
+            .collect::<Vec<_>>();
+        assert_eq_dbg(expected_keywords, &keywords);
+    }
+
     #[test]
     fn test_completion_let_scope() {
         check_scope_completion(
@@ -150,9 +398,163 @@ mod tests {
                 let z = ();
             }
             ",
-            r#"[CompletionItem { label: "y", lookup: None, snippet: None },
-                   CompletionItem { label: "x", lookup: None, snippet: None },
-                   CompletionItem { label: "quux", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "y", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "x", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "quux", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 122))) }]"#,
+        );
+    }
+
+    #[test]
+    fn test_completion_ranks_expected_type_higher() {
+        check_scope_completion(
+            r"
+            struct Foo;
+            struct Bar;
+            fn takes_foo(foo: Foo) {}
+            fn quux(bar: Bar) {
+                let foo: Foo = Foo;
+                takes_foo(<|>);
+            }
+            ",
+            r#"[CompletionItem { label: "foo", lookup: None, snippet: None, kind: None, relevance: 1, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "bar", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "Foo", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 24))) },
+                   CompletionItem { label: "takes_foo", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [61; 86))) },
+                   CompletionItem { label: "Bar", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [37; 48))) },
+                   CompletionItem { label: "quux", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [99; 197))) }]"#,
+        );
+    }
+
+    #[test]
+    fn test_completion_expected_type_is_resolved() {
+        let (analysis, position) = single_file_with_position(
+            r"
+            mod a { pub struct Foo; }
+            mod b {
+                pub struct Foo;
+                pub fn takes_foo(foo: Foo) {}
+            }
+            use a::Foo;
+            fn quux(same_text: Foo, same_type: b::Foo) {
+                b::takes_foo(<|>);
+            }
+            ",
+        );
+        let relevance = |label: &str| {
+            completions(&analysis.imp.db, position)
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .find(|it| it.label == label)
+                .unwrap()
+                .relevance
+        };
+        assert_eq!(relevance("same_type"), 1);
+        assert_eq!(relevance("same_text"), 0);
+    }
+
+    #[test]
+    fn test_completion_struct_lit_fields() {
+        check_scope_completion(
+            r"
+            struct Foo { bar: u32, baz: String }
+            fn quux() {
+                Foo { bar: 92, <|> }
+            }
+            ",
+            r#"[CompletionItem { label: "baz", lookup: None, snippet: None, kind: None, relevance: 2, detail: Some("String"), additional_edit: None, source: None },
+                   CompletionItem { label: "..Default::default()", lookup: Some("Default"), snippet: None, kind: None, relevance: 1, detail: None, additional_edit: None, source: None }]"#,
+        );
+    }
+
+    #[test]
+    fn test_completion_dot_fields_and_methods() {
+        check_scope_completion(
+            r"
+            struct Foo { bar: u32 }
+            impl Foo {
+                fn new() -> Foo { Foo { bar: 0 } }
+                fn baz(&self) {}
+            }
+            fn quux(foo: &Foo) {
+                foo.<|>
+            }
+            ",
+            r#"[CompletionItem { label: "bar", lookup: None, snippet: None, kind: None, relevance: 0, detail: Some("u32"), additional_edit: None, source: Some((FileId(1), [26; 34))) },
+                   CompletionItem { label: "baz", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [127; 143))) }]"#,
+        );
+    }
+
+    #[test]
+    fn test_completion_dot_self() {
+        check_scope_completion(
+            r"
+            struct Foo { bar: u32 }
+            impl Foo {
+                fn baz(&self) {
+                    self.<|>
+                }
+            }
+            ",
+            r#"[CompletionItem { label: "bar", lookup: None, snippet: None, kind: None, relevance: 0, detail: Some("u32"), additional_edit: None, source: Some((FileId(1), [26; 34))) },
+                   CompletionItem { label: "baz", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [76; 135))) }]"#,
+        );
+    }
+
+    #[test]
+    fn test_completion_dot_methods_from_other_files() {
+        let (analysis, position) = analysis_and_position(
+            "
+            //- /lib.rs
+            mod foo;
+            mod bar;
+            use crate::foo::Foo;
+            impl Foo {
+                fn baz(&self) {}
+            }
+            fn quux(foo: Foo) {
+                foo.<|>
+            }
+            //- /foo.rs
+            pub struct Foo;
+            //- /bar.rs
+            struct Foo;
+            impl Foo {
+                fn other(&self) {}
+            }
+            ",
+        );
+        let completions = completions(&analysis.imp.db, position).unwrap().unwrap();
+        assert_eq_dbg(
+            r#"[CompletionItem { label: "baz", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [54; 70))) }]"#,
+            &completions,
+        );
+    }
+
+    #[test]
+    fn test_completion_dot_unknown_receiver() {
+        check_scope_completion(
+            r"
+            fn quux() {
+                let foo = make();
+                foo.<|>
+            }
+            ",
+            r#"[]"#,
+        );
+    }
+
+    #[test]
+    fn test_completion_raw_ident() {
+        check_scope_completion(
+            r"
+            fn quux() {
+                let r#type = 92;
+                <|>
+            }
+            ",
+            r#"[CompletionItem { label: "r#type", lookup: Some("type"), snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "quux", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 88))) }]"#,
         );
     }
 
@@ -170,9 +572,9 @@ mod tests {
                 }
             }
             ",
-            r#"[CompletionItem { label: "b", lookup: None, snippet: None },
-                   CompletionItem { label: "a", lookup: None, snippet: None },
-                   CompletionItem { label: "quux", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "b", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "a", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "quux", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 246))) }]"#,
         );
     }
 
@@ -186,8 +588,8 @@ mod tests {
                 }
             }
             ",
-            r#"[CompletionItem { label: "x", lookup: None, snippet: None },
-                   CompletionItem { label: "quux", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "x", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "quux", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 115))) }]"#,
         );
     }
 
@@ -201,9 +603,9 @@ mod tests {
                 <|>
             }
             ",
-            r#"[CompletionItem { label: "quux", lookup: None, snippet: None },
-                CompletionItem { label: "Foo", lookup: None, snippet: None },
-                CompletionItem { label: "Baz", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "quux", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [61; 103))) },
+                CompletionItem { label: "Foo", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 24))) },
+                CompletionItem { label: "Baz", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [37; 48))) }]"#,
         );
     }
 
@@ -227,7 +629,7 @@ mod tests {
                 struct Bar;
             }
             ",
-            r#"[CompletionItem { label: "Bar", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "Bar", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [65; 76))) }]"#,
         );
     }
 
@@ -241,8 +643,8 @@ mod tests {
                 fn quux() { <|> }
             }
             ",
-            r#"[CompletionItem { label: "quux", lookup: None, snippet: None },
-                CompletionItem { label: "Bar", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "quux", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [89; 103))) },
+                CompletionItem { label: "Bar", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [61; 72))) }]"#,
         );
     }
 
@@ -253,8 +655,8 @@ mod tests {
             struct Foo;
             fn x() -> <|>
         ",
-            r#"[CompletionItem { label: "Foo", lookup: None, snippet: None },
-               CompletionItem { label: "x", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "Foo", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 24))) },
+               CompletionItem { label: "x", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [37; 56))) }]"#,
         )
     }
 
@@ -270,8 +672,8 @@ mod tests {
                 }
             }
         ",
-            r#"[CompletionItem { label: "bar", lookup: None, snippet: None },
-               CompletionItem { label: "foo", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "bar", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+               CompletionItem { label: "foo", lookup: None, snippet: None, kind: Some(Function), relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 161))) }]"#,
         )
     }
 
@@ -281,7 +683,7 @@ mod tests {
             r"
             impl S { fn foo(&self) { <|> } }
         ",
-            r#"[CompletionItem { label: "self", lookup: None, snippet: None }]"#,
+            r#"[CompletionItem { label: "self", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None }]"#,
         )
     }
 
@@ -291,13 +693,13 @@ mod tests {
             fn quux() {
                 <|>
             }
-            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $0 {}") },
-                   CompletionItem { label: "match", lookup: None, snippet: Some("match $0 {}") },
-                   CompletionItem { label: "while", lookup: None, snippet: Some("while $0 {}") },
-                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}") },
-                   CompletionItem { label: "return", lookup: None, snippet: Some("return") },
-                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);") },
-                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);") }]"#);
+            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "match", lookup: None, snippet: Some("match $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "while", lookup: None, snippet: Some("while $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "return", lookup: None, snippet: Some("return"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"#);
     }
 
     #[test]
@@ -308,15 +710,53 @@ mod tests {
                     ()
                 } <|>
             }
-            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $0 {}") },
-                   CompletionItem { label: "match", lookup: None, snippet: Some("match $0 {}") },
-                   CompletionItem { label: "while", lookup: None, snippet: Some("while $0 {}") },
-                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}") },
-                   CompletionItem { label: "else", lookup: None, snippet: Some("else {$0}") },
-                   CompletionItem { label: "else if", lookup: None, snippet: Some("else if $0 {}") },
-                   CompletionItem { label: "return", lookup: None, snippet: Some("return") },
-                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);") },
-                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);") }]"#);
+            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "match", lookup: None, snippet: Some("match $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "while", lookup: None, snippet: Some("while $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "else", lookup: None, snippet: Some("else {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "else if", lookup: None, snippet: Some("else if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "return", lookup: None, snippet: Some("return"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"#);
+    }
+
+    #[test]
+    fn test_completion_keywords_by_position() {
+        // `else` only after an `if` which doesn't have one yet
+        check_keyword_completion(
+            r"
+            fn quux() {
+                if true {} else {} <|>
+            }
+            ",
+            r#"["if", "match", "while", "loop", "return"]"#,
+        );
+        check_keyword_completion(
+            r"
+            fn quux() {
+                if true {} else if false {} <|>
+            }
+            ",
+            r#"["if", "match", "while", "loop", "else", "else if", "return"]"#,
+        );
+        check_keyword_completion(
+            r"
+            fn quux() {
+                if true {}
+                foo(<|>)
+            }
+            ",
+            r#"["if", "match", "while", "loop", "return"]"#,
+        );
+        // no expression keywords among items
+        check_keyword_completion(
+            r"
+            struct Foo;
+            <|>
+            ",
+            r#"["fn", "struct", "enum", "trait", "impl", "mod", "use"]"#,
+        );
     }
 
     #[test]
@@ -326,25 +766,25 @@ mod tests {
                 <|>
                 92
             }
-            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $0 {}") },
-                   CompletionItem { label: "match", lookup: None, snippet: Some("match $0 {}") },
-                   CompletionItem { label: "while", lookup: None, snippet: Some("while $0 {}") },
-                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}") },
-                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0;") },
-                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);") },
-                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);") }]"#);
+            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "match", lookup: None, snippet: Some("match $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "while", lookup: None, snippet: Some("while $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0;"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"#);
         check_snippet_completion(r"
             fn quux() {
                 <|>
                 92
             }
-            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $0 {}") },
-                   CompletionItem { label: "match", lookup: None, snippet: Some("match $0 {}") },
-                   CompletionItem { label: "while", lookup: None, snippet: Some("while $0 {}") },
-                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}") },
-                   CompletionItem { label: "return", lookup: None, snippet: Some("return;") },
-                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);") },
-                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);") }]"#);
+            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "match", lookup: None, snippet: Some("match $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "while", lookup: None, snippet: Some("while $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "return", lookup: None, snippet: Some("return;"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"#);
     }
 
     #[test]
@@ -355,13 +795,13 @@ mod tests {
                     () => <|>
                 }
             }
-            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $0 {}") },
-                   CompletionItem { label: "match", lookup: None, snippet: Some("match $0 {}") },
-                   CompletionItem { label: "while", lookup: None, snippet: Some("while $0 {}") },
-                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}") },
-                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0") },
-                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);") },
-                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);") }]"#);
+            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "match", lookup: None, snippet: Some("match $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "while", lookup: None, snippet: Some("while $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"#);
     }
 
     #[test]
@@ -370,26 +810,26 @@ mod tests {
             fn quux() -> i32 {
                 loop { <|> }
             }
-            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $0 {}") },
-                   CompletionItem { label: "match", lookup: None, snippet: Some("match $0 {}") },
-                   CompletionItem { label: "while", lookup: None, snippet: Some("while $0 {}") },
-                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}") },
-                   CompletionItem { label: "continue", lookup: None, snippet: Some("continue") },
-                   CompletionItem { label: "break", lookup: None, snippet: Some("break") },
-                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0") },
-                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);") },
-                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);") }]"#);
+            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "match", lookup: None, snippet: Some("match $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "while", lookup: None, snippet: Some("while $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "continue", lookup: None, snippet: Some("continue"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "break", lookup: None, snippet: Some("break"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"#);
         check_snippet_completion(r"
             fn quux() -> i32 {
                 loop { || { <|> } }
             }
-            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $0 {}") },
-                   CompletionItem { label: "match", lookup: None, snippet: Some("match $0 {}") },
-                   CompletionItem { label: "while", lookup: None, snippet: Some("while $0 {}") },
-                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}") },
-                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0") },
-                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);") },
-                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);") }]"#);
+            ", r#"[CompletionItem { label: "if", lookup: None, snippet: Some("if $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "match", lookup: None, snippet: Some("match $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "while", lookup: None, snippet: Some("while $1 { $0 }"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "loop", lookup: None, snippet: Some("loop {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "return", lookup: None, snippet: Some("return $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "pd", lookup: None, snippet: Some("eprintln!(\"$0 = {:?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                   CompletionItem { label: "ppd", lookup: None, snippet: Some("eprintln!(\"$0 = {:#?}\", $0);"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"#);
     }
 
     #[test]
@@ -398,7 +838,7 @@ mod tests {
             fn foo(file_id: FileId) {}
             fn bar(file_id: FileId) {}
             fn baz(file<|>) {}
-        ", r#"[CompletionItem { label: "file_id: FileId", lookup: Some("file_id"), snippet: None }]"#);
+        ", r#"[CompletionItem { label: "file_id: FileId", lookup: Some("file_id"), snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None }]"#);
     }
 
     #[test]
@@ -407,7 +847,7 @@ mod tests {
             fn foo(file_id: FileId) {}
             fn bar(file_id: FileId) {}
             fn baz(file<|>, x: i32) {}
-        ", r#"[CompletionItem { label: "file_id: FileId", lookup: Some("file_id"), snippet: None }]"#);
+        ", r#"[CompletionItem { label: "file_id: FileId", lookup: Some("file_id"), snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None }]"#);
     }
 
     #[test]
@@ -419,9 +859,9 @@ mod tests {
                 pub fn lines(&self, file_id: FileId) -> &LineIndex;
                 pub fn syntax(&self, file<|>)
             }
-        ", r#"[CompletionItem { label: "self", lookup: None, snippet: None },
-               CompletionItem { label: "SourceRoot", lookup: None, snippet: None },
-               CompletionItem { label: "file_id: FileId", lookup: Some("file_id"), snippet: None }]"#);
+        ", r#"[CompletionItem { label: "self", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None },
+               CompletionItem { label: "SourceRoot", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [13; 288))) },
+               CompletionItem { label: "file_id: FileId", lookup: Some("file_id"), snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: None }]"#);
     }
 
     #[test]
@@ -437,8 +877,15 @@ mod tests {
                 <|>
             }
             ",
-            r##"[CompletionItem { label: "Test function", lookup: Some("tfn"), snippet: Some("#[test]\nfn ${1:feature}() {\n$0\n}") },
-                 CompletionItem { label: "pub(crate)", lookup: None, snippet: Some("pub(crate) $0") }]"##,
+            r##"[CompletionItem { label: "fn", lookup: None, snippet: Some("fn $1($2) {$0}"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "struct", lookup: None, snippet: Some("struct $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "enum", lookup: None, snippet: Some("enum $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "trait", lookup: None, snippet: Some("trait $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "impl", lookup: None, snippet: Some("impl $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "mod", lookup: None, snippet: Some("mod $0"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "use", lookup: None, snippet: Some("use $0;"), kind: Some(Keyword), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "Test function", lookup: Some("tfn"), snippet: Some("#[test]\nfn ${1:feature}() {\n$0\n}"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None },
+                 CompletionItem { label: "pub(crate)", lookup: None, snippet: Some("pub(crate) $0"), kind: Some(Snippet), relevance: 0, detail: None, additional_edit: None, source: None }]"##,
         );
     }
 }
@@ -0,0 +1,164 @@
+use ra_db::{FilesDatabase, SyntaxDatabase};
+use ra_syntax::{
+    ast::{self, FnDefOwner, NameOwner},
+    AstNode, SourceFileNode,
+    SyntaxKind::*,
+};
+use hir::{self, source_binder, FnScopes, Path};
+
+use crate::{
+    db::RootDatabase,
+    completion::{CompletionItem, CompletionItemKind},
+    Cancelable,
+};
+
+/// Completes fields and methods after `receiver.`, if the type of the
+/// receiver is a struct which can be found from the syntax alone: `self`,
+/// or a local with a type annotation or a struct literal initializer.
+pub(super) fn completions(
+    acc: &mut Vec<CompletionItem>,
+    db: &RootDatabase,
+    module: &hir::Module,
+    file: &SourceFileNode,
+    name_ref: ast::NameRef,
+) -> Cancelable<()> {
+    let receiver = match name_ref.syntax().parent() {
+        Some(parent) if parent.kind() == FIELD_EXPR || parent.kind() == METHOD_CALL_EXPR => {
+            match parent.children().find_map(ast::Expr::cast) {
+                Some(it) => it,
+                None => return Ok(()),
+            }
+        }
+        _ => return Ok(()),
+    };
+    let type_path = match receiver_type(file, receiver) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let def_id = match module.resolve_path(db, type_path)? {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let (file_id, syntax) = def_id.source(db);
+    let struct_def = match ast::StructDef::cast(syntax.borrowed()) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    for field_def in struct_def.named_fields() {
+        if let Some(name) = field_def.name() {
+            let detail = field_def
+                .type_ref()
+                .map(|it| it.syntax().text().to_string());
+            acc.push(
+                CompletionItem::new(name.text().as_str())
+                    .detail(detail)
+                    .source(file_id, field_def.syntax().range()),
+            );
+        }
+    }
+
+    // Methods are looked up in the inherent and trait impls of the source
+    // root of the struct whose self type resolves to it.
+    let struct_name = match struct_def.name() {
+        Some(it) => it.text(),
+        None => return Ok(()),
+    };
+    let mut files = db
+        .source_root(db.file_source_root(file_id))
+        .files
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    files.sort();
+    for impl_file_id in files {
+        let impl_file = db.source_file(impl_file_id);
+        let impls = impl_file
+            .syntax()
+            .descendants()
+            .filter_map(ast::ImplItem::cast);
+        for impl_item in impls {
+            let self_type = match impl_self_type(impl_item) {
+                Some(it) => it,
+                None => continue,
+            };
+            // Only resolve the paths which may name the struct.
+            let last_name = self_type
+                .segment()
+                .and_then(|it| it.name_ref())
+                .map(|it| it.text());
+            if last_name.as_ref() != Some(&struct_name) {
+                continue;
+            }
+            let impl_module = match source_binder::module_from_child_node(
+                db,
+                impl_file_id,
+                impl_item.syntax(),
+            )? {
+                Some(it) => it,
+                None => continue,
+            };
+            let self_def_id = match Path::from_ast(self_type) {
+                Some(path) => impl_module.resolve_path(db, path)?,
+                None => None,
+            };
+            if self_def_id != Some(def_id) {
+                continue;
+            }
+            let methods = impl_item
+                .item_list()
+                .into_iter()
+                .flat_map(|it| it.functions())
+                .filter(|it| it.param_list().and_then(|it| it.self_param()).is_some());
+            for method in methods {
+                if let Some(name) = method.name() {
+                    acc.push(
+                        CompletionItem::new(name.text().as_str())
+                            .kind(CompletionItemKind::Function)
+                            .source(impl_file_id, method.syntax().range()),
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Path of the declared type of `receiver`, ignoring references.
+fn receiver_type(file: &SourceFileNode, receiver: ast::Expr) -> Option<Path> {
+    let path = match receiver {
+        ast::Expr::PathExpr(it) => it.path()?,
+        _ => return None,
+    };
+    if path.qualifier().is_none() && path.segment()?.syntax().first_child()?.kind() == SELF_KW {
+        let impl_item = path.syntax().ancestors().find_map(ast::ImplItem::cast)?;
+        return Path::from_ast(impl_self_type(impl_item)?);
+    }
+    let name_ref = path.segment()?.name_ref()?;
+    let fn_def = name_ref.syntax().ancestors().find_map(ast::FnDef::cast)?;
+    let scopes = FnScopes::new(fn_def);
+    let pat = scopes.resolve_local_name(name_ref)?.ptr().resolve(file);
+    let parent = pat.borrowed().parent()?;
+    let type_ref = if let Some(param) = ast::Param::cast(parent) {
+        param.type_ref()
+    } else if let Some(stmt) = ast::LetStmt::cast(parent) {
+        match (stmt.type_ref(), stmt.initializer()) {
+            (Some(type_ref), _) => Some(type_ref),
+            (None, Some(ast::Expr::StructLit(lit))) => {
+                return Path::from_ast(lit.syntax().children().find_map(ast::Path::cast)?);
+            }
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let path = type_ref?.syntax().descendants().find_map(ast::Path::cast)?;
+    Path::from_ast(path)
+}
+
+/// In `impl Foo` and `impl Trait for Foo`, the path of `Foo`.
+fn impl_self_type(impl_item: ast::ImplItem) -> Option<ast::Path> {
+    match impl_item.target_type()? {
+        ast::TypeRef::PathType(it) => it.syntax().child_of_kind(PATH).and_then(ast::Path::cast),
+        _ => None,
+    }
+}
@@ -1,10 +1,9 @@
 use rustc_hash::{FxHashSet};
-use ra_editor::find_node_at_offset;
 use ra_syntax::{
     algo::visit::{visitor, Visitor},
-    SourceFileNode, AstNode,
+    AstNode, Direction, SyntaxNodeRef,
     ast::{self, LoopBodyOwner},
-    SyntaxKind::*,
+    SyntaxKind::{self, *},
 };
 use     hir::{
         self,
@@ -15,7 +14,7 @@ use     hir::{
 
 use crate::{
     db::RootDatabase,
-    completion::CompletionItem,
+    completion::{CompletionItem, CompletionItemKind},
     Cancelable
 };
 
@@ -23,7 +22,6 @@ pub(super) fn completions(
     acc: &mut Vec<CompletionItem>,
     db: &RootDatabase,
     module: &hir::Module,
-    file: &SourceFileNode,
     name_ref: ast::NameRef,
 ) -> Cancelable<()> {
     let kind = match classify_name_ref(name_ref) {
@@ -36,30 +34,24 @@ pub(super) fn completions(
             if let Some(fn_def) = enclosing_fn {
                 let scopes = FnScopes::new(fn_def);
                 complete_fn(name_ref, &scopes, acc);
-                complete_expr_keywords(&file, fn_def, name_ref, acc);
+                complete_expr_keywords(fn_def, name_ref, acc);
                 complete_expr_snippets(acc);
             }
 
             let module_scope = module.scope(db)?;
-            acc.extend(
-                module_scope
-                    .entries()
-                    .filter(|(_name, res)| {
-                        // Don't expose this item
-                        match res.import {
-                            None => true,
-                            Some(import) => {
-                                let range = import.range(db, module.source().file_id());
-                                !range.is_subrange(&name_ref.syntax().range())
-                            }
-                        }
-                    })
-                    .map(|(name, _res)| CompletionItem {
-                        label: name.to_string(),
-                        lookup: None,
-                        snippet: None,
-                    }),
-            );
+            let entries = module_scope.entries().filter(|(_name, res)| {
+                // Don't expose this item
+                match res.import {
+                    None => true,
+                    Some(import) => {
+                        let range = import.range(db, module.source().file_id());
+                        !range.is_subrange(&name_ref.syntax().range())
+                    }
+                }
+            });
+            for (name, res) in entries {
+                acc.push(def_completion(db, name, res)?);
+            }
         }
         NameRefKind::Path(path) => complete_path(acc, db, module, path)?,
         NameRefKind::BareIdentInMod => {
@@ -71,7 +63,10 @@ pub(super) fn completions(
                 .last()
                 .unwrap();
             match top_node.parent().map(|it| it.kind()) {
-                Some(SOURCE_FILE) | Some(ITEM_LIST) => complete_mod_item_snippets(acc),
+                Some(SOURCE_FILE) | Some(ITEM_LIST) => {
+                    complete_item_keywords(acc);
+                    complete_mod_item_snippets(acc);
+                }
                 _ => (),
             }
         }
@@ -132,19 +127,35 @@ fn complete_fn(name_ref: ast::NameRef, scopes: &FnScopes, acc: &mut Vec<Completi
             .scope_chain(name_ref.syntax())
             .flat_map(|scope| scopes.entries(scope).iter())
             .filter(|entry| shadowed.insert(entry.name()))
-            .map(|entry| CompletionItem {
-                label: entry.name().to_string(),
-                lookup: None,
-                snippet: None,
-            }),
+            .map(|entry| name_completion(entry.name())),
     );
     if scopes.self_param.is_some() {
-        acc.push(CompletionItem {
-            label: "self".to_string(),
-            lookup: None,
-            snippet: None,
-        })
+        acc.push(CompletionItem::new("self"))
+    }
+}
+
+/// Names which are keywords can only be inserted as raw identifiers.
+fn name_completion(name: &str) -> CompletionItem {
+    match SyntaxKind::from_keyword(name) {
+        Some(_) => CompletionItem::new(format!("r#{}", name)).lookup_by(name),
+        None => CompletionItem::new(name),
+    }
+}
+
+fn def_completion(
+    db: &RootDatabase,
+    name: &str,
+    res: &hir::Resolution,
+) -> Cancelable<CompletionItem> {
+    let mut item = name_completion(name);
+    if let Some(def_id) = res.def_id {
+        let (file_id, syntax) = def_id.source(db);
+        if syntax.kind() == FN_DEF {
+            item.kind = Some(CompletionItemKind::Function);
+        }
+        item.source = Some((file_id, syntax.range()));
     }
+    Ok(item)
 }
 
 fn complete_path(
@@ -166,52 +177,54 @@ fn complete_path(
         _ => return Ok(()),
     };
     let module_scope = target_module.scope(db)?;
-    let completions = module_scope.entries().map(|(name, _res)| CompletionItem {
-        label: name.to_string(),
-        lookup: None,
-        snippet: None,
-    });
-    acc.extend(completions);
+    for (name, res) in module_scope.entries() {
+        acc.push(def_completion(db, name, res)?);
+    }
     Ok(())
 }
 
 fn complete_mod_item_snippets(acc: &mut Vec<CompletionItem>) {
-    acc.push(CompletionItem {
-        label: "Test function".to_string(),
-        lookup: Some("tfn".to_string()),
-        snippet: Some(
-            "#[test]\n\
-             fn ${1:feature}() {\n\
-             $0\n\
-             }"
-            .to_string(),
-        ),
-    });
-    acc.push(CompletionItem {
-        label: "pub(crate)".to_string(),
-        lookup: None,
-        snippet: Some("pub(crate) $0".to_string()),
-    })
+    acc.push(
+        CompletionItem::new("Test function")
+            .lookup_by("tfn")
+            .snippet(
+                "#[test]\n\
+                 fn ${1:feature}() {\n\
+                 $0\n\
+                 }",
+            )
+            .kind(CompletionItemKind::Snippet),
+    );
+    acc.push(
+        CompletionItem::new("pub(crate)")
+            .snippet("pub(crate) $0")
+            .kind(CompletionItemKind::Snippet),
+    )
+}
+
+fn complete_item_keywords(acc: &mut Vec<CompletionItem>) {
+    acc.push(keyword("fn", "fn $1($2) {$0}"));
+    acc.push(keyword("struct", "struct $0"));
+    acc.push(keyword("enum", "enum $0"));
+    acc.push(keyword("trait", "trait $0"));
+    acc.push(keyword("impl", "impl $0"));
+    acc.push(keyword("mod", "mod $0"));
+    acc.push(keyword("use", "use $0;"));
 }
 
 fn complete_expr_keywords(
-    file: &SourceFileNode,
     fn_def: ast::FnDef,
     name_ref: ast::NameRef,
     acc: &mut Vec<CompletionItem>,
 ) {
-    acc.push(keyword("if", "if $0 {}"));
-    acc.push(keyword("match", "match $0 {}"));
-    acc.push(keyword("while", "while $0 {}"));
+    acc.push(keyword("if", "if $1 { $0 }"));
+    acc.push(keyword("match", "match $1 { $0 }"));
+    acc.push(keyword("while", "while $1 { $0 }"));
     acc.push(keyword("loop", "loop {$0}"));
 
-    if let Some(off) = name_ref.syntax().range().start().checked_sub(2.into()) {
-        if let Some(if_expr) = find_node_at_offset::<ast::IfExpr>(file.syntax(), off) {
-            if if_expr.syntax().range().end() < name_ref.syntax().range().start() {
-                acc.push(keyword("else", "else {$0}"));
-                acc.push(keyword("else if", "else if $0 {}"));
-            }
-        }
+    if is_after_if_without_else(name_ref) {
+        acc.push(keyword("else", "else {$0}"));
+        acc.push(keyword("else if", "else if $1 { $0 }"));
     }
     if is_in_loop_body(name_ref) {
         acc.push(keyword("continue", "continue"));
@@ -220,6 +233,50 @@ fn complete_expr_keywords(
     acc.extend(complete_return(fn_def, name_ref));
 }
 
+/// Checks if `name_ref` starts a statement which directly follows an `if`
+/// (or an `else if` chain) which has no final `else` yet.
+fn is_after_if_without_else(name_ref: ast::NameRef) -> bool {
+    let start = name_ref.syntax().range().start();
+    let stmt = name_ref
+        .syntax()
+        .ancestors()
+        .take_while(|it| it.range().start() == start)
+        .last()
+        .unwrap();
+    if stmt.parent().map(|it| it.kind()) != Some(BLOCK) {
+        return false;
+    }
+    let prev = match stmt.siblings_no_trivia(Direction::Prev).nth(1) {
+        Some(it) => it,
+        None => return false,
+    };
+    let prev = if prev.kind() == EXPR_STMT {
+        match prev.first_child() {
+            Some(it) => it,
+            None => return false,
+        }
+    } else {
+        prev
+    };
+    if prev.kind() != IF_EXPR {
+        return false;
+    }
+    let mut if_expr = prev;
+    loop {
+        if !has_else(if_expr) {
+            return true;
+        }
+        match if_expr.children().filter(|it| it.kind() == IF_EXPR).last() {
+            Some(nested) => if_expr = nested,
+            None => return false,
+        }
+    }
+}
+
+fn has_else(if_expr: SyntaxNodeRef) -> bool {
+    if_expr.children().any(|it| it.kind() == ELSE_KW)
+}
+
 fn is_in_loop_body(name_ref: ast::NameRef) -> bool {
     for node in name_ref.syntax().ancestors() {
         if node.kind() == FN_DEF || node.kind() == LAMBDA_EXPR {
@@ -273,22 +330,20 @@ fn complete_return(fn_def: ast::FnDef, name_ref: ast::NameRef) -> Option<Complet
 }
 
 fn keyword(kw: &str, snip: &str) -> CompletionItem {
-    CompletionItem {
-        label: kw.to_string(),
-        lookup: None,
-        snippet: Some(snip.to_string()),
-    }
+    CompletionItem::new(kw)
+        .snippet(snip)
+        .kind(CompletionItemKind::Keyword)
 }
 
 fn complete_expr_snippets(acc: &mut Vec<CompletionItem>) {
-    acc.push(CompletionItem {
-        label: "pd".to_string(),
-        lookup: None,
-        snippet: Some("eprintln!(\"$0 = {:?}\", $0);".to_string()),
-    });
-    acc.push(CompletionItem {
-        label: "ppd".to_string(),
-        lookup: None,
-        snippet: Some("eprintln!(\"$0 = {:#?}\", $0);".to_string()),
-    });
+    acc.push(
+        CompletionItem::new("pd")
+            .snippet("eprintln!(\"$0 = {:?}\", $0);")
+            .kind(CompletionItemKind::Snippet),
+    );
+    acc.push(
+        CompletionItem::new("ppd")
+            .snippet("eprintln!(\"$0 = {:#?}\", $0);")
+            .kind(CompletionItemKind::Snippet),
+    );
 }
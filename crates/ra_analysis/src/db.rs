@@ -1,18 +1,36 @@
+// `database_storage!` names its per-query enum variants after the query
+// methods.
+#![allow(non_camel_case_types)]
+
 use std::sync::Arc;
+#[cfg(test)]
+use parking_lot::Mutex;
+#[cfg(test)]
+use rustc_hash::FxHashMap;
 use salsa::{self, Database};
 use ra_db::{LocationIntener, BaseDatabase};
 use hir::{self, DefId, DefLoc};
 
 use crate::{
     symbol_index,
+    syntax_highlighting,
 };
 
 #[derive(Debug)]
 pub(crate) struct RootDatabase {
     runtime: salsa::Runtime<RootDatabase>,
     id_maps: Arc<IdMaps>,
+    /// Number of executions of each query and key, so that tests can check
+    /// that a change recomputes only the affected data.
+    #[cfg(test)]
+    query_counts: Arc<Mutex<FxHashMap<QueryDescriptor, usize>>>,
 }
 
+/// A query together with its key.
+#[cfg(test)]
+pub(crate) type QueryDescriptor =
+    <RootDatabase as salsa::plumbing::DatabaseStorageTypes>::QueryDescriptor;
+
 #[derive(Debug, Default)]
 struct IdMaps {
     defs: LocationIntener<DefLoc, DefId>,
@@ -22,6 +40,42 @@ impl salsa::Database for RootDatabase {
     fn salsa_runtime(&self) -> &salsa::Runtime<RootDatabase> {
         &self.runtime
     }
+
+    #[cfg(test)]
+    fn salsa_event(&self, event: impl Fn() -> salsa::Event<RootDatabase>) {
+        if let salsa::EventKind::WillExecute { descriptor } = event().kind {
+            *self.query_counts.lock().entry(descriptor).or_insert(0) += 1;
+        }
+    }
+}
+
+impl RootDatabase {
+    /// Human-readable summary of the database, which lists the executed
+    /// queries in test builds.
+    #[cfg(test)]
+    pub(crate) fn status(&self) -> String {
+        let mut counts: Vec<String> = self
+            .query_counts
+            .lock()
+            .iter()
+            .map(|(query, count)| format!("{:?}: {}\n", query.kind, count))
+            .collect();
+        counts.sort();
+        let mut res = String::from("executed queries:\n");
+        res.extend(counts);
+        res
+    }
+
+    #[cfg(not(test))]
+    pub(crate) fn status(&self) -> String {
+        "query counters are disabled".to_string()
+    }
+
+    /// How many times `query` was executed.
+    #[cfg(test)]
+    pub(crate) fn query_count(&self, query: &QueryDescriptor) -> usize {
+        self.query_counts.lock().get(query).cloned().unwrap_or(0)
+    }
 }
 
 impl Default for RootDatabase {
@@ -29,6 +83,8 @@ impl Default for RootDatabase {
         let mut db = RootDatabase {
             runtime: salsa::Runtime::default(),
             id_maps: Default::default(),
+            #[cfg(test)]
+            query_counts: Default::default(),
         };
         db.query_mut(ra_db::SourceRootQuery)
             .set(ra_db::WORKSPACE, Default::default());
@@ -45,6 +101,8 @@ impl salsa::ParallelDatabase for RootDatabase {
         salsa::Snapshot::new(RootDatabase {
             runtime: self.runtime.snapshot(self),
             id_maps: self.id_maps.clone(),
+            #[cfg(test)]
+            query_counts: self.query_counts.clone(),
         })
     }
 }
@@ -74,6 +132,9 @@ salsa::database_storage! {
             fn file_symbols() for symbol_index::FileSymbolsQuery;
             fn library_symbols() for symbol_index::LibrarySymbolsQuery;
         }
+        impl syntax_highlighting::HighlightingDatabase {
+            fn highlight() for syntax_highlighting::HighlightQuery;
+        }
         impl hir::db::HirDatabase {
             fn module_tree() for hir::db::ModuleTreeQuery;
             fn fn_scopes() for hir::db::FnScopesQuery;
@@ -86,3 +147,87 @@ salsa::database_storage! {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{mock_analysis::MockAnalysis, AnalysisChange, AnalysisHost, Query};
+
+    use super::*;
+
+    /// The descriptor of a query call, like `query!(source_file(file_id))`.
+    macro_rules! query {
+        ($query:ident($key:expr)) => {
+            QueryDescriptor {
+                kind: __SalsaQueryDescriptorKind::$query($key),
+            }
+        };
+    }
+
+    fn count(host: &AnalysisHost, query: QueryDescriptor) -> usize {
+        host.analysis().imp.db.query_count(&query)
+    }
+
+    #[test]
+    fn test_highlighting_unchanged_file_is_cached() {
+        let mock = MockAnalysis::with_files(
+            "
+            //- /lib.rs
+            fn main() { let mut x = 92; }
+        ",
+        );
+        let file_id = mock.id_of("/lib.rs");
+        let mut host = mock.analysis_host();
+
+        let first = host.analysis().highlight(file_id).unwrap();
+        let second = host.analysis().highlight(file_id).unwrap();
+        assert_eq!(count(&host, query!(highlight(file_id))), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let mut change = AnalysisChange::new();
+        change.change_file(file_id, "fn main() { let x = 92; }".to_string());
+        host.apply_change(change);
+
+        let third = host.analysis().highlight(file_id).unwrap();
+        assert_eq!(count(&host, query!(highlight(file_id))), 2);
+        assert_ne!(first, third);
+    }
+
+    #[test]
+    fn test_editing_file_does_not_reparse_other_files() {
+        let mock = MockAnalysis::with_files(
+            "
+            //- /lib.rs
+            mod foo;
+            fn main() {}
+            //- /foo.rs
+            struct Foo;
+        ",
+        );
+        let lib_file = mock.id_of("/lib.rs");
+        let foo_file = mock.id_of("/foo.rs");
+        let mut host = mock.analysis_host();
+        let search = |host: &AnalysisHost| {
+            host.analysis()
+                .symbol_search(Query::new("Foo".to_string()))
+                .unwrap()
+        };
+
+        search(&host);
+        assert_eq!(count(&host, query!(source_file(lib_file))), 1);
+        assert_eq!(count(&host, query!(source_file(foo_file))), 1);
+        assert_eq!(count(&host, query!(file_symbols(foo_file))), 1);
+
+        let mut change = AnalysisChange::new();
+        change.change_file(lib_file, "mod foo;\nfn main() { 92 }".to_string());
+        host.apply_change(change);
+
+        search(&host);
+        assert_eq!(count(&host, query!(source_file(lib_file))), 2);
+        assert_eq!(count(&host, query!(source_file(foo_file))), 1);
+        assert_eq!(count(&host, query!(file_symbols(foo_file))), 1);
+        let status = host.analysis().status();
+        assert!(status.contains(&format!("source_file({:?}): 1", foo_file)));
+    }
+}
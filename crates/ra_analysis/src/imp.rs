@@ -3,14 +3,16 @@ use std::{
     sync::Arc,
 };
 
-use ra_editor::{self, find_node_at_offset, FileSymbol, LineIndex, LocalEdit};
+use ra_editor::{self, find_node_at_offset, FileSymbol, HighlightedRange, LineIndex, LocalEdit};
+use ra_text_edit::{AtomTextEdit, TextEdit, TextEditBuilder};
 use ra_syntax::{
-    ast::{self, ArgListOwner, Expr, NameOwner},
-    AstNode, SourceFileNode,
-    SyntaxKind::*,
+    algo::{find_leaf_at_offset, find_token_at_offset, generate, Bias},
+    ast::{self, ArgListOwner, Expr, FnDefOwner, NameOwner},
+    AstNode, SmolStr, SourceFileNode,
+    SyntaxKind::{self, *},
     SyntaxNodeRef, TextRange, TextUnit,
 };
-use ra_db::{FilesDatabase, SourceRoot, SourceRootId, WORKSPACE, SyntaxDatabase};
+use ra_db::{BaseDatabase, FilesDatabase, SourceRoot, SourceRootId, WORKSPACE, SyntaxDatabase};
 use rayon::prelude::*;
 use rustc_hash::FxHashSet;
 use salsa::{Database, ParallelDatabase};
@@ -22,12 +24,14 @@ use hir::{
 };
 
 use crate::{
-    completion::{completions, CompletionItem},
+    completion::{completions, CompletionItem, CompletionItemKind},
     db,
+    ssr::{SsrError, SsrQuery},
     symbol_index::{SymbolIndex, SymbolsDatabase},
-    AnalysisChange, Cancelable, CrateId, Diagnostic, FileId,
+    syntax_highlighting::HighlightingDatabase,
+    AnalysisChange, MemoryUsage, CallItem, Cancelable, CrateId, Diagnostic, DocFormat, FileId,
     FileSystemEdit, FilePosition, Query, SourceChange, SourceFileNodeEdit,
-    ReferenceResolution,
+    ReferenceResolution, RenameError, SsrResult,
 };
 
 #[derive(Debug, Default)]
@@ -152,11 +156,52 @@ impl AnalysisImpl {
     pub fn file_syntax(&self, file_id: FileId) -> SourceFileNode {
         self.db.source_file(file_id)
     }
+    pub fn highlight(&self, file_id: FileId) -> Cancelable<Arc<Vec<HighlightedRange>>> {
+        self.db.check_canceled()?;
+        Ok(self.db.highlight(file_id))
+    }
+    pub fn file_text(&self, file_id: FileId) -> Arc<String> {
+        self.db.file_text(file_id)
+    }
     pub fn file_line_index(&self, file_id: FileId) -> Arc<LineIndex> {
         self.db.file_lines(file_id)
     }
+    pub fn status(&self) -> String {
+        self.db.status()
+    }
+    pub fn memory_usage(&self) -> Cancelable<MemoryUsage> {
+        let mut res = MemoryUsage::default();
+        let mut files: Vec<FileId> = self
+            .db
+            .source_root(WORKSPACE)
+            .files
+            .iter()
+            .cloned()
+            .collect();
+        files.sort();
+        for &file_id in files.iter() {
+            self.db.check_canceled()?;
+            res.file_texts += self.db.file_text(file_id).len();
+            res.syntax_trees += self.db.source_file(file_id).memory_size();
+            res.symbol_index += self.db.file_symbols(file_id)?.memory_size();
+        }
+        // Library files are parsed only to build their symbol index, don't
+        // parse them again just to measure the trees.
+        for &lib_id in self.db.libraries().iter() {
+            self.db.check_canceled()?;
+            res.symbol_index += self.db.library_symbols(lib_id).memory_size();
+            for &file_id in self.db.source_root(lib_id).files.iter() {
+                res.file_texts += self.db.file_text(file_id).len();
+            }
+        }
+        Ok(res)
+    }
     pub fn world_symbols(&self, query: Query) -> Cancelable<Vec<(FileId, FileSymbol)>> {
-        let buf: Vec<Arc<SymbolIndex>> = if query.libs {
+        let buf = self.symbol_indices(query.libs);
+        Ok(query.search(&buf))
+    }
+    fn symbol_indices(&self, libs: bool) -> Vec<Arc<SymbolIndex>> {
+        if libs {
             self.db
                 .libraries()
                 .iter()
@@ -179,8 +224,7 @@ impl AnalysisImpl {
                 .map_with(snap, |db, &file_id| db.0.file_symbols(file_id))
                 .filter_map(|it| it.ok())
                 .collect()
-        };
-        Ok(query.search(&buf))
+        }
     }
     /// This returns `Vec` because a module may be included from several places. We
     /// don't handle this case yet though, so the Vec has length at most one.
@@ -202,6 +246,253 @@ impl AnalysisImpl {
         };
         Ok(vec![(file_id, sym)])
     }
+    /// For a method declared in a trait, finds the methods implementing it in
+    /// all `impl Trait for Type` blocks of the workspace. Impls are matched by
+    /// the trait's name only.
+    pub fn trait_method_impls(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        let file = self.db.source_file(position.file_id);
+        let syntax = file.syntax();
+        let fn_def = match find_node_at_offset::<ast::FnDef>(syntax, position.offset) {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let trait_def = match fn_def
+            .syntax()
+            .ancestors()
+            .nth(2)
+            .and_then(ast::TraitDef::cast)
+        {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let (trait_name, method_name) = match (trait_def.name(), fn_def.name()) {
+            (Some(trait_name), Some(method_name)) => (trait_name.text(), method_name.text()),
+            _ => return Ok(Vec::new()),
+        };
+        let mut files = self
+            .db
+            .source_root(WORKSPACE)
+            .files
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        files.sort();
+        let mut res = Vec::new();
+        for file_id in files {
+            self.db.check_canceled()?;
+            let file = self.db.source_file(file_id);
+            let impls = file
+                .syntax()
+                .descendants()
+                .filter_map(ast::ImplItem::cast)
+                .filter(|impl_item| {
+                    impl_item.target_trait().and_then(type_ref_name) == Some(trait_name.clone())
+                });
+            for impl_item in impls {
+                let methods = impl_item
+                    .item_list()
+                    .into_iter()
+                    .flat_map(|it| it.functions())
+                    .filter_map(|it| it.name())
+                    .filter(|name| name.text() == method_name);
+                for name in methods {
+                    let symbol = FileSymbol {
+                        name: name.text(),
+                        node_range: name.syntax().range(),
+                        kind: FN_DEF,
+                    };
+                    res.push((file_id, symbol));
+                }
+            }
+        }
+        Ok(res)
+    }
+    /// Dispatches on the item at `position`: for a trait or a type, finds the
+    /// impl blocks of the trait or for the type, for a trait method, the
+    /// methods implementing it.
+    pub fn goto_implementation(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        let (file_id, symbol) = match self.find_item_decl(position)? {
+            None => return Ok(Vec::new()),
+            Some((_name, decl)) => decl,
+        };
+        match symbol.kind {
+            FN_DEF => {
+                let file = self.db.source_file(file_id);
+                match symbol.name_range(&file) {
+                    None => Ok(Vec::new()),
+                    Some(range) => self.trait_method_impls(FilePosition {
+                        file_id,
+                        offset: range.start(),
+                    }),
+                }
+            }
+            TRAIT_DEF => self.find_impls(|impl_item| {
+                impl_item.target_trait().and_then(type_ref_name) == Some(symbol.name.clone())
+            }),
+            STRUCT_DEF | ENUM_DEF => self.find_impls(|impl_item| {
+                impl_item.target_type().and_then(type_ref_name) == Some(symbol.name.clone())
+            }),
+            _ => Ok(Vec::new()),
+        }
+    }
+    /// Impl blocks of the workspace for which `matches` holds, named after
+    /// their target type.
+    fn find_impls(
+        &self,
+        matches: impl Fn(ast::ImplItem) -> bool,
+    ) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        let mut files = self
+            .db
+            .source_root(WORKSPACE)
+            .files
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        files.sort();
+        let mut res = Vec::new();
+        for file_id in files {
+            self.db.check_canceled()?;
+            let file = self.db.source_file(file_id);
+            for impl_item in file.syntax().descendants().filter_map(ast::ImplItem::cast) {
+                if !matches(impl_item) {
+                    continue;
+                }
+                let name = match impl_item.target_type().and_then(type_ref_name) {
+                    None => continue,
+                    Some(it) => it,
+                };
+                let symbol = FileSymbol {
+                    name,
+                    node_range: impl_item.syntax().range(),
+                    kind: IMPL_ITEM,
+                };
+                res.push((file_id, symbol));
+            }
+        }
+        Ok(res)
+    }
+    /// For a local variable, finds the declaration of its type. The type is
+    /// taken from the annotation of the binding, or from a struct literal
+    /// initializing it, as there's no type inference yet.
+    pub fn goto_type_definition(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        let file = self.db.source_file(position.file_id);
+        let syntax = file.syntax();
+        let binding = match find_node_at_offset::<ast::BindPat>(syntax, position.offset) {
+            Some(it) => Some(it),
+            None => match self.approximately_resolve_symbol(position)? {
+                Some(rr) => rr
+                    .resolves_to
+                    .into_iter()
+                    .find(|(file_id, symbol)| *file_id == position.file_id && symbol.kind == NAME)
+                    .and_then(|(_, symbol)| {
+                        find_node_at_offset::<ast::BindPat>(syntax, symbol.node_range.end())
+                    }),
+                None => None,
+            },
+        };
+        let (binding, type_path) = match binding.and_then(|it| Some((it, binding_type_path(it)?))) {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let type_path = match hir::Path::from_ast(type_path) {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let module = match source_binder::module_from_child_node(
+            &*self.db,
+            position.file_id,
+            binding.syntax(),
+        )? {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let def_id = match module.resolve_path(&*self.db, type_path)? {
+            Some(it) => it,
+            None => return Ok(Vec::new()),
+        };
+        let (file_id, syntax) = def_id.source(&*self.db);
+        let node = syntax.borrowed();
+        match node.kind() {
+            STRUCT_DEF | ENUM_DEF | TYPE_DEF => (),
+            _ => return Ok(Vec::new()),
+        }
+        let name = match node.children().find_map(ast::Name::cast) {
+            Some(it) => it.text(),
+            None => return Ok(Vec::new()),
+        };
+        let symbol = FileSymbol {
+            name,
+            node_range: node.range(),
+            kind: node.kind(),
+        };
+        Ok(vec![(file_id, symbol)])
+    }
+    pub fn external_docs(&self, position: FilePosition) -> Cancelable<Option<String>> {
+        let rr = ctry!(self.approximately_resolve_symbol(position)?);
+        let (file_id, symbol) = ctry!(rr.resolves_to.into_iter().next());
+        let kind = match symbol.kind {
+            STRUCT_DEF => "struct",
+            ENUM_DEF => "enum",
+            FN_DEF => "fn",
+            TRAIT_DEF => "trait",
+            TYPE_DEF => "type",
+            CONST_DEF => "constant",
+            STATIC_DEF => "static",
+            _ => return Ok(None),
+        };
+        let file = self.db.source_file(file_id);
+        let item = ctry!(file
+            .syntax()
+            .covering_node(symbol.node_range)
+            .ancestors()
+            .find(|it| it.kind() == symbol.kind));
+        if !is_pub(item) {
+            return Ok(None);
+        }
+        let module = ctry!(source_binder::module_from_child_node(
+            &*self.db,
+            file_id,
+            ctry!(item.parent())
+        )?);
+        let mut path = Vec::new();
+        for module in generate(Some(module.clone()), |it| it.parent()) {
+            if let Some((_, decl)) = module.parent_link_source(&*self.db) {
+                if !is_pub(decl.borrowed().syntax()) {
+                    return Ok(None);
+                }
+                path.push(ctry!(module.name()));
+            }
+        }
+        path.reverse();
+        let crate_graph = self.db.crate_graph();
+        let crate_root = module.crate_root().source().file_id();
+        let crate_id = ctry!(crate_graph.crate_id_for_crate_root(crate_root));
+        let crate_name = ctry!(crate_graph.crate_name(crate_id));
+        let mut url = match crate_name.as_str() {
+            "std" | "core" | "alloc" | "proc_macro" | "test" => {
+                format!("https://doc.rust-lang.org/{}/", crate_name)
+            }
+            _ => {
+                let version = ctry!(crate_graph.crate_version(crate_id));
+                format!("https://docs.rs/{0}/{1}/{0}/", crate_name, version)
+            }
+        };
+        for segment in path {
+            url.push_str(&segment);
+            url.push('/');
+        }
+        url.push_str(&format!("{}.{}.html", kind, symbol.name));
+        Ok(Some(url))
+    }
     /// Returns `Vec` for the same reason as `parent_module`
     pub fn crate_for(&self, file_id: FileId) -> Cancelable<Vec<CrateId>> {
         let descr = match source_binder::module_from_file_id(&*self.db, file_id)? {
@@ -219,7 +510,151 @@ impl AnalysisImpl {
         self.db.crate_graph().crate_root(crate_id)
     }
     pub fn completions(&self, position: FilePosition) -> Cancelable<Option<Vec<CompletionItem>>> {
-        completions(&self.db, position)
+        let mut res = ctry!(completions(&self.db, position)?);
+        self.complete_imports(position, &mut res)?;
+        Ok(Some(res))
+    }
+    /// Completes items which are not in scope yet, together with an edit
+    /// adding a `use` for them.
+    fn complete_imports(
+        &self,
+        position: FilePosition,
+        acc: &mut Vec<CompletionItem>,
+    ) -> Cancelable<()> {
+        let file = self.db.source_file(position.file_id);
+        let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
+            None => return Ok(()),
+            Some(it) => it,
+        };
+        let is_unqualified_path = name_ref
+            .syntax()
+            .parent()
+            .and_then(ast::PathSegment::cast)
+            .map(|it| it.parent_path())
+            .map_or(false, |it| it.qualifier().is_none());
+        let in_use_item = name_ref
+            .syntax()
+            .ancestors()
+            .any(|it| it.kind() == USE_ITEM);
+        // The `use` is added at the top of the file, which is wrong inside
+        // an inline module.
+        let in_inline_module = name_ref
+            .syntax()
+            .ancestors()
+            .filter_map(ast::Module::cast)
+            .any(|it| !it.has_semi());
+        if !is_unqualified_path || in_use_item || in_inline_module {
+            return Ok(());
+        }
+        let prefix = name_ref
+            .syntax()
+            .text()
+            .slice(..position.offset)
+            .to_string();
+        if prefix.is_empty() {
+            return Ok(());
+        }
+        let module = match source_binder::module_from_position(&*self.db, position)? {
+            None => return Ok(()),
+            Some(it) => it,
+        };
+        let in_scope: FxHashSet<String> = acc.iter().map(|it| it.label.clone()).collect();
+        // Of the libraries, only the dependencies of the crate can be
+        // imported from.
+        let mut dep_roots = FxHashSet::default();
+        if let Some(krate) = module.krate(&*self.db) {
+            for dep in krate.dependencies(&*self.db) {
+                if let Some(root) = dep.krate.root_module(&*self.db)? {
+                    dep_roots.insert(self.db.file_source_root(root.source().file_id()));
+                }
+            }
+        }
+        let mut indices = self.symbol_indices(false);
+        indices.extend(
+            self.db
+                .libraries()
+                .iter()
+                .filter(|id| dep_roots.contains(id))
+                .map(|&id| self.db.library_symbols(id)),
+        );
+        let mut query = Query::new(prefix);
+        query.prefix();
+        query.limit(64);
+        let symbols = query.search(&indices);
+        let mut seen = FxHashSet::default();
+        for (file_id, symbol) in symbols {
+            self.db.check_canceled()?;
+            if in_scope.contains(symbol.name.as_str()) {
+                continue;
+            }
+            let path = match self.import_path(&module, file_id, &symbol)? {
+                None => continue,
+                Some(it) => it,
+            };
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            let mut item = CompletionItem::new(symbol.name.as_str())
+                .additional_edit(add_use_item(&file, &path))
+                .source(file_id, symbol.node_range)
+                .detail(Some(path));
+            if symbol.kind == FN_DEF {
+                item = item.kind(CompletionItemKind::Function);
+            }
+            acc.push(item);
+        }
+        Ok(())
+    }
+    /// Path by which `symbol` can be imported into `module`: `crate::` for
+    /// the items of the same crate and the name of the dependency for the
+    /// items of other crates. Returns `None` if `symbol` is not a module
+    /// level item, or if it lives in `module` itself.
+    fn import_path(
+        &self,
+        module: &hir::Module,
+        file_id: FileId,
+        symbol: &FileSymbol,
+    ) -> Cancelable<Option<String>> {
+        let symbol_file = self.db.source_file(file_id);
+        let item = symbol_file.syntax().covering_node(symbol.node_range);
+        let parent = ctry!(item.parent());
+        let is_module_item = match parent.kind() {
+            SOURCE_FILE => true,
+            ITEM_LIST => parent.parent().map(|it| it.kind()) == Some(MODULE),
+            _ => false,
+        };
+        if !is_module_item {
+            return Ok(None);
+        }
+        let item_module = ctry!(source_binder::module_from_child_node(
+            &*self.db, file_id, parent
+        )?);
+        if item_module.def_id(&*self.db) == module.def_id(&*self.db) {
+            return Ok(None);
+        }
+        let item_crate_root = item_module.crate_root().def_id(&*self.db);
+        let krate = if item_crate_root == module.crate_root().def_id(&*self.db) {
+            SmolStr::new("crate")
+        } else {
+            let mut dep_name = None;
+            for dep in ctry!(module.krate(&*self.db)).dependencies(&*self.db) {
+                if let Some(root) = dep.krate.root_module(&*self.db)? {
+                    if root.def_id(&*self.db) == item_crate_root {
+                        dep_name = Some(dep.name);
+                        break;
+                    }
+                }
+            }
+            ctry!(dep_name)
+        };
+        let mut segments: Vec<SmolStr> = generate(Some(item_module), |it| it.parent())
+            .filter_map(|it| it.name())
+            .collect();
+        segments.push(krate);
+        segments.reverse();
+        segments.push(symbol.name.clone());
+        let segments: Vec<&str> = segments.iter().map(|it| it.as_str()).collect();
+        Ok(Some(segments.join("::")))
     }
     pub fn approximately_resolve_symbol(
         &self,
@@ -229,6 +664,13 @@ impl AnalysisImpl {
         let syntax = file.syntax();
         if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, position.offset) {
             let mut rr = ReferenceResolution::new(name_ref.syntax().range());
+            // In `Foo { bar }` the name is both a local use and a field
+            let shorthand_field = match name_ref.syntax().parent() {
+                Some(field) if field.kind() == NAMED_FIELD && is_shorthand(field) => {
+                    self.field_def(position.file_id, field, name_ref.text())?
+                }
+                _ => None,
+            };
             if let Some(fn_descr) = source_binder::function_from_child_node(
                 &*self.db,
                 position.file_id,
@@ -245,9 +687,16 @@ impl AnalysisImpl {
                             kind: NAME,
                         },
                     );
+                    if let Some((file_id, symbol)) = shorthand_field {
+                        rr.add_resolution(file_id, symbol);
+                    }
                     return Ok(Some(rr));
                 };
             }
+            if let Some((file_id, symbol)) = shorthand_field {
+                rr.add_resolution(file_id, symbol);
+                return Ok(Some(rr));
+            }
             // If that fails try the index based approach.
             for (file_id, symbol) in self.index_resolve(name_ref)? {
                 rr.add_resolution(file_id, symbol);
@@ -278,15 +727,155 @@ impl AnalysisImpl {
                     }
                 }
             }
+            // In `let Foo { bar } = foo;` the name is both a binding and a field
+            if let Some(bind_pat) = name.syntax().parent().filter(|it| it.kind() == BIND_PAT) {
+                if bind_pat.parent().map(|it| it.kind()) == Some(FIELD_PAT_LIST) {
+                    let name_text = name.text();
+                    if let Some((file_id, symbol)) =
+                        self.field_def(position.file_id, bind_pat, name_text.clone())?
+                    {
+                        rr.add_resolution(
+                            position.file_id,
+                            FileSymbol {
+                                name: name_text,
+                                node_range: bind_pat.range(),
+                                kind: NAME,
+                            },
+                        );
+                        rr.add_resolution(file_id, symbol);
+                        return Ok(Some(rr));
+                    }
+                }
+            }
         }
         Ok(None)
     }
 
+    /// Finds the declaration of the field `name` for a field of a struct
+    /// literal or struct pattern.
+    fn field_def(
+        &self,
+        file_id: FileId,
+        field: SyntaxNodeRef,
+        name: SmolStr,
+    ) -> Cancelable<Option<(FileId, FileSymbol)>> {
+        let path = ctry!(field
+            .parent()
+            .and_then(|list| list.parent())
+            .filter(|it| it.kind() == STRUCT_LIT || it.kind() == STRUCT_PAT)
+            .and_then(|it| it.children().find_map(ast::Path::cast))
+            .and_then(hir::Path::from_ast));
+        let module = ctry!(source_binder::module_from_child_node(
+            &*self.db, file_id, field
+        )?);
+        let def_id = ctry!(module.resolve_path(&*self.db, path)?);
+        let (def_file_id, syntax) = def_id.source(&*self.db);
+        let struct_def = ctry!(ast::StructDef::cast(syntax.borrowed()));
+        let field_def = ctry!(struct_def
+            .named_fields()
+            .find(|it| it.name().map(|it| it.text()) == Some(name.clone())));
+        let symbol = FileSymbol {
+            name,
+            node_range: field_def.syntax().range(),
+            kind: NAMED_FIELD_DEF,
+        };
+        Ok(Some((def_file_id, symbol)))
+    }
+
+    pub fn ssr(
+        &self,
+        pattern: &str,
+        replacement: &str,
+        dry_run: bool,
+    ) -> Cancelable<Result<SsrResult, SsrError>> {
+        let query = match SsrQuery::new(pattern, replacement) {
+            Ok(query) => query,
+            Err(e) => return Ok(Err(e)),
+        };
+        let mut files = self
+            .db
+            .source_root(WORKSPACE)
+            .files
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        files.sort();
+        let mut matches = Vec::new();
+        let mut source_file_edits = Vec::new();
+        for file_id in files {
+            self.db.check_canceled()?;
+            let file = self.db.source_file(file_id);
+            let file_matches = query.find_matches(&file);
+            if dry_run {
+                matches.extend(file_matches.iter().map(|m| (file_id, m.range)));
+            } else if !file_matches.is_empty() {
+                let mut edit = TextEditBuilder::new();
+                for m in file_matches.iter() {
+                    edit.replace(m.range, query.replacement_for(m));
+                }
+                source_file_edits.push(SourceFileNodeEdit {
+                    file_id,
+                    edits: edit.finish().into_atoms(),
+                });
+            }
+        }
+        let res = if dry_run {
+            SsrResult::Matches(matches)
+        } else {
+            SsrResult::Change(SourceChange {
+                label: "structural replace".to_string(),
+                source_file_edits,
+                file_system_edits: Vec::new(),
+                cursor_position: None,
+            })
+        };
+        Ok(Ok(res))
+    }
+
+    pub fn ssr_at(
+        &self,
+        file_id: FileId,
+        range: TextRange,
+        pattern: &str,
+        replacement: &str,
+    ) -> Cancelable<Result<Option<SourceChange>, SsrError>> {
+        let query = match SsrQuery::new(pattern, replacement) {
+            Ok(query) => query,
+            Err(e) => return Ok(Err(e)),
+        };
+        let file = self.db.source_file(file_id);
+        let m = match query
+            .find_matches(&file)
+            .into_iter()
+            .find(|m| range.is_subrange(&m.range))
+        {
+            None => return Ok(Ok(None)),
+            Some(m) => m,
+        };
+        let mut edit = TextEditBuilder::new();
+        edit.replace(m.range, query.replacement_for(&m));
+        Ok(Ok(Some(SourceChange {
+            label: "apply SSR here".to_string(),
+            source_file_edits: vec![SourceFileNodeEdit {
+                file_id,
+                edits: edit.finish().into_atoms(),
+            }],
+            file_system_edits: Vec::new(),
+            cursor_position: None,
+        })))
+    }
+
     pub fn find_all_refs(&self, position: FilePosition) -> Cancelable<Vec<(FileId, TextRange)>> {
         let file = self.db.source_file(position.file_id);
+        if let Some(lifetime) = lifetime_at_offset(file.syntax(), position.offset) {
+            return Ok(lifetime_refs(lifetime)
+                .into_iter()
+                .map(|range| (position.file_id, range))
+                .collect());
+        }
         // Find the binding associated with the offset
         let (binding, descr) = match find_binding(&self.db, &file, position)? {
-            None => return Ok(Vec::new()),
+            None => return self.find_all_item_refs(position),
             Some(it) => it,
         };
 
@@ -332,6 +921,349 @@ impl AnalysisImpl {
         }
     }
 
+    pub fn prepare_rename(&self, position: FilePosition) -> Cancelable<Option<TextRange>> {
+        let file = self.db.source_file(position.file_id);
+        let syntax = file.syntax();
+        let range = if let Some(lifetime) = lifetime_at_offset(syntax, position.offset) {
+            lifetime.range()
+        } else if let Some(name) = find_node_at_offset::<ast::Name>(syntax, position.offset) {
+            name.syntax().range()
+        } else if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, position.offset)
+        {
+            name_ref.syntax().range()
+        } else {
+            return Ok(None);
+        };
+        // The first reference is the declaration, which we can only rename if
+        // it's in the workspace.
+        let decl_file_id = match self.find_all_refs(position)?.first() {
+            None => return Ok(None),
+            Some(&(file_id, _)) => file_id,
+        };
+        if !self.db.source_root(WORKSPACE).files.contains(&decl_file_id) {
+            return Ok(None);
+        }
+        Ok(Some(range))
+    }
+
+    pub fn rename(
+        &self,
+        position: FilePosition,
+        new_name: &str,
+    ) -> Cancelable<Result<Option<SourceChange>, RenameError>> {
+        if new_name.is_empty() {
+            return Ok(Err(RenameError("new name cannot be empty".to_string())));
+        }
+        let file = self.db.source_file(position.file_id);
+        let is_self =
+            find_leaf_at_offset(file.syntax(), position.offset).any(|leaf| leaf.kind() == SELF_KW);
+        if is_self {
+            return Ok(Err(RenameError("`self` can't be renamed".to_string())));
+        }
+        let new_name = if lifetime_at_offset(file.syntax(), position.offset).is_some() {
+            if new_name.starts_with('\'') {
+                new_name.to_string()
+            } else {
+                format!("'{}", new_name)
+            }
+        } else if ["self", "Self", "super", "crate"].contains(&new_name) {
+            // These can't be raw identifiers.
+            let msg = format!("`{}` is not a valid name", new_name);
+            return Ok(Err(RenameError(msg)));
+        } else if SyntaxKind::from_keyword(new_name).is_some() {
+            // Keywords are only valid names when written as raw identifiers.
+            format!("r#{}", new_name)
+        } else {
+            new_name.to_string()
+        };
+        let refs = self.find_all_refs(position)?;
+        if refs.is_empty() {
+            return Ok(Ok(None));
+        }
+        let mut source_file_edits: Vec<SourceFileNodeEdit> = Vec::new();
+        for (file_id, range) in refs {
+            let edit = AtomTextEdit::replace(range, new_name.clone());
+            match source_file_edits
+                .iter_mut()
+                .find(|it| it.file_id == file_id)
+            {
+                Some(it) => it.edits.push(edit),
+                None => source_file_edits.push(SourceFileNodeEdit {
+                    file_id,
+                    edits: vec![edit],
+                }),
+            }
+        }
+        Ok(Ok(Some(SourceChange {
+            label: "rename".to_string(),
+            source_file_edits,
+            file_system_edits: Vec::new(),
+            cursor_position: None,
+        })))
+    }
+
+    /// Finds references to an item (as opposed to a local variable) across
+    /// all workspace files. The declaration comes first, and references from
+    /// the same file are adjacent to each other.
+    fn find_all_item_refs(&self, position: FilePosition) -> Cancelable<Vec<(FileId, TextRange)>> {
+        let (name, decl) = match self.find_item_decl(position)? {
+            None => return Ok(Vec::new()),
+            Some(it) => it,
+        };
+        let decl_file = self.db.source_file(decl.0);
+        let decl_name_range = decl_file
+            .syntax()
+            .covering_node(decl.1.node_range)
+            .descendants()
+            .find_map(ast::Name::cast)
+            .map(|it| it.syntax().range())
+            .unwrap_or(decl.1.node_range);
+        let mut res = vec![(decl.0, decl_name_range)];
+
+        let mut files: Vec<FileId> = self
+            .db
+            .source_root(WORKSPACE)
+            .files
+            .iter()
+            .cloned()
+            // Cheap textual check to skip files which can't contain a reference.
+            .filter(|&file_id| self.db.file_text(file_id).contains(name.as_str()))
+            .collect();
+        files.sort();
+        if let Some(idx) = files.iter().position(|&it| it == decl.0) {
+            let decl_file_id = files.remove(idx);
+            files.insert(0, decl_file_id);
+        }
+
+        for file_id in files {
+            self.db.check_canceled()?;
+            let file = self.db.source_file(file_id);
+            let name_refs = file
+                .syntax()
+                .descendants()
+                .filter_map(ast::NameRef::cast)
+                .filter(|it| it.text() == name);
+            for name_ref in name_refs {
+                let position = FilePosition {
+                    file_id,
+                    offset: name_ref.syntax().range().start(),
+                };
+                let rr = match self.approximately_resolve_symbol(position)? {
+                    None => continue,
+                    Some(it) => it,
+                };
+                let is_ref = rr
+                    .resolves_to
+                    .iter()
+                    .any(|(file_id, symbol)| (*file_id, symbol) == (decl.0, &decl.1));
+                if is_ref {
+                    res.push((file_id, name_ref.syntax().range()));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Finds the declaration of the item under cursor, which might be either
+    /// the name of the item itself, or a reference to it.
+    fn find_item_decl(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<(SmolStr, (FileId, FileSymbol))>> {
+        let file = self.db.source_file(position.file_id);
+        let syntax = file.syntax();
+        if let Some(name) = find_node_at_offset::<ast::Name>(syntax, position.offset) {
+            let item = ctry!(name.syntax().parent());
+            let symbol = ctry!(ra_editor::file_symbols(&file)
+                .into_iter()
+                .find(|it| it.node_range == item.range() && it.kind == item.kind()));
+            return Ok(Some((name.text(), (position.file_id, symbol))));
+        }
+        let name_ref = ctry!(find_node_at_offset::<ast::NameRef>(syntax, position.offset));
+        let rr = ctry!(self.approximately_resolve_symbol(position)?);
+        let decl = ctry!(rr.resolves_to.into_iter().next());
+        Ok(Some((name_ref.text(), decl)))
+    }
+
+    pub fn incoming_calls(&self, position: FilePosition) -> Cancelable<Vec<CallItem>> {
+        let (file_id, symbol) = match self.fn_at_position(position)? {
+            None => return Ok(Vec::new()),
+            Some(it) => it,
+        };
+        let file = self.db.source_file(file_id);
+        let name = match file
+            .syntax()
+            .covering_node(symbol.node_range)
+            .descendants()
+            .find_map(ast::Name::cast)
+        {
+            None => return Ok(Vec::new()),
+            Some(it) => it,
+        };
+        let position = FilePosition {
+            file_id,
+            offset: name.syntax().range().start(),
+        };
+        let mut res = Vec::new();
+        // The first reference is the declaration itself.
+        for (file_id, range) in self.find_all_refs(position)?.into_iter().skip(1) {
+            let file = self.db.source_file(file_id);
+            let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), range.start()) {
+                Some(name_ref) if FnCallNode::is_callee(name_ref) => name_ref,
+                _ => continue,
+            };
+            let caller = match name_ref.syntax().ancestors().find_map(ast::FnDef::cast) {
+                None => continue,
+                Some(it) => it.syntax(),
+            };
+            let caller = ra_editor::file_symbols(&file)
+                .into_iter()
+                .find(|it| it.node_range == caller.range() && it.kind == FN_DEF);
+            if let Some(caller) = caller {
+                CallItem::add_call(&mut res, file_id, caller, name_ref.syntax().range());
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn outgoing_calls(&self, position: FilePosition) -> Cancelable<Vec<CallItem>> {
+        let (file_id, symbol) = match self.fn_at_position(position)? {
+            None => return Ok(Vec::new()),
+            Some(it) => it,
+        };
+        let file = self.db.source_file(file_id);
+        let fn_def = file.syntax().covering_node(symbol.node_range);
+        let mut res = Vec::new();
+        for name_ref in fn_def
+            .descendants()
+            .filter_map(FnCallNode::cast)
+            .filter_map(|call| call.name_ref())
+        {
+            let position = FilePosition {
+                file_id,
+                offset: name_ref.syntax().range().start(),
+            };
+            let rr = match self.approximately_resolve_symbol(position)? {
+                None => continue,
+                Some(it) => it,
+            };
+            for (callee_file_id, callee) in rr.resolves_to {
+                if callee.kind == FN_DEF {
+                    CallItem::add_call(&mut res, callee_file_id, callee, name_ref.syntax().range());
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Finds the `#[test]` functions which call the function at `position`,
+    /// directly or through other functions, following at most
+    /// `RELATED_TESTS_MAX_DEPTH` calls.
+    pub fn related_tests(&self, position: FilePosition) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        const RELATED_TESTS_MAX_DEPTH: usize = 8;
+
+        let start = match self.fn_at_position(position)? {
+            None => return Ok(Vec::new()),
+            Some(it) => it,
+        };
+        let mut visited = FxHashSet::default();
+        visited.insert(start.clone());
+        let mut frontier = vec![start];
+        let mut res = Vec::new();
+        for _ in 0..RELATED_TESTS_MAX_DEPTH {
+            let mut next_frontier = Vec::new();
+            for (file_id, symbol) in frontier {
+                let file = self.db.source_file(file_id);
+                let offset = match symbol.name_range(&file) {
+                    None => continue,
+                    Some(it) => it.start(),
+                };
+                for caller in self.incoming_calls(FilePosition { file_id, offset })? {
+                    let caller = (caller.file_id, caller.symbol);
+                    if !visited.insert(caller.clone()) {
+                        continue;
+                    }
+                    let file = self.db.source_file(caller.0);
+                    let is_test = file
+                        .syntax()
+                        .covering_node(caller.1.node_range)
+                        .ancestors()
+                        .find_map(ast::FnDef::cast)
+                        .map_or(false, |it| it.has_atom_attr("test"));
+                    if is_test {
+                        res.push(caller);
+                    } else {
+                        next_frontier.push(caller);
+                    }
+                }
+            }
+            if next_frontier.is_empty() {
+                break;
+            }
+            frontier = next_frontier;
+        }
+        Ok(res)
+    }
+
+    /// Dumps what the lowering knows about the function at `position`: its
+    /// signature and its scopes with their bindings.
+    pub fn view_hir(&self, position: FilePosition) -> Cancelable<String> {
+        let file = self.db.source_file(position.file_id);
+        let fn_def = match find_node_at_offset::<ast::FnDef>(file.syntax(), position.offset) {
+            None => return Ok("no function at the cursor".to_string()),
+            Some(it) => it,
+        };
+        let function =
+            match source_binder::function_from_source(&*self.db, position.file_id, fn_def)? {
+                None => return Ok("the function is not part of any module".to_string()),
+                Some(it) => it,
+            };
+        let fn_def = function.syntax(&*self.db);
+        let fn_def = fn_def.borrowed();
+        let mut buf = String::new();
+        let name = fn_def
+            .name()
+            .map_or(SmolStr::new("<missing>"), |it| it.text());
+        buf.push_str(&format!("fn {}\n", name));
+        if let Some(param_list) = fn_def.param_list() {
+            if let Some(self_param) = param_list.self_param() {
+                buf.push_str(&format!("  param {}\n", self_param.syntax().text()));
+            }
+            for param in param_list.params() {
+                buf.push_str(&format!("  param {}\n", param.syntax().text()));
+            }
+        }
+        if let Some(ret_type) = fn_def.ret_type() {
+            buf.push_str(&format!("  {}\n", ret_type.syntax().text()));
+        }
+        for line in function.scope(&*self.db).debug_dump().lines() {
+            buf.push_str(&format!("  {}\n", line));
+        }
+        Ok(buf)
+    }
+
+    /// Finds the function whose name (or a reference to it) is at `position`,
+    /// falling back to the function which contains `position`.
+    pub fn fn_at_position(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<(FileId, FileSymbol)>> {
+        if let Some((_name, decl)) = self.find_item_decl(position)? {
+            if decl.1.kind == FN_DEF {
+                return Ok(Some(decl));
+            }
+        }
+        let file = self.db.source_file(position.file_id);
+        let fn_def = ctry!(find_node_at_offset::<ast::FnDef>(
+            file.syntax(),
+            position.offset
+        ));
+        let symbol = ctry!(ra_editor::file_symbols(&file)
+            .into_iter()
+            .find(|it| it.node_range == fn_def.syntax().range() && it.kind == FN_DEF));
+        Ok(Some((position.file_id, symbol)))
+    }
+
     pub fn doc_comment_for(
         &self,
         file_id: FileId,
@@ -341,15 +1273,21 @@ impl AnalysisImpl {
 
         Ok(symbol.docs(&file))
     }
-    pub fn doc_text_for(&self, file_id: FileId, symbol: FileSymbol) -> Cancelable<Option<String>> {
+    pub fn doc_text_for(
+        &self,
+        file_id: FileId,
+        symbol: FileSymbol,
+        doc_format: DocFormat,
+    ) -> Cancelable<Option<String>> {
         let file = self.db.source_file(file_id);
-        let result = match (symbol.description(&file), symbol.docs(&file)) {
-            (Some(desc), Some(docs)) => {
-                Some("```rust\n".to_string() + &*desc + "\n```\n\n" + &*docs)
-            }
-            (Some(desc), None) => Some("```rust\n".to_string() + &*desc + "\n```"),
-            (None, Some(docs)) => Some(docs),
-            _ => None,
+        let description = symbol.description(&file).map(|desc| match doc_format {
+            DocFormat::Markdown => "```rust\n".to_string() + &*desc + "\n```",
+            DocFormat::PlainText => desc,
+        });
+        let result = match (description, symbol.docs(&file)) {
+            (Some(desc), Some(docs)) => Some(desc + "\n\n" + &*docs),
+            (Some(it), None) | (None, Some(it)) => Some(it),
+            (None, None) => None,
         };
 
         Ok(result)
@@ -363,7 +1301,9 @@ impl AnalysisImpl {
             .map(|d| Diagnostic {
                 range: d.range,
                 message: d.msg,
-                fix: None,
+                fix: d.fix.map(|fix| {
+                    SourceChange::from_local_edit(file_id, "insert missing delimiter", fix)
+                }),
             })
             .collect::<Vec<_>>();
         if let Some(m) = source_binder::module_from_file_id(&*self.db, file_id)? {
@@ -414,7 +1354,7 @@ impl AnalysisImpl {
         Ok(res)
     }
 
-    pub fn assists(&self, file_id: FileId, range: TextRange) -> Vec<SourceChange> {
+    pub fn assists(&self, file_id: FileId, range: TextRange) -> Cancelable<Vec<SourceChange>> {
         let file = self.file_syntax(file_id);
         let offset = range.start();
         let actions = vec![
@@ -427,17 +1367,145 @@ impl AnalysisImpl {
                 ra_editor::add_derive(&file, offset).map(|f| f()),
             ),
             ("add impl", ra_editor::add_impl(&file, offset).map(|f| f())),
+            (
+                "add `From` impl",
+                ra_editor::add_from_impl(&file, offset).map(|f| f()),
+            ),
             (
                 "introduce variable",
                 ra_editor::introduce_variable(&file, range).map(|f| f()),
             ),
+            (
+                "convert match to if let",
+                ra_editor::convert_match_to_if_let(&file, offset).map(|f| f()),
+            ),
+            (
+                "convert closure to fn",
+                ra_editor::convert_closure_to_fn(&file, offset).map(|f| f()),
+            ),
+            (
+                "convert fn to closure",
+                ra_editor::convert_fn_to_closure(&file, offset).map(|f| f()),
+            ),
+            (
+                "add explicit type (guessed from syntax)",
+                ra_editor::add_type_annotation(&file, offset).map(|f| f()),
+            ),
         ];
-        actions
+        let mut res: Vec<SourceChange> = actions
             .into_iter()
             .filter_map(|(name, local_edit)| {
                 Some(SourceChange::from_local_edit(file_id, name, local_edit?))
             })
-            .collect()
+            .collect();
+        res.extend(self.qualify_path_assists(FilePosition { file_id, offset })?);
+        Ok(res)
+    }
+
+    /// For an unqualified name which is not in scope, offers to either replace
+    /// it with the fully-qualified path of the item it approximately resolves
+    /// to, or to import that path with a `use`.
+    fn qualify_path_assists(&self, position: FilePosition) -> Cancelable<Vec<SourceChange>> {
+        let mut res = Vec::new();
+        let file = self.db.source_file(position.file_id);
+        let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
+            None => return Ok(res),
+            Some(it) => it,
+        };
+        let path = match name_ref
+            .syntax()
+            .parent()
+            .and_then(ast::PathSegment::cast)
+            .and_then(|it| it.syntax().parent())
+            .and_then(ast::Path::cast)
+        {
+            None => return Ok(res),
+            Some(it) => it,
+        };
+        if path.qualifier().is_some() {
+            return Ok(res);
+        }
+        if let Some(fn_descr) =
+            source_binder::function_from_child_node(&*self.db, position.file_id, name_ref.syntax())?
+        {
+            if fn_descr
+                .scope(&*self.db)
+                .resolve_local_name(name_ref)
+                .is_some()
+            {
+                return Ok(res);
+            }
+        }
+        let module = match source_binder::module_from_child_node(
+            &*self.db,
+            position.file_id,
+            name_ref.syntax(),
+        )? {
+            None => return Ok(res),
+            Some(it) => it,
+        };
+        let name = name_ref.text();
+        if module.scope(&*self.db)?.get(&name).is_some() {
+            return Ok(res);
+        }
+        let crate_root = module.crate_root().def_id(&*self.db);
+        let mut paths = Vec::new();
+        for (file_id, symbol) in self.index_resolve(name_ref)? {
+            let symbol_file = self.db.source_file(file_id);
+            let item = symbol_file.syntax().covering_node(symbol.node_range);
+            let item_module = match item.parent() {
+                None => continue,
+                Some(parent) => {
+                    match source_binder::module_from_child_node(&*self.db, file_id, parent)? {
+                        None => continue,
+                        Some(it) => it,
+                    }
+                }
+            };
+            if item_module.crate_root().def_id(&*self.db) != crate_root
+                || item_module.def_id(&*self.db) == module.def_id(&*self.db)
+            {
+                continue;
+            }
+            let mut segments: Vec<SmolStr> = generate(Some(item_module), |it| it.parent())
+                .filter_map(|it| it.name())
+                .collect();
+            segments.reverse();
+            segments.push(name.clone());
+            let segments: Vec<&str> = segments.iter().map(|it| it.as_str()).collect();
+            let path = format!("crate::{}", segments.join("::"));
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        let is_file_module = name_ref
+            .syntax()
+            .ancestors()
+            .filter_map(ast::Module::cast)
+            .all(|it| it.has_semi());
+        for path in paths {
+            let mut edit = TextEditBuilder::new();
+            edit.replace(name_ref.syntax().range(), path.clone());
+            res.push(SourceChange::from_local_edit(
+                position.file_id,
+                &format!("qualify as `{}`", path),
+                LocalEdit {
+                    edit: edit.finish(),
+                    cursor_position: None,
+                },
+            ));
+            if is_file_module {
+                res.push(SourceChange::from_local_edit(
+                    position.file_id,
+                    &format!("import `{}`", path),
+                    LocalEdit {
+                        edit: add_use_item(&file, &path),
+                        cursor_position: None,
+                    },
+                ));
+            }
+        }
+        Ok(res)
     }
 
     pub fn resolve_callable(
@@ -472,29 +1540,13 @@ impl AnalysisImpl {
                                 current_parameter = Some(0);
                             }
                         } else if num_params > 1 {
-                            // Count how many parameters into the call we are.
-                            // TODO: This is best effort for now and should be fixed at some point.
-                            // It may be better to see where we are in the arg_list and then check
-                            // where offset is in that list (or beyond).
-                            // Revisit this after we get documentation comments in.
-                            if let Some(ref arg_list) = calling_node.arg_list() {
-                                let start = arg_list.syntax().range().start();
-
-                                let range_search = TextRange::from_to(start, position.offset);
-                                let mut commas: usize = arg_list
-                                    .syntax()
-                                    .text()
-                                    .slice(range_search)
-                                    .to_string()
-                                    .matches(',')
-                                    .count();
-
+                            if let Some(arg_list) = calling_node.arg_list() {
+                                let mut index = active_arg_index(arg_list, position.offset);
                                 // If we have a method call eat the first param since it's just self.
                                 if has_self {
-                                    commas += 1;
+                                    index += 1;
                                 }
-
-                                current_parameter = Some(commas);
+                                current_parameter = Some(index);
                             }
                         }
 
@@ -512,10 +1564,95 @@ impl AnalysisImpl {
         let mut query = Query::new(name.to_string());
         query.exact();
         query.limit(4);
+        let res = self.world_symbols(query)?;
+        if !res.is_empty() {
+            return Ok(res);
+        }
+        // Nothing in the workspace, the symbol might come from a dependency.
+        let mut query = Query::new(name.to_string());
+        query.libs();
+        query.exact();
+        query.limit(4);
         self.world_symbols(query)
     }
 }
 
+fn lifetime_at_offset(syntax: SyntaxNodeRef, offset: TextUnit) -> Option<SyntaxNodeRef> {
+    find_leaf_at_offset(syntax, offset).find(|leaf| leaf.kind() == LIFETIME)
+}
+
+/// All occurrences of `lifetime` within the item declaring it, the
+/// declaration included. Lifetimes which aren't declared, like `'static`,
+/// have no references.
+fn lifetime_refs(lifetime: SyntaxNodeRef) -> Vec<TextRange> {
+    let text = lifetime.leaf_text().cloned();
+    let same_lifetime =
+        |node: SyntaxNodeRef| node.kind() == LIFETIME && node.leaf_text() == text.as_ref();
+    let scope = lifetime.ancestors().find(|node| {
+        node.children_of_kind(TYPE_PARAM_LIST)
+            .flat_map(|it| it.children_of_kind(LIFETIME_PARAM))
+            .flat_map(|it| it.children())
+            .any(same_lifetime)
+    });
+    match scope {
+        None => Vec::new(),
+        Some(scope) => scope
+            .descendants()
+            .filter(|&it| same_lifetime(it))
+            .map(|it| it.range())
+            .collect(),
+    }
+}
+
+/// Whether a `NAMED_FIELD` of a struct literal is written as `Foo { bar }`.
+fn is_shorthand(field: SyntaxNodeRef) -> bool {
+    field.children().all(|it| it.kind() != COLON)
+}
+
+/// Name of the last segment of a path type, like `Bar` for `foo::Bar<T>`.
+fn type_ref_name(type_ref: ast::TypeRef) -> Option<SmolStr> {
+    let path = type_ref.syntax().descendants().find_map(ast::Path::cast)?;
+    Some(path.segment()?.name_ref()?.text())
+}
+
+/// Path of the type of a `let` or parameter binding, as far as the syntax
+/// tells.
+fn binding_type_path(binding: ast::BindPat) -> Option<ast::Path> {
+    let parent = binding.syntax().parent()?;
+    let type_ref = if let Some(param) = ast::Param::cast(parent) {
+        param.type_ref()?
+    } else {
+        let stmt = ast::LetStmt::cast(parent)?;
+        match (stmt.type_ref(), stmt.initializer()) {
+            (Some(type_ref), _) => type_ref,
+            (None, Some(Expr::StructLit(lit))) => {
+                return lit.syntax().children().find_map(ast::Path::cast);
+            }
+            _ => return None,
+        }
+    };
+    type_ref.syntax().descendants().find_map(ast::Path::cast)
+}
+
+/// Whether the item is visible outside of its crate, that is, has a plain
+/// `pub` rather than `pub(crate)`, `crate` and the like.
+fn is_pub(item: SyntaxNodeRef) -> bool {
+    item.children_of_kind(VISIBILITY)
+        .any(|vis| vis.children().all(|it| it.kind() == PUB_KW))
+}
+
+/// Inserts `use path;` after the last top-level `use` item of the file, or
+/// at the very beginning of the file if there are none.
+fn add_use_item(file: &SourceFileNode, path: &str) -> TextEdit {
+    let last_use = file.syntax().children_of_kind(USE_ITEM).last();
+    let mut edit = TextEditBuilder::new();
+    match last_use {
+        Some(use_item) => edit.insert(use_item.range().end(), format!("\nuse {};", path)),
+        None => edit.insert(0.into(), format!("use {};\n\n", path)),
+    }
+    edit.finish()
+}
+
 impl SourceChange {
     pub(crate) fn from_local_edit(file_id: FileId, label: &str, edit: LocalEdit) -> SourceChange {
         let file_edit = SourceFileNodeEdit {
@@ -538,8 +1675,66 @@ enum FnCallNode<'a> {
     MethodCallExpr(ast::MethodCallExpr<'a>),
 }
 
+/// Checks if `offset` is between the parens of `arg_list`. The closing paren
+/// may be missing while the call is being typed.
+fn is_in_arg_list(arg_list: ast::ArgList, offset: TextUnit) -> bool {
+    let range = arg_list.syntax().range();
+    let is_closed = arg_list.syntax().last_child().map(|it| it.kind()) == Some(R_PAREN);
+    range.start() < offset && (offset < range.end() || !is_closed && offset == range.end())
+}
+
+/// Index of the argument at `offset`: the number of commas of `arg_list`
+/// before it. Commas nested in the arguments are not children of the list,
+/// and a trailing comma starts the next, empty, argument.
+fn active_arg_index(arg_list: ast::ArgList, offset: TextUnit) -> usize {
+    arg_list
+        .syntax()
+        .children()
+        .filter(|it| it.kind() == COMMA && it.range().end() <= offset)
+        .count()
+}
+
 impl<'a> FnCallNode<'a> {
+    pub fn cast(syntax: SyntaxNodeRef<'a>) -> Option<FnCallNode<'a>> {
+        if let Some(expr) = ast::CallExpr::cast(syntax) {
+            return Some(FnCallNode::CallExpr(expr));
+        }
+        if let Some(expr) = ast::MethodCallExpr::cast(syntax) {
+            return Some(FnCallNode::MethodCallExpr(expr));
+        }
+        None
+    }
+
+    /// Checks if `name_ref` is the name of the function being called (and not,
+    /// for example, one of the arguments).
+    pub fn is_callee(name_ref: ast::NameRef<'a>) -> bool {
+        name_ref
+            .syntax()
+            .ancestors()
+            .find_map(FnCallNode::cast)
+            .and_then(|call| call.name_ref())
+            .map(|it| it.syntax())
+            == Some(name_ref.syntax())
+    }
+
     pub fn with_node(syntax: SyntaxNodeRef, offset: TextUnit) -> Option<FnCallNode> {
+        // Prefer the innermost call whose argument list the cursor is in, so
+        // that `foo(bar(), <|>)` is a call of `foo`.
+        let token = find_token_at_offset(syntax, offset, Bias::Left);
+        let call = token
+            .into_iter()
+            .flat_map(|it| it.ancestors())
+            .find_map(|node| {
+                let call = FnCallNode::cast(node)?;
+                if is_in_arg_list(call.arg_list()?, offset) {
+                    Some(call)
+                } else {
+                    None
+                }
+            });
+        if call.is_some() {
+            return call;
+        }
         if let Some(expr) = find_node_at_offset::<ast::CallExpr>(syntax, offset) {
             return Some(FnCallNode::CallExpr(expr));
         }
@@ -549,7 +1744,7 @@ impl<'a> FnCallNode<'a> {
         None
     }
 
-    pub fn name_ref(&self) -> Option<ast::NameRef> {
+    pub fn name_ref(&self) -> Option<ast::NameRef<'a>> {
         match *self {
             FnCallNode::CallExpr(call_expr) => Some(match call_expr.expr()? {
                 Expr::PathExpr(path_expr) => path_expr.path()?.segment()?.name_ref()?,
@@ -564,7 +1759,7 @@ impl<'a> FnCallNode<'a> {
         }
     }
 
-    pub fn arg_list(&self) -> Option<ast::ArgList> {
+    pub fn arg_list(&self) -> Option<ast::ArgList<'a>> {
         match *self {
             FnCallNode::CallExpr(expr) => expr.arg_list(),
             FnCallNode::MethodCallExpr(expr) => expr.arg_list(),
@@ -13,10 +13,12 @@ macro_rules! ctry {
 mod db;
 mod imp;
 mod completion;
+mod ssr;
 mod symbol_index;
+mod syntax_highlighting;
 pub mod mock_analysis;
 
-use std::{fmt, sync::Arc};
+use std::{error::Error, fmt, sync::Arc};
 
 use ra_syntax::{SourceFileNode, TextRange, TextUnit};
 use ra_text_edit::AtomTextEdit;
@@ -30,10 +32,12 @@ use crate::{
 };
 
 pub use crate::{
-    completion::CompletionItem,
+    completion::{CompletionItem, CompletionItemKind},
+    ssr::SsrError,
 };
 pub use ra_editor::{
     FileSymbol, Fold, FoldKind, HighlightedRange, LineIndex, Runnable, RunnableKind, StructureNode,
+    split_range_by_lines,
 };
 pub use hir::FnSignatureInfo;
 
@@ -110,6 +114,15 @@ impl AnalysisHost {
     }
 }
 
+/// Approximate amount of memory, in bytes, used by the analysis.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryUsage {
+    pub syntax_trees: usize,
+    pub symbol_index: usize,
+    /// Texts of the workspace and library files.
+    pub file_texts: usize,
+}
+
 #[derive(Debug)]
 pub struct SourceChange {
     pub label: String,
@@ -118,6 +131,32 @@ pub struct SourceChange {
     pub cursor_position: Option<FilePosition>,
 }
 
+/// Why a rename was refused.
+#[derive(Debug)]
+pub struct RenameError(String);
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for RenameError {}
+
+/// How `Analysis::doc_text_for` marks up the declaration of a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    PlainText,
+    /// The declaration goes in a fenced Rust code block.
+    Markdown,
+}
+
+#[derive(Debug)]
+pub enum SsrResult {
+    Matches(Vec<(FileId, TextRange)>),
+    Change(SourceChange),
+}
+
 #[derive(Debug)]
 pub struct SourceFileNodeEdit {
     pub file_id: FileId,
@@ -150,6 +189,7 @@ pub struct Query {
     only_types: bool,
     libs: bool,
     exact: bool,
+    prefix: bool,
     limit: usize,
 }
 
@@ -162,6 +202,7 @@ impl Query {
             only_types: false,
             libs: false,
             exact: false,
+            prefix: false,
             limit: usize::max_value(),
         }
     }
@@ -174,6 +215,11 @@ impl Query {
     pub fn exact(&mut self) {
         self.exact = true;
     }
+    /// Matches only the symbols whose names start with the query, case
+    /// included, instead of containing it as a fuzzy subsequence.
+    pub fn prefix(&mut self) {
+        self.prefix = true;
+    }
     pub fn limit(&mut self, limit: usize) {
         self.limit = limit
     }
@@ -203,6 +249,33 @@ impl ReferenceResolution {
     }
 }
 
+/// A function in a call hierarchy, together with the calls which connect it
+/// to the function the hierarchy was requested for.
+#[derive(Debug)]
+pub struct CallItem {
+    pub file_id: FileId,
+    pub symbol: FileSymbol,
+    /// Ranges of the calls. For incoming calls, these are inside `symbol`,
+    /// for outgoing calls, these are inside the original function.
+    pub call_sites: Vec<TextRange>,
+}
+
+impl CallItem {
+    fn add_call(items: &mut Vec<CallItem>, file_id: FileId, symbol: FileSymbol, call: TextRange) {
+        match items
+            .iter_mut()
+            .find(|it| it.file_id == file_id && it.symbol == symbol)
+        {
+            Some(item) => item.call_sites.push(call),
+            None => items.push(CallItem {
+                file_id,
+                symbol,
+                call_sites: vec![call],
+            }),
+        }
+    }
+}
+
 /// Analysis is a snapshot of a world state at a moment in time. It is the main
 /// entry point for asking semantic information about the world. When the world
 /// state is advanced using `AnalysisHost::apply_change` method, all existing
@@ -216,23 +289,56 @@ impl Analysis {
     pub fn file_syntax(&self, file_id: FileId) -> SourceFileNode {
         self.imp.file_syntax(file_id).clone()
     }
+    pub fn file_text(&self, file_id: FileId) -> Arc<String> {
+        self.imp.file_text(file_id)
+    }
     pub fn file_line_index(&self, file_id: FileId) -> Arc<LineIndex> {
         self.imp.file_line_index(file_id)
     }
+    /// Approximate memory used by the analysis, by category. Parses the
+    /// workspace files which are not parsed yet.
+    pub fn memory_usage(&self) -> Cancelable<MemoryUsage> {
+        self.imp.memory_usage()
+    }
+    /// Human-readable summary of the analysis state. Test builds list how
+    /// many times each query was executed, like `source_file(FileId(1)): 2`.
+    pub fn status(&self) -> String {
+        self.imp.status()
+    }
     pub fn extend_selection(&self, file: &SourceFileNode, range: TextRange) -> TextRange {
         ra_editor::extend_selection(file, range).unwrap_or(range)
     }
-    pub fn matching_brace(&self, file: &SourceFileNode, offset: TextUnit) -> Option<TextUnit> {
-        ra_editor::matching_brace(file, offset)
+    /// All the ranges `extend_selection` could step through from `offset`,
+    /// innermost first.
+    pub fn selection_ranges(&self, file: &SourceFileNode, offset: TextUnit) -> Vec<TextRange> {
+        ra_editor::selection_ranges(file, offset)
+    }
+    /// For each offset, the position of the matching delimiter, or `None` if
+    /// the offset is not on a delimiter or the delimiter has no partner.
+    pub fn matching_brace(&self, file_id: FileId, offsets: &[TextUnit]) -> Vec<Option<TextUnit>> {
+        let file = self.imp.file_syntax(file_id);
+        offsets
+            .iter()
+            .map(|&offset| ra_editor::matching_brace(&file, offset))
+            .collect()
     }
     pub fn syntax_tree(&self, file_id: FileId) -> String {
         let file = self.imp.file_syntax(file_id);
         ra_editor::syntax_tree(&file)
     }
+    /// Evaluates the const item or the function call at `position`, for
+    /// debugging constant evaluation.
+    pub fn interpret_function(&self, position: FilePosition) -> String {
+        let file = self.imp.file_syntax(position.file_id);
+        ra_editor::interpret_function(&file, position.offset)
+    }
     pub fn join_lines(&self, file_id: FileId, range: TextRange) -> SourceChange {
         let file = self.imp.file_syntax(file_id);
         SourceChange::from_local_edit(file_id, "join lines", ra_editor::join_lines(&file, range))
     }
+    /// The edit for pressing Enter at `position`, with the new cursor
+    /// position. Continues line comments, keeping their indentation. Returns
+    /// `None` if the editor's default behavior is fine.
     pub fn on_enter(&self, position: FilePosition) -> Option<SourceChange> {
         let file = self.imp.file_syntax(position.file_id);
         let edit = ra_editor::on_enter(&file, position.offset)?;
@@ -251,6 +357,12 @@ impl Analysis {
         let file = self.imp.file_syntax(file_id);
         ra_editor::file_structure(&file)
     }
+    /// Like `file_structure`, but computes the nodes on demand, so callers
+    /// which need only the first few don't pay for the whole file.
+    pub fn file_structure_iter(&self, file_id: FileId) -> impl Iterator<Item = StructureNode> {
+        let file = self.imp.file_syntax(file_id);
+        ra_editor::file_structure_iter(&file)
+    }
     pub fn folding_ranges(&self, file_id: FileId) -> Vec<Fold> {
         let file = self.imp.file_syntax(file_id);
         ra_editor::folding_ranges(&file)
@@ -267,6 +379,82 @@ impl Analysis {
     pub fn find_all_refs(&self, position: FilePosition) -> Cancelable<Vec<(FileId, TextRange)>> {
         self.imp.find_all_refs(position)
     }
+    /// Structural search and replace over the workspace: a dry run returns
+    /// the ranges matched by `pattern`, a real one the change which rewrites
+    /// them according to `replacement`.
+    pub fn ssr(
+        &self,
+        pattern: &str,
+        replacement: &str,
+        dry_run: bool,
+    ) -> Cancelable<Result<SsrResult, SsrError>> {
+        self.imp.ssr(pattern, replacement, dry_run)
+    }
+    /// The change which rewrites only the match of `pattern` containing
+    /// `range`, or `None` if there's no such match.
+    pub fn ssr_at(
+        &self,
+        file_id: FileId,
+        range: TextRange,
+        pattern: &str,
+        replacement: &str,
+    ) -> Cancelable<Result<Option<SourceChange>, SsrError>> {
+        self.imp.ssr_at(file_id, range, pattern, replacement)
+    }
+    /// Returns the range of the identifier which would be renamed at
+    /// `position`, or `None` if there's nothing renameable there.
+    pub fn prepare_rename(&self, position: FilePosition) -> Cancelable<Option<TextRange>> {
+        self.imp.prepare_rename(position)
+    }
+    /// The change which renames the local, item or lifetime at `position`
+    /// to `new_name`, or `None` if there's nothing to rename.
+    pub fn rename(
+        &self,
+        position: FilePosition,
+        new_name: &str,
+    ) -> Cancelable<Result<Option<SourceChange>, RenameError>> {
+        self.imp.rename(position, new_name)
+    }
+    /// The function whose name or a reference to which is at `position`, or
+    /// else the function containing `position`.
+    pub fn fn_at_position(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<(FileId, FileSymbol)>> {
+        self.imp.fn_at_position(position)
+    }
+    /// Functions which call the function at `position`.
+    pub fn incoming_calls(&self, position: FilePosition) -> Cancelable<Vec<CallItem>> {
+        self.imp.incoming_calls(position)
+    }
+    /// Functions which are called from the function at `position`.
+    pub fn outgoing_calls(&self, position: FilePosition) -> Cancelable<Vec<CallItem>> {
+        self.imp.outgoing_calls(position)
+    }
+    /// Declaration of the type of the local variable at `position`.
+    pub fn goto_type_definition(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        self.imp.goto_type_definition(position)
+    }
+    /// Impl blocks of the trait or type at `position`, or the methods
+    /// implementing the trait method at `position`.
+    pub fn goto_implementation(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        self.imp.goto_implementation(position)
+    }
+    /// A textual dump of the lowered representation of the function at
+    /// `position`, for debugging.
+    pub fn view_hir(&self, position: FilePosition) -> Cancelable<String> {
+        self.imp.view_hir(position)
+    }
+    /// Tests which exercise the function at `position`, through the call graph.
+    pub fn related_tests(&self, position: FilePosition) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        self.imp.related_tests(position)
+    }
     pub fn doc_comment_for(
         &self,
         file_id: FileId,
@@ -274,12 +462,31 @@ impl Analysis {
     ) -> Cancelable<Option<String>> {
         self.imp.doc_comment_for(file_id, symbol)
     }
-    pub fn doc_text_for(&self, file_id: FileId, symbol: FileSymbol) -> Cancelable<Option<String>> {
-        self.imp.doc_text_for(file_id, symbol)
+    /// The declaration of `symbol` followed by its doc comment.
+    pub fn doc_text_for(
+        &self,
+        file_id: FileId,
+        symbol: FileSymbol,
+        doc_format: DocFormat,
+    ) -> Cancelable<Option<String>> {
+        self.imp.doc_text_for(file_id, symbol, doc_format)
     }
     pub fn parent_module(&self, position: FilePosition) -> Cancelable<Vec<(FileId, FileSymbol)>> {
         self.imp.parent_module(position)
     }
+    /// Methods implementing the trait method declared at `position`.
+    pub fn trait_method_impls(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Vec<(FileId, FileSymbol)>> {
+        self.imp.trait_method_impls(position)
+    }
+    /// Returns the documentation URL of the public item of a dependency crate
+    /// referenced at `position`: docs.rs for crates with a known version, and
+    /// doc.rust-lang.org for the standard library crates.
+    pub fn external_docs(&self, position: FilePosition) -> Cancelable<Option<String>> {
+        self.imp.external_docs(position)
+    }
     pub fn crate_for(&self, file_id: FileId) -> Cancelable<Vec<CrateId>> {
         self.imp.crate_for(file_id)
     }
@@ -290,15 +497,14 @@ impl Analysis {
         let file = self.imp.file_syntax(file_id);
         Ok(ra_editor::runnables(&file))
     }
-    pub fn highlight(&self, file_id: FileId) -> Cancelable<Vec<HighlightedRange>> {
-        let file = self.imp.file_syntax(file_id);
-        Ok(ra_editor::highlight(&file))
+    pub fn highlight(&self, file_id: FileId) -> Cancelable<Arc<Vec<HighlightedRange>>> {
+        self.imp.highlight(file_id)
     }
     pub fn completions(&self, position: FilePosition) -> Cancelable<Option<Vec<CompletionItem>>> {
         self.imp.completions(position)
     }
     pub fn assists(&self, file_id: FileId, range: TextRange) -> Cancelable<Vec<SourceChange>> {
-        Ok(self.imp.assists(file_id, range))
+        self.imp.assists(file_id, range)
     }
     pub fn diagnostics(&self, file_id: FileId) -> Cancelable<Vec<Diagnostic>> {
         self.imp.diagnostics(file_id)
@@ -0,0 +1,243 @@
+//! Structural search and replace.
+//!
+//! A pattern is an expression in which `$name` placeholders stand for
+//! arbitrary subtrees, like `foo($a, $b)`. A placeholder may be constrained
+//! to a kind of node with `$name:kind`, where kind is one of `expr`, `lit` or
+//! `ident`. The replacement is a template in which the placeholders are
+//! substituted by the text they matched.
+
+use std::{error::Error, fmt};
+
+use ra_syntax::{SourceFileNode, SyntaxKind::*, SyntaxNodeRef, TextRange};
+use rustc_hash::FxHashMap;
+
+#[derive(Debug)]
+pub struct SsrError(String);
+
+impl fmt::Display for SsrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for SsrError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaceholderKind {
+    Any,
+    Expr,
+    Literal,
+    Ident,
+}
+
+impl PlaceholderKind {
+    fn matches(self, node: SyntaxNodeRef) -> bool {
+        match self {
+            PlaceholderKind::Any => true,
+            PlaceholderKind::Expr => node.kind().is_expr(),
+            PlaceholderKind::Literal => node.kind() == LITERAL,
+            PlaceholderKind::Ident => match node.kind() {
+                NAME | NAME_REF => true,
+                PATH_EXPR => {
+                    node.descendants()
+                        .filter(|it| it.kind() == NAME_REF)
+                        .count()
+                        == 1
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+pub(crate) struct SsrQuery {
+    /// The pattern with placeholders replaced by plain identifiers, wrapped
+    /// in a function so that it parses.
+    pattern: SourceFileNode,
+    placeholders: FxHashMap<String, PlaceholderKind>,
+    replacement: String,
+}
+
+/// A match of the pattern: the range of the matched node and the text bound
+/// to each placeholder.
+pub(crate) struct SsrMatch {
+    pub(crate) range: TextRange,
+    bindings: FxHashMap<String, String>,
+}
+
+const PLACEHOLDER_PREFIX: &str = "__ssr_";
+
+impl SsrQuery {
+    pub(crate) fn new(pattern: &str, replacement: &str) -> Result<SsrQuery, SsrError> {
+        let mut placeholders = FxHashMap::default();
+        let pattern = substitute_placeholders(pattern, |name, kind| {
+            let kind = match kind {
+                None => PlaceholderKind::Any,
+                Some("expr") => PlaceholderKind::Expr,
+                Some("lit") => PlaceholderKind::Literal,
+                Some("ident") => PlaceholderKind::Ident,
+                Some(kind) => return Err(SsrError(format!("unknown placeholder kind `{}`", kind))),
+            };
+            placeholders.insert(name.to_string(), kind);
+            Ok(format!("{}{}", PLACEHOLDER_PREFIX, name))
+        })?;
+        let pattern = SourceFileNode::parse(&format!("fn __ssr() {{ {} }}", pattern));
+        if !pattern.errors().is_empty() || pattern_root(&pattern).is_none() {
+            return Err(SsrError("pattern is not an expression".to_string()));
+        }
+        substitute_placeholders(replacement, |name, kind| {
+            if kind.is_some() {
+                return Err(SsrError(format!(
+                    "kind of `${}` given in the replacement",
+                    name
+                )));
+            }
+            if !placeholders.contains_key(name) {
+                return Err(SsrError(format!("`${}` is not bound by the pattern", name)));
+            }
+            Ok(String::new())
+        })?;
+        let query = SsrQuery {
+            pattern,
+            placeholders,
+            replacement: replacement.to_string(),
+        };
+        if query
+            .placeholder(pattern_root(&query.pattern).unwrap())
+            .is_some()
+        {
+            // It would match every expression in the file.
+            return Err(SsrError("pattern is a lone placeholder".to_string()));
+        }
+        Ok(query)
+    }
+
+    /// Finds non-overlapping matches in `file`, outermost first.
+    pub(crate) fn find_matches(&self, file: &SourceFileNode) -> Vec<SsrMatch> {
+        let pattern = pattern_root(&self.pattern).unwrap();
+        let mut res: Vec<SsrMatch> = Vec::new();
+        for node in file.syntax().descendants() {
+            if let Some(last) = res.last() {
+                if node.range().start() < last.range.end() {
+                    continue;
+                }
+            }
+            let mut bindings = FxHashMap::default();
+            if self.match_node(pattern, node, &mut bindings) {
+                let bindings = bindings
+                    .into_iter()
+                    .map(|(name, node)| (name, node.text().to_string()))
+                    .collect();
+                res.push(SsrMatch {
+                    range: node.range(),
+                    bindings,
+                });
+            }
+        }
+        res
+    }
+
+    pub(crate) fn replacement_for(&self, m: &SsrMatch) -> String {
+        substitute_placeholders(&self.replacement, |name, _| Ok(m.bindings[name].clone())).unwrap()
+    }
+
+    fn placeholder(&self, pattern: SyntaxNodeRef) -> Option<(String, PlaceholderKind)> {
+        match pattern.kind() {
+            PATH_EXPR | NAME_REF | NAME => (),
+            _ => return None,
+        }
+        let text = pattern.text().to_string();
+        if !text.starts_with(PLACEHOLDER_PREFIX) {
+            return None;
+        }
+        let name = text[PLACEHOLDER_PREFIX.len()..].to_string();
+        let kind = *self.placeholders.get(&name)?;
+        Some((name, kind))
+    }
+
+    fn match_node<'a>(
+        &self,
+        pattern: SyntaxNodeRef,
+        code: SyntaxNodeRef<'a>,
+        bindings: &mut FxHashMap<String, SyntaxNodeRef<'a>>,
+    ) -> bool {
+        if let Some((name, kind)) = self.placeholder(pattern) {
+            if !kind.matches(code) {
+                return false;
+            }
+            if let Some(bound) = bindings.get(&name) {
+                return bound.text().to_string() == code.text().to_string();
+            }
+            bindings.insert(name, code);
+            return true;
+        }
+        if pattern.kind() != code.kind() {
+            return false;
+        }
+        if let Some(text) = pattern.leaf_text() {
+            return code.leaf_text() == Some(text);
+        }
+        let mut pattern_children = pattern.children().filter(|it| !it.kind().is_trivia());
+        let mut code_children = code.children().filter(|it| !it.kind().is_trivia());
+        loop {
+            match (pattern_children.next(), code_children.next()) {
+                (None, None) => return true,
+                (Some(p), Some(c)) => {
+                    if !self.match_node(p, c, bindings) {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// The expression of the `fn __ssr() { pattern }` wrapper.
+fn pattern_root(wrapper: &SourceFileNode) -> Option<SyntaxNodeRef<'_>> {
+    let block = wrapper
+        .syntax()
+        .descendants()
+        .find(|it| it.kind() == BLOCK)?;
+    let mut exprs = block.children().filter(|it| it.kind().is_expr());
+    let expr = exprs.next()?;
+    if exprs.next().is_some() {
+        return None;
+    }
+    Some(expr)
+}
+
+/// Replaces each `$name` or `$name:kind` in `text` with the result of `f`.
+fn substitute_placeholders(
+    text: &str,
+    mut f: impl FnMut(&str, Option<&str>) -> Result<String, SsrError>,
+) -> Result<String, SsrError> {
+    fn ident_len(text: &str) -> usize {
+        text.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(text.len())
+    }
+
+    let mut res = String::new();
+    let mut rest = text;
+    while let Some(idx) = rest.find('$') {
+        res.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+        let name_len = ident_len(rest);
+        if name_len == 0 {
+            return Err(SsrError(
+                "expected a placeholder name after `$`".to_string(),
+            ));
+        }
+        let name = &rest[..name_len];
+        rest = &rest[name_len..];
+        let mut kind = None;
+        if rest.starts_with(':') && !rest.starts_with("::") {
+            let kind_len = ident_len(&rest[1..]);
+            kind = Some(&rest[1..1 + kind_len]);
+            rest = &rest[1 + kind_len..];
+        }
+        res.push_str(&f(name, kind)?);
+    }
+    res.push_str(rest);
+    Ok(res)
+}
@@ -1,5 +1,6 @@
 use std::{
     hash::{Hash, Hasher},
+    mem,
     sync::Arc,
 };
 
@@ -78,21 +79,34 @@ impl SymbolIndex {
     pub(crate) fn for_file(file_id: FileId, file: SourceFileNode) -> SymbolIndex {
         SymbolIndex::for_files(rayon::iter::once((file_id, file)))
     }
+
+    /// Approximate amount of memory, in bytes, held by the index.
+    pub(crate) fn memory_size(&self) -> usize {
+        self.symbols.len() * mem::size_of::<(FileId, FileSymbol)>() + self.map.as_fst().size()
+    }
 }
 
 impl Query {
     pub(crate) fn search(self, indices: &[Arc<SymbolIndex>]) -> Vec<(FileId, FileSymbol)> {
         let mut op = fst::map::OpBuilder::new();
         for file_symbols in indices.iter() {
-            let automaton = fst::automaton::Subsequence::new(&self.lowercased);
-            op = op.add(file_symbols.map.search(automaton))
+            if self.prefix {
+                op = op.add(file_symbols.map.range().ge(&self.lowercased))
+            } else {
+                let automaton = fst::automaton::Subsequence::new(&self.lowercased);
+                op = op.add(file_symbols.map.search(automaton))
+            }
         }
         let mut stream = op.union();
         let mut res = Vec::new();
-        while let Some((_, indexed_values)) = stream.next() {
+        while let Some((name, indexed_values)) = stream.next() {
             if res.len() >= self.limit {
                 break;
             }
+            // The names come sorted, none of the rest has the prefix either.
+            if self.prefix && !name.starts_with(self.lowercased.as_bytes()) {
+                break;
+            }
             for indexed_value in indexed_values {
                 let file_symbols = &indices[indexed_value.index];
                 let idx = indexed_value.value as usize;
@@ -104,6 +118,9 @@ impl Query {
                 if self.exact && symbol.name != self.query {
                     continue;
                 }
+                if self.prefix && !symbol.name.starts_with(self.query.as_str()) {
+                    continue;
+                }
                 res.push((*file_id, symbol.clone()));
             }
         }
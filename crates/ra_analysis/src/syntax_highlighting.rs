@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use ra_editor::HighlightedRange;
+use ra_db::SyntaxDatabase;
+
+use crate::FileId;
+
+salsa::query_group! {
+    pub(crate) trait HighlightingDatabase: SyntaxDatabase {
+        /// Highlighting is memoized, so that the full and range semantic tokens
+        /// and decorations of an unchanged file are computed only once.
+        fn highlight(file_id: FileId) -> Arc<Vec<HighlightedRange>> {
+            type HighlightQuery;
+        }
+    }
+}
+
+fn highlight(db: &impl SyntaxDatabase, file_id: FileId) -> Arc<Vec<HighlightedRange>> {
+    let file = db.source_file(file_id);
+    Arc::new(ra_editor::highlight(&file))
+}
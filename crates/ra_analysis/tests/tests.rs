@@ -1,9 +1,10 @@
-use ra_syntax::TextRange;
-use test_utils::assert_eq_dbg;
+use ra_syntax::{SmolStr, TextRange, TextUnit};
+use ra_text_edit::TextEditBuilder;
+use test_utils::{add_cursor, assert_eq_dbg, assert_eq_text};
 
 use ra_analysis::{
     mock_analysis::{analysis_and_position, single_file, single_file_with_position, MockAnalysis},
-    AnalysisChange, CrateGraph, FileId, FnSignatureInfo,
+    AnalysisChange, CallItem, CrateGraph, DocFormat, FileId, FnSignatureInfo, SsrResult,
 };
 
 fn get_signature(text: &str) -> (FnSignatureInfo, Option<usize>) {
@@ -70,6 +71,100 @@ fn test_resolve_module() {
     );
 }
 
+#[test]
+fn test_resolve_field_shorthand() {
+    let (analysis, pos) = single_file_with_position(
+        "
+        struct Foo { bar: i32 }
+        fn main() {
+            let bar = 92;
+            Foo { <|>bar };
+        }
+    ",
+    );
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [97; 100),
+            resolves_to: [(FileId(1), FileSymbol { name: "bar", node_range: [69; 72), kind: NAME }), (FileId(1), FileSymbol { name: "bar", node_range: [22; 30), kind: NAMED_FIELD_DEF })]
+        }"#,
+        &symbols,
+    );
+
+    let (analysis, pos) = single_file_with_position(
+        "
+        struct Foo { bar: i32 }
+        fn main(foo: Foo) {
+            let Foo { <|>bar } = foo;
+        }
+    ",
+    );
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [83; 86),
+            resolves_to: [(FileId(1), FileSymbol { name: "bar", node_range: [83; 86), kind: NAME }), (FileId(1), FileSymbol { name: "bar", node_range: [22; 30), kind: NAMED_FIELD_DEF })]
+        }"#,
+        &symbols,
+    );
+}
+
+#[test]
+fn test_doc_text_for() {
+    let (analysis, pos) = single_file_with_position(
+        "
+        /// Does foo.
+        fn foo() {}
+        fn main() { <|>foo(); }
+    ",
+    );
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    let (file_id, symbol) = symbols.resolves_to[0].clone();
+    let text = |doc_format| {
+        analysis
+            .doc_text_for(file_id, symbol.clone(), doc_format)
+            .unwrap()
+            .unwrap()
+    };
+    assert_eq!(text(DocFormat::PlainText), "fn foo\n\nDoes foo.");
+    assert_eq!(
+        text(DocFormat::Markdown),
+        "```rust\nfn foo\n```\n\nDoes foo."
+    );
+}
+
+#[test]
+fn test_resolve_module_with_path_attr() {
+    let (analysis, pos) = analysis_and_position(
+        r#"
+        //- /lib.rs
+        #[path = "sub/custom.rs"]
+        mod <|>foo;
+        //- /foo.rs
+        // not this one
+        //- /sub/custom.rs
+        // empty
+    "#,
+    );
+
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [30; 33),
+            resolves_to: [(FileId(3), FileSymbol { name: "foo", node_range: [0; 0), kind: MODULE })]
+        }"#,
+        &symbols,
+    );
+
+    let (analysis, file_id) = single_file(
+        r#"#[path = "missing.rs"]
+mod foo;"#,
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "unresolved module");
+}
+
 #[test]
 fn test_unresolved_module_diagnostic() {
     let (analysis, file_id) = single_file("mod foo;");
@@ -87,6 +182,29 @@ fn test_unresolved_module_diagnostic() {
     );
 }
 
+#[test]
+fn test_unclosed_delimiter_diagnostic() {
+    let (analysis, file_id) = single_file("fn main() { foo(1, 2; }");
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    let diag = diagnostics
+        .iter()
+        .find(|it| it.message.starts_with("unclosed"))
+        .unwrap();
+    assert_eq_dbg(
+        r#"Diagnostic {
+            message: "unclosed delimiter `(`",
+            range: [15; 16),
+            fix: Some(SourceChange {
+                label: "insert missing delimiter",
+                source_file_edits: [SourceFileNodeEdit {
+                    file_id: FileId(1),
+                    edits: [AtomTextEdit { delete: [20; 20), insert: ")" }] }],
+                file_system_edits: [],
+                cursor_position: None }) }"#,
+        diag,
+    );
+}
+
 #[test]
 fn test_unresolved_module_diagnostic_no_diag_for_inline_mode() {
     let (analysis, file_id) = single_file("mod foo {}");
@@ -154,6 +272,121 @@ fn test_resolve_crate_root() {
     assert_eq!(host.analysis().crate_for(mod_file).unwrap(), vec![crate_id]);
 }
 
+#[test]
+fn test_ssr_dry_run() {
+    let (analysis, file_id) = single_file(
+        "
+        fn main() {
+            foo(1, bar);
+            foo( 2 ,baz ) + foo(x, y);
+            foo(1);
+        }
+    ",
+    );
+    let res = analysis.ssr("foo($a:lit, $b)", "", true).unwrap().unwrap();
+    let matches = match res {
+        SsrResult::Matches(matches) => matches,
+        SsrResult::Change(_) => panic!("dry run produced a change"),
+    };
+    assert_eq!(matches.len(), 2);
+    assert!(matches.iter().all(|&(file, _)| file == file_id));
+    assert_eq_dbg("[(FileId(1), [33; 44)), (FileId(1), [58; 71))]", &matches);
+}
+
+#[test]
+fn test_ssr_replace() {
+    let (analysis, file_id) = single_file(
+        "
+        fn main() {
+            foo(a + 1, b);
+            foo(x, x) + foo(x, y);
+        }
+    ",
+    );
+    let res = analysis
+        .ssr("foo($a, $b)", "bar($b, $a)", false)
+        .unwrap()
+        .unwrap();
+    let change = match res {
+        SsrResult::Change(change) => change,
+        SsrResult::Matches(_) => panic!("expected a change"),
+    };
+    assert_eq!(change.source_file_edits.len(), 1);
+    assert_eq!(change.source_file_edits[0].file_id, file_id);
+    assert_eq_dbg(
+        r#"[AtomTextEdit { delete: [33; 46), insert: "bar(b, a + 1)" },
+            AtomTextEdit { delete: [60; 69), insert: "bar(x, x)" },
+            AtomTextEdit { delete: [72; 81), insert: "bar(y, x)" }]"#,
+        &change.source_file_edits[0].edits,
+    );
+
+    let res = analysis.ssr("$a + $a", "2 * $a", false).unwrap().unwrap();
+    match res {
+        SsrResult::Change(change) => assert!(change.source_file_edits.is_empty()),
+        SsrResult::Matches(_) => panic!("expected a change"),
+    }
+    assert!(analysis.ssr("foo($a:stmt)", "", true).unwrap().is_err());
+    assert!(analysis.ssr("foo($a)", "bar($b)", true).unwrap().is_err());
+    assert!(analysis.ssr("$a", "foo($a)", true).unwrap().is_err());
+    assert!(analysis.ssr("$a:expr", "foo($a)", true).unwrap().is_err());
+}
+
+#[test]
+fn test_ssr_at() {
+    let (analysis, file_id) = single_file(
+        "
+        fn main() {
+            foo(x, x) + foo(x, y);
+        }
+    ",
+    );
+    let ssr_at = |start: u32, end: u32| {
+        let range = TextRange::from_to(start.into(), end.into());
+        analysis
+            .ssr_at(file_id, range, "foo($a, $b)", "bar($b, $a)")
+            .unwrap()
+            .unwrap()
+    };
+    let change = ssr_at(48, 48).unwrap();
+    assert_eq!(change.label, "apply SSR here");
+    assert_eq_dbg(
+        r#"[AtomTextEdit { delete: [45; 54), insert: "bar(y, x)" }]"#,
+        &change.source_file_edits[0].edits,
+    );
+    assert!(ssr_at(33, 54).is_none());
+    assert!(ssr_at(20, 20).is_none());
+}
+
+#[test]
+fn test_crate_for_and_crate_root_with_dependency() {
+    let mock = MockAnalysis::with_files(
+        "
+        //- /main.rs
+        fn main() {}
+        //- /std/lib.rs
+        pub mod collections;
+        //- /std/collections.rs
+        pub struct HashMap;
+    ",
+    );
+    let main = mock.id_of("/main.rs");
+    let std = mock.id_of("/std/lib.rs");
+    let collections = mock.id_of("/std/collections.rs");
+    let mut host = mock.analysis_host();
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main);
+    let std_crate = crate_graph.add_crate_root(std);
+    crate_graph.add_dep(main_crate, "std".into(), std_crate);
+    let mut change = AnalysisChange::new();
+    change.set_crate_graph(crate_graph);
+    host.apply_change(change);
+
+    let analysis = host.analysis();
+    assert_eq!(analysis.crate_for(main).unwrap(), vec![main_crate]);
+    assert_eq!(analysis.crate_for(collections).unwrap(), vec![std_crate]);
+    assert_eq!(analysis.crate_root(std_crate).unwrap(), std);
+}
+
 #[test]
 fn test_fn_signature_two_args_first() {
     let (desc, param) = get_signature(
@@ -180,6 +413,39 @@ fn bar() { foo(3, <|>); }"#,
     assert_eq!(param, Some(1));
 }
 
+#[test]
+fn test_fn_signature_active_param_ignores_nested_commas() {
+    let (_desc, param) = get_signature(
+        r#"fn foo(x: u32, y: (u32, u32), z: u32) {}
+fn bar(a: u32, b: u32) -> u32 { a }
+fn baz() { foo(bar(1, 2), (3, 4), <|>); }"#,
+    );
+    assert_eq!(param, Some(2));
+
+    let (desc, param) = get_signature(
+        r#"fn foo(x: u32, y: (u32, u32), z: u32) {}
+fn bar(a: u32, b: u32) -> u32 { a }
+fn baz() { foo(1, bar(2, <|>), 3); }"#,
+    );
+    assert_eq!(desc.name, "bar".to_string());
+    assert_eq!(param, Some(1));
+}
+
+#[test]
+fn test_fn_signature_active_param_after_trailing_comma() {
+    let (_desc, param) = get_signature(
+        r#"fn foo(x: u32, y: u32, z: u32) {}
+fn bar() { foo(1, 2,<|>) }"#,
+    );
+    assert_eq!(param, Some(2));
+
+    let (_desc, param) = get_signature(
+        r#"fn foo(x: u32, y: u32, z: u32) {}
+fn bar() { foo(<|>) }"#,
+    );
+    assert_eq!(param, Some(0));
+}
+
 #[test]
 fn test_fn_signature_for_impl() {
     let (desc, param) = get_signature(
@@ -453,6 +719,313 @@ fn test_find_all_refs_for_fn_param() {
     assert_eq!(refs.len(), 2);
 }
 
+#[test]
+fn test_find_all_refs_for_raw_ident() {
+    let code = r#"
+    fn main() {
+        let r#type = 1;
+        r#type<|> + r#type;
+    }"#;
+
+    let refs = get_all_refs(code);
+    assert_eq!(refs.len(), 3);
+}
+
+#[test]
+fn approximate_resolve_works_for_raw_idents() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        fn r#match() {}
+        fn main() { r#match<|>() }
+    ",
+    );
+
+    let symbols = analysis.approximately_resolve_symbol(pos).unwrap().unwrap();
+    assert_eq_dbg(
+        r#"ReferenceResolution {
+            reference_range: [28; 35),
+            resolves_to: [(FileId(1), FileSymbol { name: "match", node_range: [0; 15), kind: FN_DEF })]
+        }"#,
+        &symbols,
+    );
+}
+
+#[test]
+fn test_prepare_rename_local() {
+    let (analysis, pos) = single_file_with_position(
+        "
+        fn main() {
+            let foo = 1;
+            foo<|> + 1;
+        }
+    ",
+    );
+    let range = analysis.prepare_rename(pos).unwrap();
+    assert_eq_dbg("Some([58; 61))", &range);
+}
+
+#[test]
+fn test_prepare_rename_keyword() {
+    let (analysis, pos) = single_file_with_position(
+        "
+        fn main() {
+            le<|>t foo = 1;
+        }
+    ",
+    );
+    let range = analysis.prepare_rename(pos).unwrap();
+    assert_eq_dbg("None", &range);
+}
+
+#[test]
+fn test_rename_lifetime() {
+    let (analysis, pos) = single_file_with_position(
+        "
+        fn longest<'a<|>>(x: &'a str, y: &'static str) -> &'a str {
+            x
+        }
+    ",
+    );
+    let change = analysis.rename(pos, "'b").unwrap().unwrap().unwrap();
+    assert_eq_dbg(
+        r#"[SourceFileNodeEdit { file_id: FileId(1), edits: [AtomTextEdit { delete: [20; 22), insert: "'b" }, AtomTextEdit { delete: [28; 30), insert: "'b" }, AtomTextEdit { delete: [57; 59), insert: "'b" }] }]"#,
+        &change.source_file_edits,
+    );
+}
+
+#[test]
+fn test_rename_self_is_rejected() {
+    let (analysis, pos) = single_file_with_position(
+        "
+        struct Foo;
+        impl Foo {
+            fn foo(&self) -> &Foo {
+                se<|>lf
+            }
+        }
+    ",
+    );
+    let err = analysis.rename(pos, "this").unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "`self` can't be renamed");
+}
+
+#[test]
+fn test_rename_to_path_keyword_is_rejected() {
+    let (analysis, pos) = single_file_with_position(
+        "
+        fn main() {
+            let foo = 1;
+            foo<|> + 1;
+        }
+    ",
+    );
+    for name in ["self", "super", "crate"].iter() {
+        let err = analysis.rename(pos, name).unwrap().unwrap_err();
+        assert_eq!(err.to_string(), format!("`{}` is not a valid name", name));
+    }
+    let change = analysis.rename(pos, "match").unwrap().unwrap().unwrap();
+    assert_eq!(change.source_file_edits[0].edits[0].insert, "r#match");
+}
+
+fn call_items(items: Vec<CallItem>) -> Vec<(FileId, SmolStr, Vec<TextRange>)> {
+    items
+        .into_iter()
+        .map(|it| (it.file_id, it.symbol.name, it.call_sites))
+        .collect()
+}
+
+#[test]
+fn test_incoming_calls() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        fn ca<|>llee() {}
+        fn caller1() { callee(); callee() }
+        //- /foo.rs
+        fn caller2() { bar(super::callee()) }
+    ",
+    );
+    let calls = analysis.incoming_calls(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(1), "caller1", [[39; 45), [49; 55)]),
+            (FileId(2), "caller2", [[26; 32)])]"#,
+        &call_items(calls),
+    );
+}
+
+#[test]
+fn test_outgoing_calls() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        fn cal<|>ler() {
+            leaf();
+            foo::leaf2(leaf());
+        }
+        fn leaf() {}
+        //- /foo.rs
+        pub fn leaf2(_: ()) {}
+    ",
+    );
+    let calls = analysis.outgoing_calls(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(1), "leaf", [[27; 31), [50; 54)]),
+            (FileId(2), "leaf2", [[44; 49)])]"#,
+        &call_items(calls),
+    );
+}
+
+#[test]
+fn test_goto_implementation_of_trait() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod shapes;
+        pub trait Sh<|>ape {}
+        struct Square;
+        impl Shape for Square {}
+        impl Square {}
+        //- /shapes.rs
+        struct Circle;
+        impl crate::Shape for Circle {}
+    ",
+    );
+    let impls = analysis.goto_implementation(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(1), FileSymbol { name: "Square", node_range: [46; 70), kind: IMPL_ITEM }),
+            (FileId(2), FileSymbol { name: "Circle", node_range: [15; 46), kind: IMPL_ITEM })]"#,
+        &impls,
+    );
+}
+
+#[test]
+fn test_goto_implementation_of_type() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        trait Shape {}
+        struct Square;
+        impl Shape for Square {}
+        impl Square {}
+        fn area(s: Squ<|>are) {}
+    ",
+    );
+    let impls = analysis.goto_implementation(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(1), FileSymbol { name: "Square", node_range: [30; 54), kind: IMPL_ITEM }),
+            (FileId(1), FileSymbol { name: "Square", node_range: [55; 69), kind: IMPL_ITEM })]"#,
+        &impls,
+    );
+}
+
+#[test]
+fn test_goto_type_definition() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct Foo;
+        fn bar(foo: Foo) {
+            let baz = fo<|>o;
+        }
+    ",
+    );
+    let defs = analysis.goto_type_definition(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(1), FileSymbol { name: "Foo", node_range: [0; 11), kind: STRUCT_DEF })]"#,
+        &defs,
+    );
+
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        struct Foo { x: i32 }
+        fn bar() {
+            let f<|>oo = Foo { x: 92 };
+            let x: i32 = foo.x;
+        }
+    ",
+    );
+    let defs = analysis.goto_type_definition(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(1), FileSymbol { name: "Foo", node_range: [0; 21), kind: STRUCT_DEF })]"#,
+        &defs,
+    );
+
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        struct Foo;
+        //- /foo.rs
+        struct Foo;
+        fn bar(<|>foo: Foo) {}
+    ",
+    );
+    let defs = analysis.goto_type_definition(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(2), FileSymbol { name: "Foo", node_range: [0; 11), kind: STRUCT_DEF })]"#,
+        &defs,
+    );
+}
+
+#[test]
+fn test_goto_type_definition_of_primitive() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        fn bar() {
+            let <|>x: i32 = 92;
+        }
+    ",
+    );
+    let defs = analysis.goto_type_definition(pos).unwrap();
+    assert_eq_dbg(r#"[]"#, &defs);
+}
+
+#[test]
+fn test_goto_implementation_of_trait_method() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        trait Shape { fn ar<|>ea(&self); }
+        struct Square;
+        impl Shape for Square { fn area(&self) {} }
+    ",
+    );
+    let impls = analysis.goto_implementation(pos).unwrap();
+    assert_eq_dbg(
+        r#"[(FileId(1), FileSymbol { name: "area", node_range: [74; 78), kind: FN_DEF })]"#,
+        &impls,
+    );
+}
+
+#[test]
+fn test_related_tests_through_recursion() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod tests;
+        fn he<|>lper() { even() }
+        fn even() { odd() }
+        fn odd() { even(); helper() }
+        //- /tests.rs
+        #[test]
+        fn test_odd() { super::odd() }
+        #[test]
+        fn test_unrelated() {}
+    ",
+    );
+    let tests = analysis.related_tests(pos).unwrap();
+    let names = tests
+        .iter()
+        .map(|(file_id, symbol)| (*file_id, symbol.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![(FileId(2), "test_odd")]);
+}
+
 #[test]
 fn test_complete_crate_path() {
     let (analysis, position) = analysis_and_position(
@@ -466,8 +1039,8 @@ fn test_complete_crate_path() {
     );
     let completions = analysis.completions(position).unwrap().unwrap();
     assert_eq_dbg(
-        r#"[CompletionItem { label: "Spam", lookup: None, snippet: None },
-            CompletionItem { label: "foo", lookup: None, snippet: None }]"#,
+        r#"[CompletionItem { label: "Spam", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [9; 21))) },
+            CompletionItem { label: "foo", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(2), [0; 14))) }]"#,
         &completions,
     );
 }
@@ -485,8 +1058,8 @@ fn test_complete_crate_path_with_braces() {
     );
     let completions = analysis.completions(position).unwrap().unwrap();
     assert_eq_dbg(
-        r#"[CompletionItem { label: "Spam", lookup: None, snippet: None },
-            CompletionItem { label: "foo", lookup: None, snippet: None }]"#,
+        r#"[CompletionItem { label: "Spam", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [9; 21))) },
+            CompletionItem { label: "foo", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(2), [0; 17))) }]"#,
         &completions,
     );
 }
@@ -508,7 +1081,336 @@ fn test_complete_crate_path_in_nested_tree() {
     );
     let completions = analysis.completions(position).unwrap().unwrap();
     assert_eq_dbg(
-        r#"[CompletionItem { label: "Spam", lookup: None, snippet: None }]"#,
+        r#"[CompletionItem { label: "Spam", lookup: None, snippet: None, kind: None, relevance: 0, detail: None, additional_edit: None, source: Some((FileId(1), [49; 65))) }]"#,
         &completions,
     );
 }
+
+#[test]
+fn test_complete_unimported_item() {
+    let (analysis, position) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        fn main() { Spam<|> }
+        //- /foo.rs
+        pub mod bar {
+            pub struct Spammer;
+        }
+    ",
+    );
+    let completions = analysis.completions(position).unwrap().unwrap();
+    let item = completions
+        .into_iter()
+        .find(|it| it.label == "Spammer")
+        .unwrap();
+    assert_eq!(item.detail.as_ref().unwrap(), "crate::foo::bar::Spammer");
+    let text = analysis.file_text(position.file_id);
+    assert_eq!(
+        item.additional_edit.unwrap().apply(&text),
+        "use crate::foo::bar::Spammer;\n\nmod foo;\nfn main() { Spam }\n"
+    );
+}
+
+#[test]
+fn test_complete_unimported_item_behind_fuzzy_matches() {
+    // All of these match `Spam` fuzzily and sort before `Spammer`.
+    let fuzzy_matches: String = (0..100)
+        .map(|i| format!("        pub struct SaPaM{};\n", i))
+        .collect();
+    let fixture = format!(
+        "
+        //- /lib.rs
+        mod foo;
+        fn main() {{ Spam<|> }}
+        //- /foo.rs
+{}        pub struct Spammer;
+    ",
+        fuzzy_matches
+    );
+    let (analysis, position) = analysis_and_position(&fixture);
+    let completions = analysis.completions(position).unwrap().unwrap();
+    assert!(completions.iter().any(|it| it.label == "Spammer"));
+}
+
+#[test]
+fn test_complete_unimported_item_from_dependency() {
+    let (mock, position) = MockAnalysis::with_files_and_position(
+        "
+        //- /main.rs
+        use std::fmt;
+        fn main() { let map = Hash<|> }
+        //- /std/lib.rs
+        pub mod collections;
+        pub mod fmt {}
+        //- /std/collections.rs
+        pub struct HashMap;
+    ",
+    );
+    let main = mock.id_of("/main.rs");
+    let std = mock.id_of("/std/lib.rs");
+    let mut host = mock.analysis_host();
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main);
+    let std_crate = crate_graph.add_crate_root(std);
+    crate_graph.add_dep(main_crate, "std".into(), std_crate);
+    let mut change = AnalysisChange::new();
+    change.set_crate_graph(crate_graph);
+    host.apply_change(change);
+
+    let analysis = host.analysis();
+    let completions = analysis.completions(position).unwrap().unwrap();
+    let item = completions
+        .into_iter()
+        .find(|it| it.label == "HashMap")
+        .unwrap();
+    assert_eq!(item.detail.as_ref().unwrap(), "std::collections::HashMap");
+    let text = analysis.file_text(position.file_id);
+    assert_eq!(
+        item.additional_edit.unwrap().apply(&text),
+        "use std::fmt;\nuse std::collections::HashMap;\nfn main() { let map = Hash }\n"
+    );
+}
+
+#[test]
+fn test_qualify_path_assist() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        fn main() { let x: Sp<|>am; }
+        //- /foo.rs
+        pub mod bar {
+            pub struct Spam;
+        }
+    ",
+    );
+    let range = TextRange::offset_len(pos.offset, 0.into());
+    let assists = analysis.assists(pos.file_id, range).unwrap();
+    let edits: Vec<_> = assists
+        .iter()
+        .map(|it| (it.label.as_str(), &it.source_file_edits[0].edits))
+        .collect();
+    assert_eq_dbg(
+        r#"[("qualify as `crate::foo::bar::Spam`",
+             [AtomTextEdit { delete: [28; 32), insert: "crate::foo::bar::Spam" }]),
+            ("import `crate::foo::bar::Spam`",
+             [AtomTextEdit { delete: [0; 0), insert: "use crate::foo::bar::Spam;\n\n" }])]"#,
+        &edits,
+    );
+}
+
+#[test]
+fn test_qualify_path_assist_not_applicable_in_scope() {
+    let (analysis, pos) = analysis_and_position(
+        "
+        //- /lib.rs
+        mod foo;
+        use foo::Spam;
+        fn main() { let x: Sp<|>am; }
+        //- /foo.rs
+        pub struct Spam;
+    ",
+    );
+    let range = TextRange::offset_len(pos.offset, 0.into());
+    let assists = analysis.assists(pos.file_id, range).unwrap();
+    assert!(assists.is_empty());
+}
+
+fn external_docs_with_dep(fixture: &str, name: &str, version: Option<&str>) -> Option<String> {
+    let (mock, position) = MockAnalysis::with_files_and_position(fixture);
+    let main = mock.id_of("/main.rs");
+    let dep = mock.id_of("/dep/lib.rs");
+    let mut host = mock.analysis_host();
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main);
+    let dep_crate = crate_graph.add_crate_root(dep);
+    if let Some(version) = version {
+        crate_graph.set_crate_version(dep_crate, version.into());
+    }
+    crate_graph.add_dep(main_crate, name.into(), dep_crate);
+    let mut change = AnalysisChange::new();
+    change.set_crate_graph(crate_graph);
+    host.apply_change(change);
+    host.analysis().external_docs(position).unwrap()
+}
+
+#[test]
+fn test_external_docs() {
+    let url = external_docs_with_dep(
+        "
+        //- /main.rs
+        fn main() { let x: Fo<|>o; }
+        //- /dep/lib.rs
+        pub mod bar;
+        //- /dep/bar.rs
+        pub struct Foo;
+    ",
+        "dep",
+        Some("1.2.3"),
+    );
+    assert_eq!(
+        url.as_ref().map(String::as_str),
+        Some("https://docs.rs/dep/1.2.3/dep/bar/struct.Foo.html")
+    );
+}
+
+#[test]
+fn test_external_docs_for_std() {
+    let url = external_docs_with_dep(
+        "
+        //- /main.rs
+        fn main() { let x: Has<|>hMap; }
+        //- /dep/lib.rs
+        pub mod collections;
+        //- /dep/collections.rs
+        pub struct HashMap;
+    ",
+        "std",
+        None,
+    );
+    assert_eq!(
+        url.as_ref().map(String::as_str),
+        Some("https://doc.rust-lang.org/std/collections/struct.HashMap.html")
+    );
+}
+
+#[test]
+fn test_external_docs_not_for_crate_visible_items_and_unknown_versions() {
+    let fixture = "
+        //- /main.rs
+        fn main() { let x: Fo<|>o; }
+        //- /dep/lib.rs
+        pub(crate) struct Foo;
+    ";
+    assert_eq!(external_docs_with_dep(fixture, "dep", Some("1.2.3")), None);
+    let fixture = "
+        //- /main.rs
+        fn main() { let x: Fo<|>o; }
+        //- /dep/lib.rs
+        pub struct Foo;
+    ";
+    assert_eq!(external_docs_with_dep(fixture, "dep", None), None);
+}
+
+#[test]
+fn test_external_docs_not_for_locals_and_private_items() {
+    let (analysis, position) = single_file_with_position("fn main() { let x = 92; x<|>; }");
+    assert_eq!(analysis.external_docs(position).unwrap(), None);
+
+    let (analysis, position) = analysis_and_position(
+        "
+        //- /main.rs
+        mod foo;
+        fn main() { let x: Fo<|>o; }
+        //- /foo.rs
+        pub(crate) struct Foo;
+    ",
+    );
+    assert_eq!(analysis.external_docs(position).unwrap(), None);
+}
+
+#[test]
+fn test_interpret_function() {
+    let (analysis, position) = single_file_with_position("const N: usize = <|>2 + 3;");
+    assert_eq!(analysis.interpret_function(position), "5");
+}
+
+fn apply_on_enter(before: &str) -> Option<String> {
+    let (analysis, position) = single_file_with_position(before);
+    let change = analysis.on_enter(position)?;
+    assert_eq!(change.source_file_edits.len(), 1);
+    let mut edit = TextEditBuilder::new();
+    for atom in change.source_file_edits[0].edits.iter() {
+        edit.replace(atom.delete, atom.insert.clone());
+    }
+    let actual = edit.finish().apply(&analysis.file_text(position.file_id));
+    Some(add_cursor(&actual, change.cursor_position.unwrap().offset))
+}
+
+#[test]
+fn test_on_enter_in_doc_comment() {
+    let actual = apply_on_enter("/// Some <|>docs\nfn foo() {}\n").unwrap();
+    assert_eq_text!("/// Some \n/// <|>docs\nfn foo() {}\n", &actual);
+}
+
+#[test]
+fn test_on_enter_in_indented_block() {
+    let actual = apply_on_enter(
+        "
+mod foo {
+    fn foo() {
+        // first <|>line
+        92
+    }
+}
+",
+    )
+    .unwrap();
+    assert_eq_text!(
+        "
+mod foo {
+    fn foo() {
+        // first 
+        // <|>line
+        92
+    }
+}
+",
+        &actual
+    );
+    assert!(apply_on_enter("fn foo() {\n    let x = <|>92;\n}\n").is_none());
+}
+
+#[test]
+fn test_view_hir() {
+    let (analysis, position) = single_file_with_position(
+        "
+        fn foo(a: u32, (b, c): (u32, u32)) -> u32 {
+            let x = <|>a;
+            x + b
+        }
+    ",
+    );
+    let dump = analysis.view_hir(position).unwrap();
+    assert_eq!(
+        dump,
+        "fn foo
+  param a: u32
+  param (b, c): (u32, u32)
+  -> u32
+  scope Id(0): a@[16; 17), b@[25; 26), c@[28; 29)
+  scope Id(1) in Id(0): x@[69; 70)
+"
+    );
+}
+
+#[test]
+fn test_matching_brace() {
+    let (analysis, file_id) = single_file("fn foo(x: u32) { let s = \"{\"; (x) }\nfn bar( {}");
+    let text = analysis.file_text(file_id);
+    let offset = |pat: &str| TextUnit::of_str(&text[..text.find(pat).unwrap()]);
+    let offsets = [
+        offset("("),
+        offset(") {"),
+        offset("{ let"),
+        offset("\"{"),
+        offset("let"),
+        offset("(x)"),
+        offset("( {}"),
+    ];
+    let actual = analysis.matching_brace(file_id, &offsets);
+    assert_eq!(
+        actual,
+        vec![
+            Some(offset(") {")),
+            Some(offset("(")),
+            Some(offset("}\n")),
+            Some(offset("\";")),
+            None,
+            Some(offset(") }")),
+            None,
+        ]
+    );
+    assert!(analysis.matching_brace(file_id, &[]).is_empty());
+}
@@ -26,7 +26,11 @@ fn main() -> Result<()> {
                         .takes_value(true),
                 ),
         )
-        .subcommand(SubCommand::with_name("parse").arg(Arg::with_name("no-dump").long("--no-dump")))
+        .subcommand(
+            SubCommand::with_name("parse")
+                .arg(Arg::with_name("no-dump").long("--no-dump"))
+                .arg(Arg::with_name("sexp").long("--sexp")),
+        )
         .subcommand(SubCommand::with_name("symbols"))
         .subcommand(
             SubCommand::with_name("extend-selection")
@@ -39,7 +43,9 @@ fn main() -> Result<()> {
             let start = Instant::now();
             let file = file()?;
             let elapsed = start.elapsed();
-            if !matches.is_present("no-dump") {
+            if matches.is_present("sexp") {
+                println!("{}", file.debug_dump());
+            } else if !matches.is_present("no-dump") {
                 println!("{}", syntax_tree(&file));
             }
             eprintln!("parsing: {:?}", elapsed);
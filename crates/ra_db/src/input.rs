@@ -20,6 +20,7 @@ pub struct CrateGraph {
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CrateData {
     file_id: FileId,
+    version: Option<SmolStr>,
     dependencies: Vec<Dependency>,
 }
 
@@ -27,6 +28,7 @@ impl CrateData {
     fn new(file_id: FileId) -> CrateData {
         CrateData {
             file_id,
+            version: None,
             dependencies: Vec::new(),
         }
     }
@@ -61,6 +63,12 @@ impl CrateGraph {
     pub fn add_dep(&mut self, from: CrateId, name: SmolStr, to: CrateId) {
         self.arena.get_mut(&from).unwrap().add_dep(name, to)
     }
+    pub fn set_crate_version(&mut self, crate_id: CrateId, version: SmolStr) {
+        self.arena.get_mut(&crate_id).unwrap().version = Some(version)
+    }
+    pub fn crate_version(&self, crate_id: CrateId) -> Option<&SmolStr> {
+        self.arena[&crate_id].version.as_ref()
+    }
     pub fn crate_root(&self, crate_id: CrateId) -> FileId {
         self.arena[&crate_id].file_id
     }
@@ -71,6 +79,14 @@ impl CrateGraph {
             .find(|(_crate_id, data)| data.file_id == file_id)?;
         Some(crate_id)
     }
+    /// Name under which the crate is depended upon by other crates, if any.
+    pub fn crate_name(&self, crate_id: CrateId) -> Option<&SmolStr> {
+        self.arena
+            .values()
+            .flat_map(|data| data.dependencies.iter())
+            .find(|dep| dep.crate_id == crate_id)
+            .map(|dep| &dep.name)
+    }
     pub fn dependencies<'a>(
         &'a self,
         crate_id: CrateId,
@@ -1,10 +1,15 @@
+use itertools::Itertools;
 use join_to_string::join;
 
 use ra_syntax::{
-    algo::{find_covering_node, find_leaf_at_offset},
+    algo::find_leaf_at_offset,
     ast::{self, AstNode, AttrsOwner, NameOwner, TypeParamsOwner},
     Direction, SourceFileNode,
-    SyntaxKind::{COMMA, WHITESPACE, COMMENT},
+    SyntaxKind::{
+        BYTE, CHAR, COMMA, COMMENT, FALSE_KW, FLOAT_NUMBER, FN_KW, IF_KW, INT_NUMBER, POS_FIELD,
+        PATH, POS_FIELD_LIST, RAW_STRING, RET_TYPE, SELF_KW, STRING, TRUE_KW, TYPE_ARG,
+        TYPE_ARG_LIST, WHITESPACE,
+    },
     SyntaxNodeRef, TextRange, TextUnit,
 };
 
@@ -115,11 +120,116 @@ pub fn add_impl<'a>(
     })
 }
 
+/// For a newtype like `struct Meters(f64);`, generates
+/// `impl From<f64> for Meters`, unless there is one already.
+pub fn add_from_impl<'a>(
+    file: &'a SourceFileNode,
+    offset: TextUnit,
+) -> Option<impl FnOnce() -> LocalEdit + 'a> {
+    let struct_def = find_node_at_offset::<ast::StructDef>(file.syntax(), offset)?;
+    if struct_def.type_param_list().is_some() {
+        return None;
+    }
+    let name = struct_def.name()?.text();
+    let field_list = struct_def.syntax().child_of_kind(POS_FIELD_LIST)?;
+    let mut fields = field_list.children_of_kind(POS_FIELD);
+    let field = fields.next()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    let field_type_ref = field.children().find_map(ast::TypeRef::cast)?;
+    let field_type = field_type_ref.syntax().text().to_string();
+
+    let has_from_impl = file
+        .syntax()
+        .descendants()
+        .filter_map(ast::ImplItem::cast)
+        .any(|impl_item| {
+            let (trait_ref, self_type) = match (impl_item.target_trait(), impl_item.target_type()) {
+                (Some(trait_ref), Some(self_type)) => (trait_ref, self_type),
+                _ => return false,
+            };
+            let is_for_struct = path_of_type(self_type).map_or(false, |path| {
+                path.qualifier().is_none()
+                    && path.segment().map_or(false, |segment| {
+                        segment.syntax().child_of_kind(TYPE_ARG_LIST).is_none()
+                            && segment.name_ref().map_or(false, |it| it.text() == name)
+                    })
+            });
+            is_for_struct
+                && from_type_arg(trait_ref).map_or(false, |arg| {
+                    same_syntax(arg.syntax(), field_type_ref.syntax())
+                })
+        });
+    if has_from_impl {
+        return None;
+    }
+
+    Some(move || {
+        let mut edit = TextEditBuilder::new();
+        let buf = format!(
+            "\n\nimpl From<{ty}> for {name} {{\n    \
+             fn from(v: {ty}) -> Self {{\n        \
+             {name}(v)\n    \
+             }}\n\
+             }}",
+            ty = field_type,
+            name = name,
+        );
+        edit.insert(struct_def.syntax().range().end(), buf);
+        LocalEdit {
+            edit: edit.finish(),
+            cursor_position: None,
+        }
+    })
+}
+
+fn path_of_type(type_ref: ast::TypeRef) -> Option<ast::Path> {
+    match type_ref {
+        ast::TypeRef::PathType(it) => it.syntax().child_of_kind(PATH).and_then(ast::Path::cast),
+        _ => None,
+    }
+}
+
+/// The `T` of a `From<T>` trait reference, possibly written with a path
+/// like `std::convert::From<T>`.
+fn from_type_arg(trait_ref: ast::TypeRef) -> Option<ast::TypeRef> {
+    let segment = path_of_type(trait_ref)?.segment()?;
+    if segment.name_ref()?.text() != "From" {
+        return None;
+    }
+    let mut args = segment
+        .syntax()
+        .child_of_kind(TYPE_ARG_LIST)?
+        .children_of_kind(TYPE_ARG);
+    let arg = args.next()?;
+    if args.next().is_some() {
+        return None;
+    }
+    arg.children().find_map(ast::TypeRef::cast)
+}
+
+/// Whether `a` and `b` are the same tree, ignoring whitespace and comments.
+fn same_syntax(a: SyntaxNodeRef, b: SyntaxNodeRef) -> bool {
+    if a.kind() != b.kind() || a.leaf_text() != b.leaf_text() {
+        return false;
+    }
+    let mut a_children = a.children().filter(|it| !it.kind().is_trivia());
+    let mut b_children = b.children().filter(|it| !it.kind().is_trivia());
+    loop {
+        match (a_children.next(), b_children.next()) {
+            (None, None) => return true,
+            (Some(a), Some(b)) if same_syntax(a, b) => (),
+            _ => return false,
+        }
+    }
+}
+
 pub fn introduce_variable<'a>(
     file: &'a SourceFileNode,
     range: TextRange,
 ) -> Option<impl FnOnce() -> LocalEdit + 'a> {
-    let node = find_covering_node(file.syntax(), range);
+    let node = file.syntax().covering_node(range);
     let expr = node.ancestors().filter_map(ast::Expr::cast).next()?;
 
     let anchor_stmt = anchor_stmt(expr)?;
@@ -174,6 +284,318 @@ pub fn introduce_variable<'a>(
     }
 }
 
+pub fn convert_match_to_if_let<'a>(
+    file: &'a SourceFileNode,
+    offset: TextUnit,
+) -> Option<impl FnOnce() -> LocalEdit + 'a> {
+    let match_expr = find_node_at_offset::<ast::MatchExpr>(file.syntax(), offset)?;
+    let arm_list = match_expr.match_arm_list()?;
+    // Only offer the assist on the `match EXPR` part.
+    if offset > arm_list.syntax().range().start() {
+        return None;
+    }
+    let expr = match_expr.expr()?;
+    let (first, second) = arm_list.arms().collect_tuple()?;
+    let (pat_arm, catch_all_arm) = match (catch_all_kind(first)?, catch_all_kind(second)?) {
+        (CatchAll::No, CatchAll::Wildcard) | (CatchAll::No, CatchAll::None) => (first, second),
+        // `_` first would shadow the second arm.
+        (CatchAll::None, CatchAll::No) => (second, first),
+        _ => return None,
+    };
+    let pat = pat_arm.pats().next()?;
+    let then_branch = pat_arm.expr()?;
+    let else_branch = catch_all_arm.expr()?;
+
+    return Some(move || {
+        let mut buf = String::new();
+        buf.push_str("if let ");
+        pat.syntax().text().push_to(&mut buf);
+        buf.push_str(" = ");
+        expr.syntax().text().push_to(&mut buf);
+        buf.push(' ');
+        push_branch(&mut buf, then_branch);
+        buf.push_str(" else ");
+        push_branch(&mut buf, else_branch);
+
+        let mut edit = TextEditBuilder::new();
+        edit.replace(match_expr.syntax().range(), buf);
+        LocalEdit {
+            edit: edit.finish(),
+            cursor_position: Some(match_expr.syntax().range().start()),
+        }
+    });
+
+    enum CatchAll {
+        No,
+        Wildcard,
+        None,
+    }
+
+    /// Returns `None` if the arm can't be converted at all.
+    fn catch_all_kind(arm: ast::MatchArm) -> Option<CatchAll> {
+        let has_guard =
+            arm.guard().is_some() || arm.syntax().children().any(|it| it.kind() == IF_KW);
+        if has_guard {
+            return None;
+        }
+        let (pat,) = arm.pats().collect_tuple()?;
+        let res = match pat {
+            ast::Pat::PlaceholderPat(_) => CatchAll::Wildcard,
+            ast::Pat::BindPat(bind) if bind.name().map(|it| it.text()) == Some("None".into()) => {
+                CatchAll::None
+            }
+            _ => CatchAll::No,
+        };
+        Some(res)
+    }
+
+    fn push_branch(buf: &mut String, expr: ast::Expr) {
+        match expr {
+            ast::Expr::BlockExpr(block) => block.syntax().text().push_to(buf),
+            _ => {
+                buf.push_str("{ ");
+                expr.syntax().text().push_to(buf);
+                buf.push_str(" }");
+            }
+        }
+    }
+}
+
+pub fn convert_closure_to_fn<'a>(
+    file: &'a SourceFileNode,
+    offset: TextUnit,
+) -> Option<impl FnOnce() -> LocalEdit + 'a> {
+    let let_stmt = find_node_at_offset::<ast::LetStmt>(file.syntax(), offset)?;
+    let name = match let_stmt.pat()? {
+        ast::Pat::BindPat(it) => it.name()?,
+        _ => return None,
+    };
+    let lambda = match let_stmt.initializer()? {
+        ast::Expr::LambdaExpr(it) => it,
+        _ => return None,
+    };
+    let body = lambda.body()?;
+    let params = lambda
+        .param_list()?
+        .params()
+        .map(|param| {
+            let ty = param
+                .type_ref()
+                .map_or("_".to_string(), |it| it.syntax().text().to_string());
+            Some(format!("{}: {}", param.pat()?.syntax().text(), ty))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if captures_environment(lambda) {
+        return None;
+    }
+    return Some(move || {
+        let ret_type = match lambda.syntax().child_of_kind(RET_TYPE) {
+            Some(it) => it.text().to_string(),
+            None => format!("-> {}", guess_type(body).unwrap_or_else(|| "_".to_string())),
+        };
+        let body = match body {
+            ast::Expr::BlockExpr(it) => it.syntax().text().to_string(),
+            _ => format!("{{ {} }}", body.syntax().text()),
+        };
+        let mut edit = TextEditBuilder::new();
+        edit.replace(
+            let_stmt.syntax().range(),
+            format!(
+                "fn {}({}) {} {}",
+                name.text(),
+                params.join(", "),
+                ret_type,
+                body
+            ),
+        );
+        LocalEdit {
+            edit: edit.finish(),
+            cursor_position: Some(let_stmt.syntax().range().start()),
+        }
+    });
+
+    /// Whether the closure uses `self` or a local variable of the enclosing
+    /// function. Shadowing within the closure is only roughly accounted for.
+    fn captures_environment(lambda: ast::LambdaExpr) -> bool {
+        let fn_def = match lambda.syntax().ancestors().find_map(ast::FnDef::cast) {
+            None => return false,
+            Some(it) => it,
+        };
+        let bound_names = |node: SyntaxNodeRef| {
+            node.descendants()
+                .filter_map(ast::BindPat::cast)
+                .filter_map(|it| it.name())
+                .map(|it| it.text())
+                .collect::<Vec<_>>()
+        };
+        let inner = bound_names(lambda.syntax());
+        let outer = bound_names(fn_def.syntax())
+            .into_iter()
+            .filter(|it| !inner.contains(it))
+            .collect::<Vec<_>>();
+        lambda.syntax().descendants().any(|node| {
+            if node.kind() == SELF_KW {
+                return true;
+            }
+            let name_ref = ast::PathExpr::cast(node)
+                .and_then(|it| it.path())
+                .filter(|it| it.qualifier().is_none())
+                .and_then(|it| it.segment())
+                .and_then(|it| it.name_ref());
+            name_ref.map_or(false, |it| outer.contains(&it.text()))
+        })
+    }
+}
+
+pub fn convert_fn_to_closure<'a>(
+    file: &'a SourceFileNode,
+    offset: TextUnit,
+) -> Option<impl FnOnce() -> LocalEdit + 'a> {
+    // Only local functions can become closures.
+    let (fn_def, block) = find_leaf_at_offset(file.syntax(), offset).find_map(|leaf| {
+        let fn_def = leaf.ancestors().find_map(ast::FnDef::cast)?;
+        let block = fn_def.syntax().parent().and_then(ast::Block::cast)?;
+        Some((fn_def, block))
+    })?;
+    let name = fn_def.name()?;
+    let body = fn_def.body()?;
+    // Only offer the assist on the signature.
+    if offset >= body.syntax().range().start() {
+        return None;
+    }
+    // Visibility, attributes, qualifiers like `unsafe` and generics have no
+    // closure equivalent.
+    let fn_kw = fn_def
+        .syntax()
+        .children()
+        .find(|it| !it.kind().is_trivia())?;
+    if fn_kw.kind() != FN_KW || fn_def.type_param_list().is_some() {
+        return None;
+    }
+    let param_list = fn_def.param_list()?;
+    if param_list.self_param().is_some() {
+        return None;
+    }
+    // Unlike functions, closures can't be used before they are declared, or
+    // recursively.
+    let used_early = block
+        .syntax()
+        .descendants()
+        .filter_map(ast::NameRef::cast)
+        .any(|it| {
+            it.text() == name.text() && it.syntax().range().end() <= fn_def.syntax().range().end()
+        });
+    if used_early {
+        return None;
+    }
+    Some(move || {
+        let params = param_list
+            .params()
+            .map(|it| it.syntax().text().to_string())
+            .collect::<Vec<_>>();
+        let mut buf = format!("let {} = |{}| ", name.text(), params.join(", "));
+        match (fn_def.ret_type(), body.statements().next(), body.expr()) {
+            (Some(ret_type), _, _) => {
+                ret_type.syntax().text().push_to(&mut buf);
+                buf.push(' ');
+                body.syntax().text().push_to(&mut buf);
+            }
+            (None, None, Some(expr)) => expr.syntax().text().push_to(&mut buf),
+            (None, _, _) => body.syntax().text().push_to(&mut buf),
+        }
+        buf.push(';');
+        let start = fn_kw.range().start();
+        let mut edit = TextEditBuilder::new();
+        edit.replace(
+            TextRange::from_to(start, fn_def.syntax().range().end()),
+            buf,
+        );
+        LocalEdit {
+            edit: edit.finish(),
+            cursor_position: Some(start),
+        }
+    })
+}
+
+/// Annotates `let x = expr;` with the type of `expr`. There's no type
+/// inference yet, so the type is a guess based on the syntax of `expr`, see
+/// `guess_type`.
+pub fn add_type_annotation<'a>(
+    file: &'a SourceFileNode,
+    offset: TextUnit,
+) -> Option<impl FnOnce() -> LocalEdit + 'a> {
+    let let_stmt = find_node_at_offset::<ast::LetStmt>(file.syntax(), offset)?;
+    if let_stmt.type_ref().is_some() {
+        return None;
+    }
+    let pat = match let_stmt.pat()? {
+        ast::Pat::BindPat(it) => it,
+        _ => return None,
+    };
+    let ty = guess_type(let_stmt.initializer()?)?;
+    Some(move || {
+        let mut edit = TextEditBuilder::new();
+        edit.insert(pat.syntax().range().end(), format!(": {}", ty));
+        LocalEdit {
+            edit: edit.finish(),
+            cursor_position: None,
+        }
+    })
+}
+
+/// Very rough approximation of type inference: only types which are evident
+/// from the syntax of the expression are known, and unsuffixed number
+/// literals are assumed to have the fallback types, `i32` and `f64`.
+fn guess_type(expr: ast::Expr) -> Option<String> {
+    let res = match expr {
+        ast::Expr::Literal(lit) => {
+            let token = lit.syntax().first_child()?;
+            let text = token.leaf_text()?;
+            match token.kind() {
+                INT_NUMBER => literal_suffix(text, INT_SUFFIXES).unwrap_or("i32"),
+                FLOAT_NUMBER => literal_suffix(text, FLOAT_SUFFIXES).unwrap_or("f64"),
+                STRING | RAW_STRING => "&str",
+                CHAR => "char",
+                BYTE => "u8",
+                TRUE_KW | FALSE_KW => "bool",
+                _ => return None,
+            }
+            .to_string()
+        }
+        ast::Expr::CastExpr(cast) => cast
+            .syntax()
+            .children()
+            .find_map(ast::TypeRef::cast)?
+            .syntax()
+            .text()
+            .to_string(),
+        ast::Expr::StructLit(lit) => lit
+            .syntax()
+            .children()
+            .find_map(ast::Path::cast)?
+            .syntax()
+            .text()
+            .to_string(),
+        ast::Expr::ParenExpr(paren) => {
+            guess_type(paren.syntax().children().find_map(ast::Expr::cast)?)?
+        }
+        _ => return None,
+    };
+    return Some(res);
+
+    const INT_SUFFIXES: &[&str] = &[
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+    ];
+    const FLOAT_SUFFIXES: &[&str] = &["f32", "f64"];
+
+    fn literal_suffix(text: &str, suffixes: &[&'static str]) -> Option<&'static str> {
+        suffixes
+            .iter()
+            .cloned()
+            .find(|&suffix| text.ends_with(suffix))
+    }
+}
+
 fn non_trivia_sibling(node: SyntaxNodeRef, direction: Direction) -> Option<SyntaxNodeRef> {
     node.siblings(direction)
         .skip(1)
@@ -183,7 +605,7 @@ fn non_trivia_sibling(node: SyntaxNodeRef, direction: Direction) -> Option<Synta
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{check_action, check_action_range};
+    use crate::test_utils::{check_action, check_action_range, extract_offset};
 
     #[test]
     fn test_swap_comma() {
@@ -333,4 +755,189 @@ fn foo() {
         );
     }
 
+    #[test]
+    fn test_add_type_annotation() {
+        check_action(
+            "
+fn foo() {
+    let <|>x = 92;
+}",
+            "
+fn foo() {
+    let <|>x: i32 = 92;
+}",
+            |file, off| add_type_annotation(file, off).map(|f| f()),
+        );
+        check_action(
+            "
+fn foo() {
+    let x<|> = 1.0f32;
+}",
+            "
+fn foo() {
+    let x<|>: f32 = 1.0f32;
+}",
+            |file, off| add_type_annotation(file, off).map(|f| f()),
+        );
+    }
+
+    #[test]
+    fn test_add_type_annotation_not_applicable() {
+        let check = |text: &str| {
+            let (offset, text) = extract_offset(text);
+            let file = SourceFileNode::parse(&text);
+            assert!(add_type_annotation(&file, offset).is_none());
+        };
+        check("fn foo() { let <|>x: i32 = 92; }");
+        check("fn foo() { let <|>x = bar(); }");
+        check("fn foo() { let <|>(x, y) = (1, 2); }");
+    }
+
+    #[test]
+    fn test_convert_match_to_if_let() {
+        check_action(
+            "
+fn foo() {
+    <|>match opt {
+        Some(x) => bar(x),
+        None => { baz() }
+    }
+}",
+            "
+fn foo() {
+    <|>if let Some(x) = opt { bar(x) } else { baz() }
+}",
+            |file, off| convert_match_to_if_let(file, off).map(|f| f()),
+        );
+        check_action(
+            "
+fn foo() {
+    match<|> opt {
+        None => (),
+        Some(x) => bar(x),
+    }
+}",
+            "
+fn foo() {
+    <|>if let Some(x) = opt { bar(x) } else { () }
+}",
+            |file, off| convert_match_to_if_let(file, off).map(|f| f()),
+        );
+    }
+
+    #[test]
+    fn test_convert_match_to_if_let_not_applicable() {
+        let check = |text: &str| {
+            let (offset, text) = extract_offset(text);
+            let file = SourceFileNode::parse(&text);
+            assert!(convert_match_to_if_let(&file, offset).is_none());
+        };
+        check("fn foo() { <|>match x { 1 => (), 2 => (), _ => () } }");
+        check("fn foo() { <|>match x { _ => (), Some(x) => () } }");
+        check("fn foo() { <|>match x { Some(x) if x > 0 => (), _ => () } }");
+        check("fn foo() { match x { Some(x) => <|>(), _ => () } }");
+    }
+
+    #[test]
+    fn test_convert_closure_to_fn() {
+        check_action(
+            "fn main() { let <|>add = |x: u32, y| x + y; add(1, 2); }",
+            "fn main() { <|>fn add(x: u32, y: _) -> _ { x + y } add(1, 2); }",
+            |file, off| convert_closure_to_fn(file, off).map(|f| f()),
+        );
+        check_action(
+            "fn main() { let x = 1; let f = <|>|x| { x }; }",
+            "fn main() { let x = 1; <|>fn f(x: _) -> _ { x } }",
+            |file, off| convert_closure_to_fn(file, off).map(|f| f()),
+        );
+        check_action(
+            "fn main() { let <|>answer = || -> u64 { 92 }; }",
+            "fn main() { <|>fn answer() -> u64 { 92 } }",
+            |file, off| convert_closure_to_fn(file, off).map(|f| f()),
+        );
+        check_action(
+            "fn main() { let <|>answer = || 92; }",
+            "fn main() { <|>fn answer() -> i32 { 92 } }",
+            |file, off| convert_closure_to_fn(file, off).map(|f| f()),
+        );
+    }
+
+    #[test]
+    fn test_convert_closure_to_fn_not_applicable() {
+        let check = |text: &str| {
+            let (offset, text) = extract_offset(text);
+            let file = SourceFileNode::parse(&text);
+            assert!(convert_closure_to_fn(&file, offset).is_none());
+        };
+        check("fn main() { let k = 2; let <|>f = |x| x * k; }");
+        check("fn main(k: u32) { let <|>f = |x| { let y = k; x + y }; }");
+        check("impl S { fn foo(&self) { let <|>f = || self.bar(); } }");
+        check("fn main() { let <|>x = 92; }");
+    }
+
+    #[test]
+    fn test_add_from_impl() {
+        check_action(
+            "struct Meters(<|>f64);\n",
+            "struct Meters(<|>f64);\n\nimpl From<f64> for Meters {\n    fn from(v: f64) -> Self {\n        Meters(v)\n    }\n}\n",
+            |file, off| add_from_impl(file, off).map(|f| f()),
+        );
+        check_action(
+            "struct <|>Id(pub Vec<u8>);",
+            "struct <|>Id(pub Vec<u8>);\n\nimpl From<Vec<u8>> for Id {\n    fn from(v: Vec<u8>) -> Self {\n        Id(v)\n    }\n}",
+            |file, off| add_from_impl(file, off).map(|f| f()),
+        );
+    }
+
+    #[test]
+    fn test_add_from_impl_not_applicable() {
+        let check = |text: &str| {
+            let (offset, text) = extract_offset(text);
+            let file = SourceFileNode::parse(&text);
+            assert!(add_from_impl(&file, offset).is_none());
+        };
+        check("struct <|>Point(f64, f64);");
+        check("struct <|>Meters { value: f64 }");
+        check("struct <|>Wrapper<T>(T);");
+        check("struct <|>Meters(f64);\nimpl From< f64 > for Meters {}");
+        check("struct <|>Id(Vec<u8>);\nimpl From<Vec</* bytes */ u8>> for Id {}");
+        check("struct <|>Meters(f64);\nimpl std::convert::From<f64> for Meters {}");
+    }
+
+    #[test]
+    fn test_add_from_impl_other_impls() {
+        check_action(
+            "struct <|>Id(u8);\nimpl From<u8> for Other {}\nimpl Into<u8> for Id {}\nimpl From<u16> for Id {}",
+            "struct <|>Id(u8);\n\nimpl From<u8> for Id {\n    fn from(v: u8) -> Self {\n        Id(v)\n    }\n}\nimpl From<u8> for Other {}\nimpl Into<u8> for Id {}\nimpl From<u16> for Id {}",
+            |file, off| add_from_impl(file, off).map(|f| f()),
+        );
+    }
+
+    #[test]
+    fn test_convert_fn_to_closure() {
+        check_action(
+            "fn main() { <|>fn add(x: u32, y: u32) -> u32 { x + y } add(1, 2); }",
+            "fn main() { <|>let add = |x: u32, y: u32| -> u32 { x + y }; add(1, 2); }",
+            |file, off| convert_fn_to_closure(file, off).map(|f| f()),
+        );
+        check_action(
+            "fn main() { fn <|>log(x: u32) { print(x) } log(1); }",
+            "fn main() { <|>let log = |x: u32| print(x); log(1); }",
+            |file, off| convert_fn_to_closure(file, off).map(|f| f()),
+        );
+    }
+
+    #[test]
+    fn test_convert_fn_to_closure_not_applicable() {
+        let check = |text: &str| {
+            let (offset, text) = extract_offset(text);
+            let file = SourceFileNode::parse(&text);
+            assert!(convert_fn_to_closure(&file, offset).is_none());
+        };
+        check("fn <|>main() {}");
+        check("fn main() { fn <|>fact(n: u32) -> u32 { n * fact(n - 1) } }");
+        check("fn main() { go(); fn <|>go() {} }");
+        check("fn main() { fn <|>id<T>(x: T) -> T { x } }");
+        check("fn main() { fn foo() { <|>bar() } }");
+    }
 }
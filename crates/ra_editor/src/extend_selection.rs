@@ -1,5 +1,5 @@
 use ra_syntax::{
-    algo::{find_covering_node, find_leaf_at_offset, LeafAtOffset},
+    algo::{find_leaf_at_offset, LeafAtOffset},
     Direction, SourceFileNode,
     SyntaxKind::*,
     SyntaxNodeRef, TextRange, TextUnit,
@@ -10,6 +10,23 @@ pub fn extend_selection(file: &SourceFileNode, range: TextRange) -> Option<TextR
     extend(syntax.borrowed(), range)
 }
 
+/// Ranges of the token at `offset` and of each of its ancestors, innermost
+/// first and without repeats, for `textDocument/selectionRange`.
+pub fn selection_ranges(file: &SourceFileNode, offset: TextUnit) -> Vec<TextRange> {
+    let leaf = match find_leaf_at_offset(file.syntax(), offset) {
+        LeafAtOffset::None => return Vec::new(),
+        LeafAtOffset::Single(l) => l,
+        LeafAtOffset::Between(l, r) => pick_best(l, r),
+    };
+    let mut res: Vec<TextRange> = Vec::new();
+    for node in leaf.ancestors() {
+        if res.last() != Some(&node.range()) {
+            res.push(node.range());
+        }
+    }
+    res
+}
+
 pub(crate) fn extend(root: SyntaxNodeRef, range: TextRange) -> Option<TextRange> {
     if range.is_empty() {
         let offset = range.start();
@@ -30,7 +47,7 @@ pub(crate) fn extend(root: SyntaxNodeRef, range: TextRange) -> Option<TextRange>
         };
         return Some(leaf_range);
     };
-    let node = find_covering_node(root, range);
+    let node = root.covering_node(range);
     if node.kind() == COMMENT && range == node.range() {
         if let Some(range) = extend_comments(node) {
             return Some(range);
@@ -101,7 +118,10 @@ fn pick_best<'a>(l: SyntaxNodeRef<'a>, r: SyntaxNodeRef<'a>) -> SyntaxNodeRef<'a
     }
 }
 
+/// Extends a line comment to the run of adjacent line comments of the same
+/// kind. A blank line ends the run.
 fn extend_comments(node: SyntaxNodeRef) -> Option<TextRange> {
+    comment_prefix(node)?;
     let prev = adj_comments(node, Direction::Prev);
     let next = adj_comments(node, Direction::Next);
     if prev != next {
@@ -112,10 +132,11 @@ fn extend_comments(node: SyntaxNodeRef) -> Option<TextRange> {
 }
 
 fn adj_comments(node: SyntaxNodeRef, dir: Direction) -> SyntaxNodeRef {
+    let prefix = comment_prefix(node);
     let mut res = node;
     for node in node.siblings(dir) {
         match node.kind() {
-            COMMENT => res = node,
+            COMMENT if comment_prefix(node) == prefix => res = node,
             WHITESPACE if !node.leaf_text().unwrap().as_str().contains("\n\n") => (),
             _ => break,
         }
@@ -123,6 +144,20 @@ fn adj_comments(node: SyntaxNodeRef, dir: Direction) -> SyntaxNodeRef {
     res
 }
 
+/// `//`, `///` or `//!` for line comments, `None` for block comments.
+fn comment_prefix(comment: SyntaxNodeRef) -> Option<&'static str> {
+    let text = comment.leaf_text()?.as_str();
+    if text.starts_with("//!") {
+        Some("//!")
+    } else if text.starts_with("///") && !text.starts_with("////") {
+        Some("///")
+    } else if text.starts_with("//") {
+        Some("//")
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,6 +174,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_selection_ranges() {
+        let (cursor, text) = extract_offset(r#"fn foo() { 1 + <|>92 }"#);
+        let file = SourceFileNode::parse(&text);
+        let ranges = selection_ranges(&file, cursor)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            vec!["92", "1 + 92", "{ 1 + 92 }", "fn foo() { 1 + 92 }"]
+        );
+    }
+
     #[test]
     fn test_extend_selection_arith() {
         do_check(r#"fn foo() { <|>1 + 1 }"#, &["1", "1 + 1", "{ 1 + 1 }"]);
@@ -230,6 +279,42 @@ _bar1<|>*/
         );
     }
 
+    #[test]
+    fn test_extend_selection_comment_block() {
+        do_check(
+            r#"
+fn foo() {
+    // first
+    // sec<|>ond
+    // third
+
+    // other block
+    bar();
+}
+"#,
+            &[
+                "second",
+                "// second",
+                "// first\n    // second\n    // third",
+                "{\n    // first\n    // second\n    // third\n\n    // other block\n    bar();\n}",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_extend_selection_comment_block_same_kind() {
+        do_check(
+            r#"
+/* block */
+// one
+// t<|>wo
+/// doc
+struct S;
+"#,
+            &["two", "// two", "// one\n// two"],
+        );
+    }
+
     #[test]
     fn test_extend_selection_prefer_idents() {
         do_check(
@@ -0,0 +1,294 @@
+//! A tiny evaluator for constant expressions, used to debug const evaluation.
+//!
+//! Only integer and boolean arithmetic is supported, together with references
+//! to other constants and calls of parameterless functions from the same file.
+
+use std::fmt;
+
+use ra_syntax::{
+    ast::{self, AstNode, NameOwner},
+    SourceFileNode,
+    SyntaxKind::*,
+    SyntaxNodeRef, TextUnit,
+};
+
+use crate::find_node_at_offset;
+
+/// Guards against constants which refer to themselves.
+const MAX_DEPTH: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Int(i128),
+    Bool(bool),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(it) => write!(f, "{}", it),
+            Value::Bool(it) => write!(f, "{}", it),
+        }
+    }
+}
+
+type EvalResult = Result<Value, String>;
+
+/// Evaluates the const item or the function call at `offset` and returns the
+/// value, or an explanation of why it can't be evaluated.
+pub fn interpret_function(file: &SourceFileNode, offset: TextUnit) -> String {
+    let res = if let Some(const_def) = find_node_at_offset::<ast::ConstDef>(file.syntax(), offset) {
+        eval_const(file, const_def, 0)
+    } else if let Some(call) = find_node_at_offset::<ast::CallExpr>(file.syntax(), offset) {
+        eval_call(file, call, 0)
+    } else {
+        return "no const item or function call at the cursor".to_string();
+    };
+    match res {
+        Ok(value) => value.to_string(),
+        Err(msg) => format!("can't evaluate: {}", msg),
+    }
+}
+
+fn eval_const(file: &SourceFileNode, const_def: ast::ConstDef, depth: u32) -> EvalResult {
+    let expr = const_def
+        .syntax()
+        .children()
+        .find_map(ast::Expr::cast)
+        .ok_or_else(|| "const has no initializer".to_string())?;
+    eval_expr(file, expr, depth + 1)
+}
+
+fn eval_call(file: &SourceFileNode, call: ast::CallExpr, depth: u32) -> EvalResult {
+    let name = match call.expr() {
+        Some(ast::Expr::PathExpr(path)) => path_name(path)?,
+        _ => return Err(format!("unsupported callee in `{}`", call.syntax().text())),
+    };
+    let fn_def = file
+        .syntax()
+        .descendants()
+        .filter_map(ast::FnDef::cast)
+        .find(|it| it.name().map_or(false, |it| it.text().as_str() == name))
+        .ok_or_else(|| format!("unknown function `{}`", name))?;
+    let has_params = fn_def.param_list().map_or(false, |it| {
+        it.params().next().is_some() || it.self_param().is_some()
+    });
+    if has_params {
+        return Err(format!("function `{}` has parameters", name));
+    }
+    let body = fn_def
+        .body()
+        .ok_or_else(|| format!("function `{}` has no body", name))?;
+    if body.statements().next().is_some() {
+        return Err(format!("function `{}` has statements", name));
+    }
+    let expr = body
+        .expr()
+        .ok_or_else(|| format!("function `{}` returns nothing", name))?;
+    eval_expr(file, expr, depth + 1)
+}
+
+fn eval_expr(file: &SourceFileNode, expr: ast::Expr, depth: u32) -> EvalResult {
+    if depth > MAX_DEPTH {
+        return Err("recursion limit reached".to_string());
+    }
+    match expr {
+        ast::Expr::Literal(it) => eval_literal(it.syntax()),
+        ast::Expr::ParenExpr(it) => eval_expr(file, single_expr(it.syntax())?, depth),
+        ast::Expr::PrefixExpr(it) => {
+            let value = eval_expr(file, single_expr(it.syntax())?, depth)?;
+            match (op_kind(it.syntax()), value) {
+                (Some(MINUS), Value::Int(it)) => it
+                    .checked_neg()
+                    .map(Value::Int)
+                    .ok_or_else(|| "arithmetic overflow".to_string()),
+                (Some(EXCL), Value::Bool(it)) => Ok(Value::Bool(!it)),
+                (Some(EXCL), Value::Int(it)) => Ok(Value::Int(!it)),
+                _ => Err(format!("unsupported expression `{}`", it.syntax().text())),
+            }
+        }
+        ast::Expr::BinExpr(it) => eval_bin_expr(file, it, depth),
+        ast::Expr::PathExpr(it) => {
+            let name = path_name(it)?;
+            let const_def = file
+                .syntax()
+                .descendants()
+                .filter_map(ast::ConstDef::cast)
+                .find(|it| it.name().map_or(false, |it| it.text().as_str() == name))
+                .ok_or_else(|| format!("unknown const `{}`", name))?;
+            eval_const(file, const_def, depth)
+        }
+        ast::Expr::CallExpr(it) => eval_call(file, it, depth),
+        _ => Err(format!("unsupported expression `{}`", expr.syntax().text())),
+    }
+}
+
+fn eval_bin_expr(file: &SourceFileNode, expr: ast::BinExpr, depth: u32) -> EvalResult {
+    let unsupported = || format!("unsupported expression `{}`", expr.syntax().text());
+    let mut operands = expr.syntax().children().filter_map(ast::Expr::cast);
+    let (lhs, rhs) = match (operands.next(), operands.next()) {
+        (Some(lhs), Some(rhs)) => (lhs, rhs),
+        _ => return Err(unsupported()),
+    };
+    let op = op_kind(expr.syntax()).ok_or_else(unsupported)?;
+    let lhs = eval_expr(file, lhs, depth)?;
+    // Short-circuit like the real thing does.
+    match (op, lhs) {
+        (AMPAMP, Value::Bool(false)) => return Ok(Value::Bool(false)),
+        (PIPEPIPE, Value::Bool(true)) => return Ok(Value::Bool(true)),
+        _ => (),
+    }
+    let rhs = eval_expr(file, rhs, depth)?;
+    let overflow = || "arithmetic overflow".to_string();
+    let res = match (lhs, rhs) {
+        (Value::Int(l), Value::Int(r)) => match op {
+            PLUS => Value::Int(l.checked_add(r).ok_or_else(overflow)?),
+            MINUS => Value::Int(l.checked_sub(r).ok_or_else(overflow)?),
+            STAR => Value::Int(l.checked_mul(r).ok_or_else(overflow)?),
+            SLASH | PERCENT if r == 0 => return Err("division by zero".to_string()),
+            SLASH => Value::Int(l.checked_div(r).ok_or_else(overflow)?),
+            PERCENT => Value::Int(l.checked_rem(r).ok_or_else(overflow)?),
+            AMP => Value::Int(l & r),
+            PIPE => Value::Int(l | r),
+            CARET => Value::Int(l ^ r),
+            SHL => {
+                let res = shift_amount(r).and_then(|r| l.checked_shl(r));
+                Value::Int(res.ok_or_else(overflow)?)
+            }
+            SHR => {
+                let res = shift_amount(r).and_then(|r| l.checked_shr(r));
+                Value::Int(res.ok_or_else(overflow)?)
+            }
+            EQEQ => Value::Bool(l == r),
+            NEQ => Value::Bool(l != r),
+            L_ANGLE => Value::Bool(l < r),
+            LTEQ => Value::Bool(l <= r),
+            R_ANGLE => Value::Bool(l > r),
+            GTEQ => Value::Bool(l >= r),
+            _ => return Err(unsupported()),
+        },
+        (Value::Bool(l), Value::Bool(r)) => match op {
+            AMPAMP | AMP => Value::Bool(l && r),
+            PIPEPIPE | PIPE => Value::Bool(l || r),
+            CARET | NEQ => Value::Bool(l != r),
+            EQEQ => Value::Bool(l == r),
+            _ => return Err(unsupported()),
+        },
+        _ => return Err(format!("mismatched types in `{}`", expr.syntax().text())),
+    };
+    Ok(res)
+}
+
+/// The right operand of a shift as a `u32`, if it fits.
+fn shift_amount(r: i128) -> Option<u32> {
+    if r >= 0 && r <= i128::from(std::u32::MAX) {
+        Some(r as u32)
+    } else {
+        None
+    }
+}
+
+fn eval_literal(literal: SyntaxNodeRef) -> EvalResult {
+    let token = literal
+        .first_child()
+        .ok_or_else(|| "empty literal".to_string())?;
+    match token.kind() {
+        TRUE_KW => Ok(Value::Bool(true)),
+        FALSE_KW => Ok(Value::Bool(false)),
+        INT_NUMBER => {
+            let text = token.leaf_text().unwrap().replace('_', "");
+            let (radix, digits) = match text.get(..2) {
+                Some("0x") => (16, &text[2..]),
+                Some("0o") => (8, &text[2..]),
+                Some("0b") => (2, &text[2..]),
+                _ => (10, &text[..]),
+            };
+            // Strip a type suffix, like in `92u8`.
+            let digits = match digits.find(|c: char| c == 'i' || c == 'u') {
+                Some(idx) => &digits[..idx],
+                None => digits,
+            };
+            i128::from_str_radix(digits, radix)
+                .map(Value::Int)
+                .map_err(|_| format!("invalid integer `{}`", token.text()))
+        }
+        _ => Err(format!("unsupported literal `{}`", literal.text())),
+    }
+}
+
+fn path_name(path: ast::PathExpr) -> Result<String, String> {
+    path.path()
+        .filter(|it| it.qualifier().is_none())
+        .and_then(|it| it.segment())
+        .and_then(|it| it.name_ref())
+        .map(|it| it.text().to_string())
+        .ok_or_else(|| format!("unsupported path `{}`", path.syntax().text()))
+}
+
+fn single_expr(node: SyntaxNodeRef) -> Result<ast::Expr, String> {
+    node.children()
+        .find_map(ast::Expr::cast)
+        .ok_or_else(|| format!("incomplete expression `{}`", node.text()))
+}
+
+/// The kind of the operator token of a prefix or binary expression.
+fn op_kind(node: SyntaxNodeRef) -> Option<ra_syntax::SyntaxKind> {
+    node.children()
+        .find(|it| it.is_leaf() && !it.kind().is_trivia() && ast::Expr::cast(*it).is_none())
+        .map(|it| it.kind())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::extract_offset;
+
+    fn check(code: &str, expected: &str) {
+        let (offset, code) = extract_offset(code);
+        let file = SourceFileNode::parse(&code);
+        assert_eq!(interpret_function(&file, offset), expected);
+    }
+
+    #[test]
+    fn test_interpret_const() {
+        check("const N: usize = <|>-(2 + 3) * 4 + 0x10;", "-4");
+        check("const B: bool = <|>1 < 2 && !false;", "true");
+        check("const M: u32 = 7; const <|>N: u32 = M % 4 << 2;", "12");
+        check(
+            "const N: u32 = <|>1 / 0;",
+            "can't evaluate: division by zero",
+        );
+        check(
+            "const N: i128 = <|>-(-0x7fff_ffff_ffff_ffff_ffff_ffff_ffff_ffff - 1);",
+            "can't evaluate: arithmetic overflow",
+        );
+        check(
+            "const N: i128 = <|>(-0x7fff_ffff_ffff_ffff_ffff_ffff_ffff_ffff - 1) / -1;",
+            "can't evaluate: arithmetic overflow",
+        );
+        check(
+            "const N: u32 = <|>1 << -1;",
+            "can't evaluate: arithmetic overflow",
+        );
+        check(
+            "const N: &str = <|>\"hello\";",
+            "can't evaluate: unsupported literal `\"hello\"`",
+        );
+    }
+
+    #[test]
+    fn test_interpret_call() {
+        check(
+            "fn answer() -> u32 { 40 + two() } fn two() -> u32 { 2 } fn main() { answer(<|>); }",
+            "42",
+        );
+        check(
+            "fn id(x: u32) -> u32 { x } fn main() { id(<|>1); }",
+            "can't evaluate: function `id` has parameters",
+        );
+        check(
+            "const N: u32 = N<|>;",
+            "can't evaluate: recursion limit reached",
+        );
+    }
+}
@@ -1,6 +1,7 @@
 mod code_actions;
 mod extend_selection;
 mod folding_ranges;
+mod interpret;
 mod line_index;
 mod symbols;
 #[cfg(test)]
@@ -8,68 +9,134 @@ mod test_utils;
 mod typing;
 
 pub use self::{
-    code_actions::{add_derive, add_impl, flip_comma, introduce_variable, LocalEdit},
-    extend_selection::extend_selection,
+    code_actions::{
+        add_derive, add_from_impl, add_impl, add_type_annotation, convert_closure_to_fn,
+        convert_fn_to_closure, convert_match_to_if_let, flip_comma, introduce_variable, LocalEdit,
+    },
+    extend_selection::{extend_selection, selection_ranges},
     folding_ranges::{folding_ranges, Fold, FoldKind},
-    line_index::{LineCol, LineIndex},
-    symbols::{file_structure, file_symbols, FileSymbol, StructureNode},
+    interpret::interpret_function,
+    line_index::{split_range_by_lines, LineCol, LineIndex},
+    symbols::{file_structure, file_structure_iter, file_symbols, FileSymbol, StructureNode},
     typing::{join_lines, on_enter, on_eq_typed},
 };
 use ra_text_edit::{TextEdit, TextEditBuilder};
 use ra_syntax::{
     algo::find_leaf_at_offset,
-    ast::{self, AstNode, NameOwner},
+    ast::{self, AstNode, AttrsOwner, NameOwner},
     SourceFileNode,
-    Location,
     SyntaxKind::{self, *},
     SyntaxNodeRef, TextRange, TextUnit,
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct HighlightedRange {
     pub range: TextRange,
     pub tag: &'static str,
+    /// Refinements of `tag`, like `mutable` for a `let mut` binding.
+    pub modifiers: Vec<&'static str>,
 }
 
 #[derive(Debug)]
 pub struct Diagnostic {
     pub range: TextRange,
     pub msg: String,
+    pub fix: Option<LocalEdit>,
 }
 
 #[derive(Debug)]
 pub struct Runnable {
     pub range: TextRange,
     pub kind: RunnableKind,
+    /// Cargo features which must be enabled for the runnable to be compiled,
+    /// from the `#[cfg(feature = "...")]` attributes of the function and of
+    /// its enclosing modules.
+    pub features: Vec<String>,
 }
 
 #[derive(Debug)]
 pub enum RunnableKind {
     Test { name: String },
+    Bench { name: String },
     Bin,
 }
 
+/// Besides brackets, matches the pipes of a closure's parameter list and the
+/// quotes of a string or char literal.
 pub fn matching_brace(file: &SourceFileNode, offset: TextUnit) -> Option<TextUnit> {
+    find_leaf_at_offset(file.syntax(), offset)
+        .filter_map(|node| match node.kind() {
+            PIPE => matching_pipe(node),
+            STRING | RAW_STRING | BYTE_STRING | RAW_BYTE_STRING | CHAR | BYTE => {
+                matching_quote(node, offset)
+            }
+            _ => matching_bracket(node),
+        })
+        .next()
+}
+
+fn matching_bracket(brace_node: SyntaxNodeRef) -> Option<TextUnit> {
     const BRACES: &[SyntaxKind] = &[
         L_CURLY, R_CURLY, L_BRACK, R_BRACK, L_PAREN, R_PAREN, L_ANGLE, R_ANGLE,
     ];
-    let (brace_node, brace_idx) = find_leaf_at_offset(file.syntax(), offset)
-        .filter_map(|node| {
-            let idx = BRACES.iter().position(|&brace| brace == node.kind())?;
-            Some((node, idx))
-        })
-        .next()?;
+    let brace_idx = BRACES
+        .iter()
+        .position(|&brace| brace == brace_node.kind())?;
     let parent = brace_node.parent()?;
     let matching_kind = BRACES[brace_idx ^ 1];
-    let matching_node = parent
+    let matching_node = parent.child_of_kind(matching_kind)?;
+    Some(matching_node.range().start())
+}
+
+fn matching_pipe(pipe: SyntaxNodeRef) -> Option<TextUnit> {
+    // Only the pipes around closure parameters come in pairs, a bitwise or
+    // a pattern `|` has no partner.
+    let param_list = pipe.parent().filter(|it| it.kind() == PARAM_LIST)?;
+    param_list.parent().filter(|it| it.kind() == LAMBDA_EXPR)?;
+    let matching_node = param_list
         .children()
-        .find(|node| node.kind() == matching_kind)?;
+        .find(|node| node.kind() == PIPE && *node != pipe)?;
     Some(matching_node.range().start())
 }
 
+fn matching_quote(literal: SyntaxNodeRef, offset: TextUnit) -> Option<TextUnit> {
+    let text = literal.leaf_text()?;
+    let quote = if literal.kind() == CHAR || literal.kind() == BYTE {
+        '\''
+    } else {
+        '"'
+    };
+    let start = literal.range().start();
+    let open = start + TextUnit::from_usize(text.find(quote)?);
+    let close = start + TextUnit::from_usize(text.rfind(quote)?);
+    // An unterminated literal has a single quote.
+    if open == close || !text.ends_with(|c| c == quote || c == '#') {
+        return None;
+    }
+    let touches = |pos: TextUnit| offset == pos || offset == pos + TextUnit::of_char(quote);
+    if touches(open) && offset != close {
+        Some(close)
+    } else if touches(close) {
+        Some(open)
+    } else {
+        None
+    }
+}
+
 pub fn highlight(file: &SourceFileNode) -> Vec<HighlightedRange> {
     let mut res = Vec::new();
+    // `Some(is_rust)` while inside a fenced code block of a doc comment.
+    let mut fence = None;
     for node in file.syntax().descendants() {
+        if node.kind() == COMMENT {
+            if let Some(prefix_len) = doc_comment_prefix_len(node) {
+                highlight_doc_comment(node, prefix_len, &mut fence, &mut res);
+                continue;
+            }
+        }
+        if node.kind() != WHITESPACE && node.leaf_text().is_some() {
+            fence = None;
+        }
         let tag = match node.kind() {
             COMMENT => "comment",
             STRING | RAW_STRING | RAW_BYTE_STRING | BYTE_STRING => "string",
@@ -84,24 +151,178 @@ pub fn highlight(file: &SourceFileNode) -> Vec<HighlightedRange> {
         res.push(HighlightedRange {
             range: node.range(),
             tag,
+            modifiers: highlight_modifiers(node),
         })
     }
     res
 }
 
+/// Mutable bindings, `unsafe` code and `static`/`const` items, as far as the
+/// surrounding syntax tells.
+fn highlight_modifiers(node: SyntaxNodeRef) -> Vec<&'static str> {
+    let mut res = Vec::new();
+    match node.kind() {
+        UNSAFE_KW => res.push("unsafe"),
+        NAME => {
+            let parent = match node.parent() {
+                Some(it) => it,
+                None => return res,
+            };
+            let has_kw = |kind| parent.children().any(|it| it.kind() == kind);
+            match parent.kind() {
+                BIND_PAT if has_kw(MUT_KW) => res.push("mutable"),
+                FN_DEF if has_kw(UNSAFE_KW) => res.push("unsafe"),
+                STATIC_DEF | CONST_DEF => {
+                    res.push("static");
+                    if has_kw(MUT_KW) {
+                        res.push("mutable");
+                    }
+                }
+                _ => (),
+            }
+        }
+        _ => (),
+    }
+    res
+}
+
+/// Length of the `///` or `//!` prefix of a line doc comment.
+fn doc_comment_prefix_len(comment: SyntaxNodeRef) -> Option<TextUnit> {
+    let text = comment.leaf_text()?;
+    if (text.starts_with("///") && !text.starts_with("////")) || text.starts_with("//!") {
+        Some(TextUnit::of_str("///"))
+    } else {
+        None
+    }
+}
+
+/// Tags the Rust code inside fenced code blocks of doc comments like the code
+/// itself, and the rest of the comment as a comment.
+fn highlight_doc_comment(
+    comment: SyntaxNodeRef,
+    prefix_len: TextUnit,
+    fence: &mut Option<bool>,
+    res: &mut Vec<HighlightedRange>,
+) {
+    let text = comment.leaf_text().unwrap();
+    let content = &text.as_str()[prefix_len.to_usize()..];
+    if content.trim_start().starts_with("```") {
+        *fence = match fence {
+            Some(_) => None,
+            None => Some(is_rust_fence(content.trim_start()[3..].trim())),
+        };
+    } else if *fence == Some(true) {
+        res.push(HighlightedRange {
+            range: TextRange::offset_len(comment.range().start(), prefix_len),
+            tag: "comment",
+            modifiers: Vec::new(),
+        });
+        let mut offset = comment.range().start() + prefix_len;
+        for token in ra_syntax::tokenize(content) {
+            let tag = match token.kind {
+                COMMENT => Some("comment"),
+                STRING | RAW_STRING | RAW_BYTE_STRING | BYTE_STRING => Some("string"),
+                IDENT => Some("text"),
+                INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE => Some("literal"),
+                LIFETIME => Some("parameter"),
+                k if k.is_keyword() => Some("keyword"),
+                _ => None,
+            };
+            if let Some(tag) = tag {
+                res.push(HighlightedRange {
+                    range: TextRange::offset_len(offset, token.len),
+                    tag,
+                    modifiers: Vec::new(),
+                });
+            }
+            offset += token.len;
+        }
+        return;
+    }
+    res.push(HighlightedRange {
+        range: comment.range(),
+        tag: "comment",
+        modifiers: Vec::new(),
+    });
+}
+
+/// Code blocks are Rust unless the info string names some other language.
+fn is_rust_fence(info: &str) -> bool {
+    info.split(',').map(|it| it.trim()).all(|it| match it {
+        "" | "rust" | "ignore" | "should_panic" | "no_run" | "compile_fail" | "edition2018" => true,
+        _ => false,
+    })
+}
+
 pub fn diagnostics(file: &SourceFileNode) -> Vec<Diagnostic> {
-    fn location_to_range(location: Location) -> TextRange {
-        match location {
-            Location::Offset(offset) => TextRange::offset_len(offset, 1.into()),
-            Location::Range(range) => range,
+    // Editors don't show empty ranges well, so point at the next character.
+    fn widen_empty(range: TextRange) -> TextRange {
+        if range.is_empty() {
+            TextRange::offset_len(range.start(), 1.into())
+        } else {
+            range
         }
     }
 
-    file.errors()
+    let mut res = file
+        .errors()
         .into_iter()
         .map(|err| Diagnostic {
-            range: location_to_range(err.location()),
+            range: widen_empty(err.range()),
             msg: format!("Syntax Error: {}", err),
+            fix: None,
+        })
+        .collect::<Vec<_>>();
+    res.extend(unclosed_delimiters(file));
+    res
+}
+
+/// Reports opening delimiters which are never closed, offering to insert the
+/// missing closing delimiter at the end of the node the opener belongs to.
+fn unclosed_delimiters(file: &SourceFileNode) -> Vec<Diagnostic> {
+    const DELIMITERS: &[(SyntaxKind, &str)] = &[
+        (L_CURLY, "{"),
+        (R_CURLY, "}"),
+        (L_BRACK, "["),
+        (R_BRACK, "]"),
+        (L_PAREN, "("),
+        (R_PAREN, ")"),
+    ];
+    let mut open: Vec<(SyntaxNodeRef, usize)> = Vec::new();
+    let mut unclosed = Vec::new();
+    for leaf in file.syntax().descendants() {
+        let idx = match DELIMITERS.iter().position(|&(kind, _)| kind == leaf.kind()) {
+            Some(idx) => idx,
+            None => continue,
+        };
+        if idx % 2 == 0 {
+            open.push((leaf, idx));
+            continue;
+        }
+        // A closing delimiter also closes everything opened after its match,
+        // and a stray one is left to the parser to report.
+        if let Some(pos) = open.iter().rposition(|&(_, opener)| opener == idx ^ 1) {
+            unclosed.extend(open.drain(pos + 1..));
+            open.pop();
+        }
+    }
+    unclosed.extend(open);
+    unclosed.sort_by_key(|(opener, _)| opener.range().start());
+    unclosed
+        .into_iter()
+        .map(|(opener, idx)| {
+            let (_, closer) = DELIMITERS[idx ^ 1];
+            let offset = opener.parent().unwrap_or(opener).range().end();
+            let mut edit = TextEditBuilder::new();
+            edit.insert(offset, closer.to_string());
+            Diagnostic {
+                range: opener.range(),
+                msg: format!("unclosed delimiter `{}`", DELIMITERS[idx].1),
+                fix: Some(LocalEdit {
+                    edit: edit.finish(),
+                    cursor_position: None,
+                }),
+            }
         })
         .collect()
 }
@@ -122,17 +343,67 @@ pub fn runnables(file: &SourceFileNode) -> Vec<Runnable> {
                 RunnableKind::Test {
                     name: name.to_string(),
                 }
+            } else if f.has_atom_attr("bench") {
+                RunnableKind::Bench {
+                    name: name.to_string(),
+                }
             } else {
                 return None;
             };
             Some(Runnable {
                 range: f.syntax().range(),
                 kind,
+                features: required_features(f),
             })
         })
         .collect()
 }
 
+fn required_features(fn_def: ast::FnDef) -> Vec<String> {
+    let module_attrs = fn_def
+        .syntax()
+        .ancestors()
+        .filter_map(ast::Module::cast)
+        .flat_map(|it| it.attrs());
+    let mut res = Vec::new();
+    for attr in fn_def.attrs().chain(module_attrs) {
+        match attr.as_call() {
+            Some((name, args)) if name == "cfg" => cfg_features(args, &mut res),
+            _ => (),
+        }
+    }
+    res.sort();
+    res.dedup();
+    res
+}
+
+/// Collects the features of a cfg predicate which must be enabled for it to
+/// hold. Features under `any` and `not` are not required, so they are skipped.
+fn cfg_features(predicate: ast::TokenTree, acc: &mut Vec<String>) {
+    let tokens = predicate
+        .syntax()
+        .children()
+        .filter(|it| !it.kind().is_trivia())
+        .collect::<Vec<_>>();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind() != IDENT {
+            continue;
+        }
+        match (token.leaf_text().unwrap().as_str(), &tokens[i + 1..]) {
+            ("feature", [eq, value, ..]) if eq.kind() == EQ && value.kind() == STRING => {
+                let value = value.leaf_text().unwrap();
+                acc.push(value[1..value.len() - 1].to_string());
+            }
+            ("all", [args, ..]) => {
+                if let Some(args) = ast::TokenTree::cast(*args) {
+                    cfg_features(args, acc);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
 pub fn find_node_at_offset<'a, N: AstNode<'a>>(
     syntax: SyntaxNodeRef<'a>,
     offset: TextUnit,
@@ -156,16 +427,77 @@ fn main() {}
         );
         let hls = highlight(&file);
         assert_eq_dbg(
-            r#"[HighlightedRange { range: [1; 11), tag: "comment" },
-                HighlightedRange { range: [12; 14), tag: "keyword" },
-                HighlightedRange { range: [15; 19), tag: "function" },
-                HighlightedRange { range: [29; 36), tag: "text" },
-                HighlightedRange { range: [38; 50), tag: "string" },
-                HighlightedRange { range: [52; 54), tag: "literal" }]"#,
+            r#"[HighlightedRange { range: [1; 11), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [12; 14), tag: "keyword", modifiers: [] },
+                HighlightedRange { range: [15; 19), tag: "function", modifiers: [] },
+                HighlightedRange { range: [29; 36), tag: "text", modifiers: [] },
+                HighlightedRange { range: [38; 50), tag: "string", modifiers: [] },
+                HighlightedRange { range: [52; 54), tag: "literal", modifiers: [] }]"#,
+            &hls,
+        );
+    }
+
+    #[test]
+    fn test_highlighting_doc_comment_code() {
+        let file = SourceFileNode::parse(
+            r#"
+/// Example:
+/// ```
+/// let x = 92;
+/// ```
+/// ```text
+/// let y
+/// ```
+fn foo() {}
+"#,
+        );
+        let hls = highlight(&file);
+        assert_eq_dbg(
+            r#"[HighlightedRange { range: [1; 13), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [14; 21), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [22; 25), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [26; 29), tag: "keyword", modifiers: [] },
+                HighlightedRange { range: [30; 31), tag: "text", modifiers: [] },
+                HighlightedRange { range: [34; 36), tag: "literal", modifiers: [] },
+                HighlightedRange { range: [38; 45), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [46; 57), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [58; 67), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [68; 75), tag: "comment", modifiers: [] },
+                HighlightedRange { range: [76; 78), tag: "keyword", modifiers: [] },
+                HighlightedRange { range: [79; 82), tag: "function", modifiers: [] }]"#,
             &hls,
         );
     }
 
+    #[test]
+    fn test_highlighting_modifiers() {
+        let file = SourceFileNode::parse(
+            r#"
+static mut COUNTER: u32 = 0;
+unsafe fn foo() {
+    let mut x = 92;
+    let y = x;
+}
+"#,
+        );
+        let modifiers = |range: TextRange| {
+            highlight(&file)
+                .into_iter()
+                .find(|it| it.range == range)
+                .unwrap()
+                .modifiers
+        };
+        let range_of = |text: &str| {
+            let start = file.syntax().text().to_string().find(text).unwrap();
+            TextRange::offset_len((start as u32).into(), TextUnit::of_str(text))
+        };
+        assert_eq!(modifiers(range_of("COUNTER")), vec!["static", "mutable"]);
+        assert_eq!(modifiers(range_of("unsafe")), vec!["unsafe"]);
+        assert_eq!(modifiers(range_of("foo")), vec!["unsafe"]);
+        assert_eq!(modifiers(range_of("x")), vec!["mutable"]);
+        assert_eq!(modifiers(range_of("y")), Vec::<&str>::new());
+    }
+
     #[test]
     fn test_runnables() {
         let file = SourceFileNode::parse(
@@ -178,17 +510,50 @@ fn test_foo() {}
 #[test]
 #[ignore]
 fn test_foo() {}
+
+#[bench]
+fn bench_foo(b: &mut Bencher) {}
 "#,
         );
         let runnables = runnables(&file);
         assert_eq_dbg(
-            r#"[Runnable { range: [1; 13), kind: Bin },
-                Runnable { range: [15; 39), kind: Test { name: "test_foo" } },
-                Runnable { range: [41; 75), kind: Test { name: "test_foo" } }]"#,
+            r#"[Runnable { range: [1; 13), kind: Bin, features: [] },
+                Runnable { range: [15; 39), kind: Test { name: "test_foo" }, features: [] },
+                Runnable { range: [41; 75), kind: Test { name: "test_foo" }, features: [] },
+                Runnable { range: [77; 118), kind: Bench { name: "bench_foo" }, features: [] }]"#,
             &runnables,
         )
     }
 
+    #[test]
+    fn test_runnables_features() {
+        let file = SourceFileNode::parse(
+            r#"
+#[cfg(all(feature = "serde", not(feature = "std")))]
+mod tests {
+    #[test]
+    #[cfg(feature = "derive")]
+    fn test_foo() {}
+
+    #[test]
+    #[cfg(any(feature = "a", feature = "b"))]
+    fn test_bar() {}
+}
+"#,
+        );
+        let features = runnables(&file)
+            .into_iter()
+            .map(|it| it.features)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            features,
+            vec![
+                vec!["derive".to_string(), "serde".to_string()],
+                vec!["serde".to_string()],
+            ]
+        );
+    }
+
     #[test]
     fn test_matching_brace() {
         fn do_check(before: &str, after: &str) {
@@ -203,5 +568,40 @@ fn test_foo() {}
         }
 
         do_check("struct Foo { a: i32, }<|>", "struct Foo <|>{ a: i32, }");
+        do_check(
+            "fn f() { let f = <|>|x, y| x; }",
+            "fn f() { let f = |x, y<|>| x; }",
+        );
+        do_check(
+            "fn f() { let f = |x, y|<|> x; }",
+            "fn f() { let f = <|>|x, y| x; }",
+        );
+        do_check(
+            r#"fn f() { let s = <|>"hello"; }"#,
+            r#"fn f() { let s = "hello<|>"; }"#,
+        );
+        do_check(
+            r#"fn f() { let s = "hello"<|>; }"#,
+            r#"fn f() { let s = <|>"hello"; }"#,
+        );
+        do_check(
+            r##"fn f() { let s = r#<|>"hi"#; }"##,
+            r##"fn f() { let s = r#"hi<|>"#; }"##,
+        );
+        do_check("fn f() { let c = <|>'x'; }", "fn f() { let c = 'x<|>'; }");
+    }
+
+    #[test]
+    fn test_matching_brace_no_match() {
+        fn do_check(code: &str) {
+            let (pos, code) = extract_offset(code);
+            let file = SourceFileNode::parse(&code);
+            assert_eq!(matching_brace(&file, pos), None);
+        }
+
+        do_check("fn f() { let x = 1 <|>| 2; }");
+        do_check("fn f() { match x { A <|>| B => () } }");
+        do_check(r#"fn f() { let s = "hel<|>lo"; }"#);
+        do_check(r#"fn f() { let s = <|>"hello; }"#);
     }
 }
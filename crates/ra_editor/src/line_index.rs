@@ -1,4 +1,4 @@
-use crate::TextUnit;
+use crate::{TextRange, TextUnit};
 use rustc_hash::FxHashMap;
 use superslice::Ext;
 
@@ -24,6 +24,15 @@ impl Utf16Char {
     fn len(&self) -> TextUnit {
         self.end - self.start
     }
+    /// Characters outside of the basic multilingual plane take four bytes in
+    /// UTF-8 and are encoded as a surrogate pair in UTF-16.
+    fn len_utf16(&self) -> TextUnit {
+        if self.len() == TextUnit::from_usize(4) {
+            TextUnit::from_usize(2)
+        } else {
+            TextUnit::from_usize(1)
+        }
+    }
 }
 
 impl LineIndex {
@@ -90,7 +99,7 @@ impl LineIndex {
             let mut correction = TextUnit::from_usize(0);
             for c in utf16_chars {
                 if col >= c.end {
-                    correction += c.len() - TextUnit::from_usize(1);
+                    correction += c.len() - c.len_utf16();
                 } else {
                     // From here on, all utf16 characters come *after* the character we are mapping,
                     // so we don't need to take them into account
@@ -109,7 +118,7 @@ impl LineIndex {
         if let Some(utf16_chars) = self.utf16_lines.get(&line) {
             for c in utf16_chars {
                 if col >= c.start {
-                    col += c.len() - TextUnit::from_usize(1);
+                    col += c.len() - c.len_utf16();
                 } else {
                     // From here on, all utf16 characters come *after* the character we are mapping,
                     // so we don't need to take them into account
@@ -122,6 +131,43 @@ impl LineIndex {
     }
 }
 
+/// Splits `range` into one piece per line it spans, leaving the line breaks
+/// out. Multiline tokens have to be sent to the client this way.
+pub fn split_range_by_lines(range: TextRange, line_index: &LineIndex) -> Vec<TextRange> {
+    let newlines = &line_index.newlines;
+    let first_line = newlines.upper_bound(&range.start()) - 1;
+    let mut res = Vec::new();
+    let mut start = range.start();
+    for &line_start in &newlines[first_line + 1..] {
+        if line_start >= range.end() {
+            break;
+        }
+        let line_end = line_start - TextUnit::of_char('\n');
+        if start < line_end {
+            res.push(TextRange::from_to(start, line_end));
+        }
+        start = line_start;
+    }
+    if start < range.end() {
+        res.push(TextRange::from_to(start, range.end()));
+    }
+    res
+}
+
+#[test]
+fn test_split_range_by_lines() {
+    let text = "fn foo() {}\n/* one\n   two\n   three */\n";
+    let index = LineIndex::new(text);
+    let start = text.find("/*").unwrap();
+    let end = text.find("*/").unwrap() + 2;
+    let range = TextRange::from_to((start as u32).into(), (end as u32).into());
+    let lines = split_range_by_lines(range, &index)
+        .into_iter()
+        .map(|it| &text[it])
+        .collect::<Vec<_>>();
+    assert_eq!(lines, vec!["/* one", "   two", "   three */"]);
+}
+
 #[test]
 fn test_line_index() {
     let text = "hello\nworld";
@@ -321,4 +367,28 @@ const C: char = \"メ メ\";
 
         assert_eq!(col_index.utf16_to_utf8_col(2, 15), TextUnit::from_usize(15));
     }
+
+    #[test]
+    fn test_surrogate_pair() {
+        let text = "let s = \"😀x\";\nfoo";
+        let index = LineIndex::new(text);
+        assert_eq!('😀'.len_utf8(), 4);
+        assert_eq!('😀'.len_utf16(), 2);
+
+        let x = TextUnit::from_usize(text.find('x').unwrap());
+        let line_col = LineCol {
+            line: 0,
+            col_utf16: 11,
+        };
+        assert_eq!(index.line_col(x), line_col);
+        assert_eq!(index.offset(line_col), x);
+
+        let foo = TextUnit::from_usize(text.find("foo").unwrap());
+        let line_col = LineCol {
+            line: 1,
+            col_utf16: 0,
+        };
+        assert_eq!(index.line_col(foo), line_col);
+        assert_eq!(index.offset(line_col), foo);
+    }
 }
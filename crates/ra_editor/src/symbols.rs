@@ -3,7 +3,9 @@ use crate::TextRange;
 use ra_syntax::{
     algo::visit::{visitor, Visitor},
     ast::{self, DocCommentsOwner, NameOwner},
-    AstNode, SourceFileNode, SmolStr, SyntaxKind, SyntaxNodeRef, WalkEvent,
+    AstNode, SourceFileNode, SmolStr,
+    SyntaxKind::{self, *},
+    SyntaxNode, SyntaxNodeRef, WalkEvent,
 };
 
 #[derive(Debug, Clone)]
@@ -13,6 +15,8 @@ pub struct StructureNode {
     pub navigation_range: TextRange,
     pub node_range: TextRange,
     pub kind: SyntaxKind,
+    /// Short description shown after the label, like a function's signature
+    pub detail: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -23,18 +27,48 @@ pub struct FileSymbol {
 }
 
 impl FileSymbol {
+    /// Range of the symbol's name, which is more precise than `node_range`
+    /// when navigating to the symbol.
+    pub fn name_range(&self, file: &SourceFileNode) -> Option<TextRange> {
+        let node = self.node(file)?;
+        let name = node.child_of_kind(NAME)?;
+        Some(name.range())
+    }
+    /// Path of the modules, traits and impls enclosing this symbol within the
+    /// file, e.g. `foo::Bar` for a method in `mod foo { impl Bar {} }`.
+    pub fn container_name(&self, file: &SourceFileNode) -> Option<String> {
+        let node = self.node(file)?;
+        let mut segments: Vec<String> = node
+            .ancestors()
+            .skip(1)
+            .filter_map(|node| {
+                visitor()
+                    .visit(|it: ast::Module| Some(it.name()?.text().to_string()))
+                    .visit(|it: ast::TraitDef| Some(it.name()?.text().to_string()))
+                    .visit(|it: ast::ImplItem| Some(it.target_type()?.syntax().text().to_string()))
+                    .accept(node)?
+            })
+            .collect();
+        if segments.is_empty() {
+            return None;
+        }
+        segments.reverse();
+        Some(segments.join("::"))
+    }
+    fn node<'a>(&self, file: &'a SourceFileNode) -> Option<SyntaxNodeRef<'a>> {
+        file.syntax()
+            .covering_node(self.node_range)
+            .ancestors()
+            .take_while(|node| node.range() == self.node_range)
+            .find(|node| node.kind() == self.kind)
+    }
     pub fn docs(&self, file: &SourceFileNode) -> Option<String> {
         file.syntax()
-            .descendants()
-            .filter(|node| node.kind() == self.kind && node.range() == self.node_range)
+            .descendants_of_kind(self.kind)
+            .filter(|node| node.range() == self.node_range)
             .filter_map(|node: SyntaxNodeRef| {
                 fn doc_comments<'a, N: DocCommentsOwner<'a>>(node: N) -> Option<String> {
-                    let comments = node.doc_comment_text();
-                    if comments.is_empty() {
-                        None
-                    } else {
-                        Some(comments)
-                    }
+                    node.docs()
                 }
 
                 visitor()
@@ -56,8 +90,8 @@ impl FileSymbol {
     pub fn description(&self, file: &SourceFileNode) -> Option<String> {
         // TODO: After type inference is done, add type information to improve the output
         file.syntax()
-            .descendants()
-            .filter(|node| node.kind() == self.kind && node.range() == self.node_range)
+            .descendants_of_kind(self.kind)
+            .filter(|node| node.range() == self.node_range)
             .filter_map(|node: SyntaxNodeRef| {
                 // TODO: Refactor to be have less repetition
                 visitor()
@@ -133,30 +167,105 @@ fn to_symbol(node: SyntaxNodeRef) -> Option<FileSymbol> {
 }
 
 pub fn file_structure(file: &SourceFileNode) -> Vec<StructureNode> {
-    let mut res = Vec::new();
-    let mut stack = Vec::new();
-
-    for event in file.syntax().preorder() {
-        match event {
-            WalkEvent::Enter(node) => {
-                if let Some(mut symbol) = structure_node(node) {
-                    symbol.parent = stack.last().map(|&n| n);
-                    stack.push(res.len());
-                    res.push(symbol);
+    file_structure_iter(file).collect()
+}
+
+/// Lazy version of `file_structure`: the tree is walked only as far as
+/// needed to produce the next node. The iterator owns the tree, so it can
+/// outlive the `SourceFileNode` it was created from.
+pub fn file_structure_iter(file: &SourceFileNode) -> impl Iterator<Item = StructureNode> {
+    FileStructure {
+        events: OwnedPreorder::new(file.syntax().owned()),
+        stack: Vec::new(),
+        n_yielded: 0,
+    }
+}
+
+/// Like `SyntaxNodeRef::preorder`, but doesn't borrow the tree.
+struct OwnedPreorder {
+    root: SyntaxNode,
+    next: Option<WalkEvent<SyntaxNode>>,
+}
+
+impl OwnedPreorder {
+    fn new(root: SyntaxNode) -> OwnedPreorder {
+        OwnedPreorder {
+            next: Some(WalkEvent::Enter(root.clone())),
+            root,
+        }
+    }
+}
+
+impl Iterator for OwnedPreorder {
+    type Item = WalkEvent<SyntaxNode>;
+
+    fn next(&mut self) -> Option<WalkEvent<SyntaxNode>> {
+        let event = self.next.take()?;
+        self.next = match &event {
+            WalkEvent::Enter(node) => Some(match node.first_child() {
+                Some(child) => WalkEvent::Enter(child),
+                None => WalkEvent::Leave(node.clone()),
+            }),
+            WalkEvent::Leave(node) if *node == self.root => None,
+            WalkEvent::Leave(node) => Some(match node.next_sibling() {
+                Some(sibling) => WalkEvent::Enter(sibling),
+                None => WalkEvent::Leave(node.parent().unwrap()),
+            }),
+        };
+        Some(event)
+    }
+}
+
+/// Turns the walk events of a tree into `StructureNode`s, in preorder.
+struct FileStructure<I> {
+    events: I,
+    /// Enclosing structure nodes, with their indices.
+    stack: Vec<(SyntaxNode, usize)>,
+    n_yielded: usize,
+}
+
+impl<I: Iterator<Item = WalkEvent<SyntaxNode>>> Iterator for FileStructure<I> {
+    type Item = StructureNode;
+
+    fn next(&mut self) -> Option<StructureNode> {
+        for event in &mut self.events {
+            match event {
+                WalkEvent::Enter(node) => {
+                    if let Some(mut symbol) = structure_node(node.borrowed()) {
+                        symbol.parent = self.stack.last().map(|&(_, idx)| idx);
+                        self.stack.push((node, self.n_yielded));
+                        self.n_yielded += 1;
+                        return Some(symbol);
+                    }
                 }
-            }
-            WalkEvent::Leave(node) => {
-                if structure_node(node).is_some() {
-                    stack.pop().unwrap();
+                WalkEvent::Leave(node) => {
+                    if self.stack.last().map(|(it, _)| it) == Some(&node) {
+                        self.stack.pop();
+                    }
                 }
             }
         }
+        None
     }
-    res
 }
 
 fn structure_node(node: SyntaxNodeRef) -> Option<StructureNode> {
     fn decl<'a, N: NameOwner<'a>>(node: N) -> Option<StructureNode> {
+        decl_with_detail(node, None)
+    }
+
+    fn decl_with_type_ref<'a, N: NameOwner<'a>>(
+        node: N,
+        type_ref: Option<ast::TypeRef<'a>>,
+    ) -> Option<StructureNode> {
+        let detail = type_ref.map(|it| collapse_ws(it.syntax()));
+        decl_with_detail(node, detail)
+    }
+
+    fn decl_with_detail<'a, N: NameOwner<'a>>(
+        node: N,
+        detail: Option<String>,
+    ) -> Option<StructureNode> {
         let name = node.name()?;
         Some(StructureNode {
             parent: None,
@@ -164,19 +273,51 @@ fn structure_node(node: SyntaxNodeRef) -> Option<StructureNode> {
             navigation_range: name.syntax().range(),
             node_range: node.syntax().range(),
             kind: node.syntax().kind(),
+            detail,
         })
     }
 
+    fn collapse_ws(node: SyntaxNodeRef) -> String {
+        node.text()
+            .to_string()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     visitor()
-        .visit(decl::<ast::FnDef>)
-        .visit(decl::<ast::StructDef>)
-        .visit(decl::<ast::NamedFieldDef>)
+        .visit(|fn_def: ast::FnDef| {
+            let mut detail = String::from("fn");
+            if let Some(param_list) = fn_def.param_list() {
+                detail.push_str(&collapse_ws(param_list.syntax()));
+            }
+            if let Some(ret_type) = fn_def.ret_type() {
+                detail.push(' ');
+                detail.push_str(&collapse_ws(ret_type.syntax()));
+            }
+            decl_with_detail(fn_def, Some(detail))
+        })
+        .visit(|s: ast::StructDef| {
+            let n_fields = s
+                .syntax()
+                .children()
+                .filter(|it| it.kind() == NAMED_FIELD_DEF_LIST || it.kind() == POS_FIELD_LIST)
+                .flat_map(|it| it.children())
+                .filter(|it| it.kind() == NAMED_FIELD_DEF || it.kind() == POS_FIELD)
+                .count();
+            let detail = match n_fields {
+                1 => "1 field".to_string(),
+                n => format!("{} fields", n),
+            };
+            decl_with_detail(s, Some(detail))
+        })
+        .visit(|it: ast::NamedFieldDef| decl_with_type_ref(it, it.type_ref()))
         .visit(decl::<ast::EnumDef>)
         .visit(decl::<ast::TraitDef>)
         .visit(decl::<ast::Module>)
-        .visit(decl::<ast::TypeDef>)
-        .visit(decl::<ast::ConstDef>)
-        .visit(decl::<ast::StaticDef>)
+        .visit(|it: ast::TypeDef| decl_with_type_ref(it, it.type_ref()))
+        .visit(|it: ast::ConstDef| decl_with_type_ref(it, it.type_ref()))
+        .visit(|it: ast::StaticDef| decl_with_type_ref(it, it.type_ref()))
         .visit(|im: ast::ImplItem| {
             let target_type = im.target_type()?;
             let target_trait = im.target_trait();
@@ -195,6 +336,7 @@ fn structure_node(node: SyntaxNodeRef) -> Option<StructureNode> {
                 navigation_range: target_type.syntax().range(),
                 node_range: im.syntax().range(),
                 kind: im.syntax().kind(),
+                detail: None,
             };
             Some(node)
         })
@@ -203,6 +345,8 @@ fn structure_node(node: SyntaxNodeRef) -> Option<StructureNode> {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use super::*;
     use test_utils::assert_eq_dbg;
 
@@ -230,17 +374,89 @@ impl fmt::Debug for E {}
         );
         let symbols = file_structure(&file);
         assert_eq_dbg(
-            r#"[StructureNode { parent: None, label: "Foo", navigation_range: [8; 11), node_range: [1; 26), kind: STRUCT_DEF },
-                StructureNode { parent: Some(0), label: "x", navigation_range: [18; 19), node_range: [18; 24), kind: NAMED_FIELD_DEF },
-                StructureNode { parent: None, label: "m", navigation_range: [32; 33), node_range: [28; 53), kind: MODULE },
-                StructureNode { parent: Some(2), label: "bar", navigation_range: [43; 46), node_range: [40; 51), kind: FN_DEF },
-                StructureNode { parent: None, label: "E", navigation_range: [60; 61), node_range: [55; 75), kind: ENUM_DEF },
-                StructureNode { parent: None, label: "T", navigation_range: [81; 82), node_range: [76; 88), kind: TYPE_DEF },
-                StructureNode { parent: None, label: "S", navigation_range: [96; 97), node_range: [89; 108), kind: STATIC_DEF },
-                StructureNode { parent: None, label: "C", navigation_range: [115; 116), node_range: [109; 127), kind: CONST_DEF },
-                StructureNode { parent: None, label: "impl E", navigation_range: [134; 135), node_range: [129; 138), kind: IMPL_ITEM },
-                StructureNode { parent: None, label: "impl fmt::Debug for E", navigation_range: [160; 161), node_range: [140; 164), kind: IMPL_ITEM }]"#,
+            r#"[StructureNode { parent: None, label: "Foo", navigation_range: [8; 11), node_range: [1; 26), kind: STRUCT_DEF, detail: Some("1 field") },
+                StructureNode { parent: Some(0), label: "x", navigation_range: [18; 19), node_range: [18; 24), kind: NAMED_FIELD_DEF, detail: Some("i32") },
+                StructureNode { parent: None, label: "m", navigation_range: [32; 33), node_range: [28; 53), kind: MODULE, detail: None },
+                StructureNode { parent: Some(2), label: "bar", navigation_range: [43; 46), node_range: [40; 51), kind: FN_DEF, detail: Some("fn()") },
+                StructureNode { parent: None, label: "E", navigation_range: [60; 61), node_range: [55; 75), kind: ENUM_DEF, detail: None },
+                StructureNode { parent: None, label: "T", navigation_range: [81; 82), node_range: [76; 88), kind: TYPE_DEF, detail: Some("()") },
+                StructureNode { parent: None, label: "S", navigation_range: [96; 97), node_range: [89; 108), kind: STATIC_DEF, detail: Some("i32") },
+                StructureNode { parent: None, label: "C", navigation_range: [115; 116), node_range: [109; 127), kind: CONST_DEF, detail: Some("i32") },
+                StructureNode { parent: None, label: "impl E", navigation_range: [134; 135), node_range: [129; 138), kind: IMPL_ITEM, detail: None },
+                StructureNode { parent: None, label: "impl fmt::Debug for E", navigation_range: [160; 161), node_range: [140; 164), kind: IMPL_ITEM, detail: None }]"#,
             &symbols,
         )
     }
+
+    #[test]
+    fn test_file_structure_iter_is_lazy() {
+        let file = SourceFileNode::parse(&"mod m { fn foo() {} }\n".repeat(1000));
+        let n_events = Cell::new(0);
+        let events =
+            OwnedPreorder::new(file.syntax().owned()).inspect(|_| n_events.set(n_events.get() + 1));
+        let mut iter = FileStructure {
+            events,
+            stack: Vec::new(),
+            n_yielded: 0,
+        };
+        let first = iter.next().unwrap();
+        assert_eq!(first.label, "m");
+        // Only the events up to the first module have been consumed.
+        let n_first = n_events.get();
+        assert!(n_first < 10, "walked {} events for the first node", n_first);
+        let rest = iter.collect::<Vec<_>>();
+        assert_eq!(rest.len(), 1999);
+        assert_eq!(rest[0].parent, Some(0));
+        assert_eq!(rest[1].parent, None);
+        assert_eq!(rest[2].parent, Some(2));
+        assert!(n_events.get() > 1000 * n_first);
+    }
+
+    #[test]
+    fn test_file_structure_fn_detail() {
+        let file = SourceFileNode::parse(
+            r#"
+fn foo(x: i32,
+       y: &str) -> Option<u32> {}
+"#,
+        );
+        let details: Vec<_> = file_structure(&file)
+            .into_iter()
+            .map(|it| it.detail)
+            .collect();
+        assert_eq_dbg(r#"[Some("fn(x: i32, y: &str) -> Option<u32>")]"#, &details)
+    }
+
+    #[test]
+    fn test_symbol_container_name_and_name_range() {
+        let file = SourceFileNode::parse(
+            r#"
+mod foo {
+    struct Bar;
+    impl Bar {
+        fn baz(&self) {}
+    }
+}
+fn quux() {}
+"#,
+        );
+        let symbols = file_symbols(&file);
+        let info: Vec<_> = symbols
+            .iter()
+            .map(|it| {
+                (
+                    it.name.as_str(),
+                    it.container_name(&file),
+                    it.name_range(&file),
+                )
+            })
+            .collect();
+        assert_eq_dbg(
+            r#"[("foo", None, Some([5; 8))),
+                ("Bar", Some("foo"), Some([22; 25))),
+                ("baz", Some("foo::Bar"), Some([53; 56))),
+                ("quux", None, Some([78; 82)))]"#,
+            &info,
+        )
+    }
 }
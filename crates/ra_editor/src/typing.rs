@@ -1,7 +1,7 @@
 use std::mem;
 
 use ra_syntax::{
-    algo::{find_covering_node, find_leaf_at_offset, LeafAtOffset},
+    algo::{find_leaf_at_offset, LeafAtOffset},
     ast,
     text_utils::intersect,
     AstNode, SourceFileNode, SyntaxKind,
@@ -30,8 +30,9 @@ pub fn join_lines(file: &SourceFileNode, range: TextRange) -> LocalEdit {
         range
     };
 
-    let node = find_covering_node(file.syntax(), range);
+    let node = file.syntax().covering_node(range);
     let mut edit = TextEditBuilder::new();
+    let mut seam = None;
     for node in node.descendants() {
         let text = match node.leaf_text() {
             Some(text) => text,
@@ -46,13 +47,23 @@ pub fn join_lines(file: &SourceFileNode, range: TextRange) -> LocalEdit {
             let off = node.range().start() + range.start() + pos;
             if !edit.invalidates_offset(off) {
                 remove_newline(&mut edit, node, text.as_str(), off);
+                seam = seam.or(Some(off));
             }
         }
     }
 
+    let edit = edit.finish();
+    // Put the cursor where the first two lines were glued together.
+    let cursor_position = seam.and_then(|seam| {
+        let atom = edit
+            .as_atoms()
+            .iter()
+            .find(|atom| contains_offset_nonstrict(atom.delete, seam))?;
+        edit.apply_to_offset(atom.delete.start())
+    });
     LocalEdit {
-        edit: edit.finish(),
-        cursor_position: None,
+        edit,
+        cursor_position,
     }
 }
 
@@ -138,12 +149,17 @@ fn remove_newline(
     offset: TextUnit,
 ) {
     if node.kind() != WHITESPACE || node_text.bytes().filter(|&b| b == b'\n').count() != 1 {
-        // The node is either the first or the last in the file
+        // The node is either the first or the last in the file, or it spans
+        // several lines. Collapse the whole run of blank lines into a single
+        // space, so that there's one edit per boundary.
         let suff = &node_text[TextRange::from_to(
             offset - node.range().start() + TextUnit::of_char('\n'),
             TextUnit::of_str(node_text),
         )];
-        let spaces = suff.bytes().take_while(|&b| b == b' ').count();
+        let spaces = suff
+            .bytes()
+            .take_while(|&b| b == b' ' || b == b'\n')
+            .count();
 
         edit.replace(
             TextRange::offset_len(offset, ((spaces + 1) as u32).into()),
@@ -265,7 +281,7 @@ fn foo() {
 ",
             r"
 fn foo() {
-    <|>foo(1)
+    foo(1<|>)
 }
 ",
         );
@@ -283,7 +299,7 @@ pub fn reparse(&self, edit: &AtomTextEdit) -> File {
 ",
             r"
 pub fn reparse(&self, edit: &AtomTextEdit) -> File {
-    <|>self.incremental_reparse(edit).unwrap_or_else(|| self.full_reparse(edit))
+    self.incremental_reparse(edit).unwrap_or_else(|| <|>self.full_reparse(edit))
 }
 ",
         );
@@ -394,6 +410,49 @@ fn foo() {
         );
     }
 
+    #[test]
+    fn test_join_lines_cursor_at_seam() {
+        check_join_lines(
+            r"
+fn foo() {
+    <|>let x = foo(1,
+        2);
+}
+",
+            r"
+fn foo() {
+    let x = foo(1,<|> 2);
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_join_lines_coalesces_blank_lines() {
+        let (sel, before) = extract_range(
+            r"
+fn foo() {
+    <|>let x = 1;
+
+
+    let y = 2;<|>
+}
+",
+        );
+        let file = SourceFileNode::parse(&before);
+        let result = join_lines(&file, sel);
+        assert_eq!(result.edit.as_atoms().len(), 1);
+        let actual = add_cursor(&result.edit.apply(&before), result.cursor_position.unwrap());
+        assert_eq_text!(
+            r"
+fn foo() {
+    let x = 1;<|> let y = 2;
+}
+",
+            &actual
+        );
+    }
+
     fn check_join_lines_sel(before: &str, after: &str) {
         let (sel, before) = extract_range(before);
         let file = SourceFileNode::parse(&before);
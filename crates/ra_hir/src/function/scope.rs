@@ -58,6 +58,15 @@ impl FnScopes {
         })
     }
     pub fn resolve_local_name<'a>(&'a self, name_ref: ast::NameRef) -> Option<&'a ScopeEntry> {
+        // Local variables can't be referred to by a qualified path.
+        let path = name_ref
+            .syntax()
+            .parent()
+            .and_then(ast::PathSegment::cast)
+            .map(|it| it.parent_path());
+        if path.and_then(|it| it.qualifier()).is_some() {
+            return None;
+        }
         let mut shadowed = FxHashSet::default();
         let ret = self
             .scope_chain(name_ref.syntax())
@@ -68,6 +77,25 @@ impl FnScopes {
         ret
     }
 
+    /// Renders each scope with its parent and bindings, one per line, for
+    /// debugging.
+    pub fn debug_dump(&self) -> String {
+        let mut buf = String::new();
+        for (id, data) in self.scopes.iter() {
+            buf.push_str(&format!("scope {:?}", id));
+            if let Some(parent) = data.parent {
+                buf.push_str(&format!(" in {:?}", parent));
+            }
+            let entries = data
+                .entries
+                .iter()
+                .map(|it| format!("{}@{:?}", it.name(), it.ptr().range()))
+                .collect::<Vec<_>>();
+            buf.push_str(&format!(": {}\n", entries.join(", ")));
+        }
+        buf
+    }
+
     pub fn find_all_refs(&self, pat: ast::BindPat) -> Vec<ReferenceDescriptor> {
         let fn_def = pat.syntax().ancestors().find_map(ast::FnDef::cast).unwrap();
         let name_ptr = LocalSyntaxPtr::new(pat.syntax());
@@ -444,4 +472,20 @@ mod tests {
             46,
         );
     }
+
+    #[test]
+    fn test_resolve_local_name_qualified_path() {
+        let (off, code) = extract_offset(
+            r"
+        fn foo(x: String) {
+            super::x<|>;
+        }",
+        );
+        let file = SourceFileNode::parse(&code);
+        let fn_def: ast::FnDef = find_node_at_offset(file.syntax(), off).unwrap();
+        let name_ref: ast::NameRef = find_node_at_offset(file.syntax(), off).unwrap();
+
+        let scopes = FnScopes::new(fn_def);
+        assert!(scopes.resolve_local_name(name_ref).is_none());
+    }
 }
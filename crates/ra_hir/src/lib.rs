@@ -39,7 +39,7 @@ use crate::{
 pub use self::{
     path::{Path, PathKind},
     krate::Crate,
-    module::{Module, ModuleId, Problem, nameres::ItemMap},
+    module::{Module, ModuleId, Problem, nameres::{ItemMap, Resolution}},
     function::{Function, FnScopes},
 };
 
@@ -100,6 +100,13 @@ impl DefId {
         };
         Ok(res)
     }
+
+    /// The file and the syntax node of the item declaring this def.
+    pub fn source(self, db: &impl HirDatabase) -> (FileId, SyntaxNode) {
+        let loc = self.loc(db);
+        let syntax = db.file_item(loc.source_item_id);
+        (loc.source_item_id.file_id, syntax)
+    }
 }
 
 /// Identifier of item within a specific file. This is stable over reparses, so
@@ -19,14 +19,15 @@ use super::{
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub enum Submodule {
-    Declaration(SmolStr),
+    /// `mod foo;`, with the value of its `#[path]` attribute, if any.
+    Declaration(SmolStr, Option<SmolStr>),
     Definition(SmolStr, ModuleSource),
 }
 
 impl Submodule {
     fn name(&self) -> &SmolStr {
         match self {
-            Submodule::Declaration(name) => name,
+            Submodule::Declaration(name, _) => name,
             Submodule::Definition(name, _) => name,
         }
     }
@@ -109,9 +110,11 @@ fn build_subtree(
         });
 
         let (points_to, problem) = match sub {
-            Submodule::Declaration(name) => {
-                let (points_to, problem) =
-                    resolve_submodule(source, &name, &source_root.file_resolver);
+            Submodule::Declaration(name, path) => {
+                let (points_to, problem) = match path {
+                    Some(path) => resolve_path_attr(source, path, &source_root.file_resolver),
+                    None => resolve_submodule(source, name, &source_root.file_resolver),
+                };
                 let points_to = points_to
                     .into_iter()
                     .map(|file_id| match roots.remove(&file_id) {
@@ -152,6 +155,20 @@ fn build_subtree(
     Ok(id)
 }
 
+/// `#[path]` is relative to the directory of the file with the declaration,
+/// whatever the name of that file is.
+fn resolve_path_attr(
+    source: ModuleSource,
+    path: &SmolStr,
+    file_resolver: &FileResolverImp,
+) -> (Vec<FileId>, Option<Problem>) {
+    let candidate = RelativePathBuf::from(format!("../{}", path));
+    match file_resolver.resolve(source.file_id(), &candidate) {
+        Some(file_id) => (vec![file_id], None),
+        None => (Vec::new(), Some(Problem::UnresolvedModule { candidate })),
+    }
+}
+
 fn resolve_submodule(
     source: ModuleSource,
     name: &SmolStr,
@@ -6,7 +6,7 @@ use std::{
 use rustc_hash::FxHashMap;
 use ra_syntax::{
     AstNode,  SyntaxNode, SmolStr,
-    ast::{self, FnDef, FnDefNode, NameOwner, ModuleItemOwner}
+    ast::{self, AttrsOwner, FnDef, FnDefNode, NameOwner, ModuleItemOwner}
 };
 use ra_db::{SourceRootId, FileId, Cancelable,};
 
@@ -81,7 +81,12 @@ pub(crate) fn submodules(
         modules(root)
             .map(|(name, m)| {
                 if m.has_semi() {
-                    Submodule::Declaration(name)
+                    let path = m
+                        .attrs()
+                        .filter_map(|attr| attr.as_key_value())
+                        .find(|(key, _value)| key == "path")
+                        .map(|(_key, value)| value);
+                    Submodule::Declaration(name, path)
                 } else {
                     let src = ModuleSource::new_inline(db, file_id, m);
                     Submodule::Definition(name, src)
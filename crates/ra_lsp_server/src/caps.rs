@@ -1,11 +1,31 @@
 use languageserver_types::{
     CodeActionProviderCapability, CompletionOptions, DocumentOnTypeFormattingOptions,
-    ExecuteCommandOptions, FoldingRangeProviderCapability, RenameOptions, RenameProviderCapability,
-    ServerCapabilities, SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
-    TextDocumentSyncOptions,
+    ExecuteCommandOptions, FoldingRangeProviderCapability, ImplementationProviderCapability,
+    RenameOptions, RenameProviderCapability, ServerCapabilities, SignatureHelpOptions,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    TypeDefinitionProviderCapability,
 };
+use serde_json::{json, Value};
 
-pub fn server_capabilities() -> ServerCapabilities {
+use crate::semantic_tokens::{TOKEN_MODIFIERS, TOKEN_TYPES};
+
+/// Capabilities are returned as raw json, as `languageserver_types` doesn't
+/// know about some of the providers we support.
+pub fn server_capabilities() -> Value {
+    let mut caps = serde_json::to_value(lsp_capabilities()).unwrap();
+    caps["semanticTokensProvider"] = json!({
+        "legend": {
+            "tokenTypes": TOKEN_TYPES,
+            "tokenModifiers": TOKEN_MODIFIERS,
+        },
+        "full": { "delta": true },
+    });
+    caps["selectionRangeProvider"] = json!(true);
+    caps["callHierarchyProvider"] = json!(true);
+    caps
+}
+
+fn lsp_capabilities() -> ServerCapabilities {
     ServerCapabilities {
         text_document_sync: Some(TextDocumentSyncCapability::Options(
             TextDocumentSyncOptions {
@@ -18,17 +38,17 @@ pub fn server_capabilities() -> ServerCapabilities {
         )),
         hover_provider: Some(true),
         completion_provider: Some(CompletionOptions {
-            resolve_provider: None,
-            trigger_characters: Some(vec![":".to_string()]),
+            resolve_provider: Some(true),
+            trigger_characters: Some(vec![":".to_string(), ".".to_string()]),
         }),
         signature_help_provider: Some(SignatureHelpOptions {
             trigger_characters: Some(vec!["(".to_string(), ",".to_string(), ")".to_string()]),
         }),
         definition_provider: Some(true),
-        type_definition_provider: None,
-        implementation_provider: None,
+        type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+        implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
         references_provider: Some(true),
-        document_highlight_provider: None,
+        document_highlight_provider: Some(true),
         document_symbol_provider: Some(true),
         workspace_symbol_provider: Some(true),
         code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
@@ -50,3 +70,23 @@ pub fn server_capabilities() -> ServerCapabilities {
         workspace: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_tokens_legend_is_advertised() {
+        let caps = server_capabilities();
+        let legend = &caps["semanticTokensProvider"]["legend"];
+        assert_eq!(legend["tokenTypes"], json!(TOKEN_TYPES));
+        assert_eq!(legend["tokenModifiers"], json!(TOKEN_MODIFIERS));
+        assert_eq!(
+            legend["tokenModifiers"],
+            json!(["mutable", "unsafe", "static"])
+        );
+        assert_eq!(caps["hoverProvider"], json!(true));
+        assert_eq!(caps["selectionRangeProvider"], json!(true));
+        assert_eq!(caps["callHierarchyProvider"], json!(true));
+    }
+}
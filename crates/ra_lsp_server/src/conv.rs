@@ -4,10 +4,11 @@ use languageserver_types::{
 };
 use ra_analysis::{FileId, FileSystemEdit, SourceChange, SourceFileNodeEdit, FilePosition};
 use ra_editor::{LineCol, LineIndex};
-use ra_text_edit::{AtomTextEdit, TextEdit};
+use ra_text_edit::{AtomTextEdit, TextEdit, sort_and_check_conflicts};
 use ra_syntax::{SyntaxKind, TextRange, TextUnit};
+use gen_lsp_server::ErrorCode;
 
-use crate::{req, server_world::ServerWorld, Result};
+use crate::{req, server_world::ServerWorld, LspError, Result};
 
 pub trait Conv {
     type Output;
@@ -265,7 +266,9 @@ impl TryConvWith for SourceFileNodeEdit {
             version: None,
         };
         let line_index = world.analysis().file_line_index(self.file_id);
-        let edits = self.edits.into_iter().map_conv_with(&line_index).collect();
+        let mut edits = self.edits;
+        check_edit_conflicts(self.file_id, &mut edits)?;
+        let edits = edits.into_iter().map_conv_with(&line_index).collect();
         Ok(TextDocumentEdit {
             text_document,
             edits,
@@ -306,6 +309,16 @@ pub fn to_location(
     Ok(loc)
 }
 
+/// Overlapping edits would corrupt the file on the client side, so we refuse
+/// to send them.
+pub fn check_edit_conflicts(file_id: FileId, edits: &mut [AtomTextEdit]) -> Result<()> {
+    if let Err((r1, r2)) = sort_and_check_conflicts(edits) {
+        let message = format!("conflicting edits in {:?}: {:?} and {:?}", file_id, r1, r2);
+        return Err(LspError::new(ErrorCode::InternalError as i32, message).into());
+    }
+    Ok(())
+}
+
 pub trait MapConvWith<'a>: Sized + 'a {
     type Ctx;
     type Output;
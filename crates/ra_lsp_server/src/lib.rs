@@ -4,8 +4,12 @@ mod main_loop;
 mod path_map;
 mod project_model;
 pub mod req;
+mod semantic_tokens;
 mod server_world;
 mod vfs;
 
 pub type Result<T> = ::std::result::Result<T, ::failure::Error>;
-pub use crate::{caps::server_capabilities, main_loop::main_loop, main_loop::LspError};
+pub use crate::{
+    caps::server_capabilities, main_loop::main_loop, main_loop::LspError,
+    server_world::ServerConfig,
+};
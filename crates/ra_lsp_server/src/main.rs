@@ -2,7 +2,8 @@ use serde_derive::Deserialize;
 use serde::Deserialize as _D;
 use flexi_logger::{Duplicate, Logger};
 use gen_lsp_server::{run_server, stdio_transport};
-use ra_lsp_server::Result;
+use languageserver_types::MarkupKind;
+use ra_lsp_server::{Result, ServerConfig};
 
 fn main() -> Result<()> {
     ::std::env::set_var("RUST_BACKTRACE", "short");
@@ -28,6 +29,8 @@ fn main() -> Result<()> {
 #[serde(rename_all = "camelCase")]
 struct InitializationOptions {
     publish_decorations: bool,
+    #[serde(default)]
+    max_file_size: Option<usize>,
 }
 
 fn main_inner() -> Result<()> {
@@ -42,12 +45,45 @@ fn main_inner() -> Result<()> {
                 .root_uri
                 .and_then(|it| it.to_file_path().ok())
                 .unwrap_or(cwd);
-            let publish_decorations = params
+            let options = params
                 .initialization_options
-                .and_then(|v| InitializationOptions::deserialize(v).ok())
-                .map(|it| it.publish_decorations)
+                .and_then(|v| InitializationOptions::deserialize(v).ok());
+            let text_document_caps = params.capabilities.text_document;
+            let supports_snippets = text_document_caps
+                .as_ref()
+                .and_then(|it| it.completion.as_ref())
+                .and_then(|it| it.completion_item.as_ref())
+                .and_then(|it| it.snippet_support)
                 == Some(true);
-            ra_lsp_server::main_loop(false, root, publish_decorations, r, s)
+            let supports_markdown_completion = text_document_caps
+                .as_ref()
+                .and_then(|it| it.completion.as_ref())
+                .and_then(|it| it.completion_item.as_ref())
+                .and_then(|it| it.documentation_format.as_ref())
+                .map_or(false, |it| it.contains(&MarkupKind::Markdown));
+            let supports_markdown_hover = text_document_caps
+                .as_ref()
+                .and_then(|it| it.hover.as_ref())
+                .and_then(|it| it.content_format.as_ref())
+                .map_or(false, |it| it.contains(&MarkupKind::Markdown));
+            let supports_dynamic_watched_files = params
+                .capabilities
+                .workspace
+                .as_ref()
+                .and_then(|it| it.did_change_watched_files.as_ref())
+                .and_then(|it| it.dynamic_registration)
+                == Some(true);
+            let config = ServerConfig {
+                publish_decorations: options.as_ref().map(|it| it.publish_decorations)
+                    == Some(true),
+                max_file_size: options.and_then(|it| it.max_file_size),
+                supports_snippets,
+                supports_markdown_hover,
+                supports_markdown_completion,
+                supports_dynamic_watched_files,
+                client_request_timeout_secs: None,
+            };
+            ra_lsp_server::main_loop(false, root, config, r, s)
         },
     )?;
     log::info!("shutting down IO...");
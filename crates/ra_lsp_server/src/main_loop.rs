@@ -1,18 +1,21 @@
 mod handlers;
 mod subscriptions;
 
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
 
 use crossbeam_channel::{unbounded, select, Receiver, Sender};
 use gen_lsp_server::{
     handle_shutdown, ErrorCode, RawMessage, RawNotification, RawRequest, RawResponse,
 };
-use languageserver_types::NumberOrString;
+use languageserver_types::{CancelParams, NumberOrString};
 use ra_analysis::{Canceled, FileId, LibraryData};
 use rayon;
 use thread_worker::Worker;
 use threadpool::ThreadPool;
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 use serde::{de::DeserializeOwned, Serialize};
 use failure::{format_err, bail};
 use failure_derive::Fail;
@@ -21,7 +24,9 @@ use crate::{
     main_loop::subscriptions::Subscriptions,
     project_model::{workspace_loader, CargoWorkspace},
     req,
-    server_world::{ServerWorld, ServerWorldState},
+    server_world::{
+        uri_to_path, ClientRequest, ClientRequests, ServerConfig, ServerWorld, ServerWorldState,
+    },
     vfs::{self, FileEvent},
     Result,
 };
@@ -45,13 +50,15 @@ impl LspError {
 #[derive(Debug)]
 enum Task {
     Respond(RawResponse),
-    Notify(RawNotification),
+    /// A notification about an open file, computed while the file had the
+    /// given subscription generation. Dropped if the file was closed since.
+    NotifyFile(FileId, u64, RawNotification),
 }
 
 pub fn main_loop(
     internal_mode: bool,
     root: PathBuf,
-    publish_decorations: bool,
+    config: ServerConfig,
     msg_receiver: &Receiver<RawMessage>,
     msg_sender: &Sender<RawMessage>,
 ) -> Result<()> {
@@ -59,21 +66,28 @@ pub fn main_loop(
     let (task_sender, task_receiver) = unbounded::<Task>();
     let (fs_worker, fs_watcher) = vfs::roots_loader();
     let (ws_worker, ws_watcher) = workspace_loader();
+    let (client_request_sender, client_request_receiver) = unbounded::<ClientRequest>();
 
     log::info!("server initialized, serving requests");
-    let mut state = ServerWorldState::default();
+    let client_requests =
+        ClientRequests::new(client_request_sender, config.client_request_timeout_secs);
+    let mut state = ServerWorldState {
+        config: Arc::new(RwLock::new(config)),
+        client_requests: Some(client_requests),
+        ..ServerWorldState::default()
+    };
 
     let mut pending_requests = FxHashSet::default();
     let mut subs = Subscriptions::new();
     let main_res = main_loop_inner(
         internal_mode,
-        publish_decorations,
         root,
         &pool,
         msg_sender,
         msg_receiver,
         task_sender,
         task_receiver.clone(),
+        client_request_receiver,
         fs_worker,
         ws_worker,
         &mut state,
@@ -82,7 +96,7 @@ pub fn main_loop(
     );
 
     log::info!("waiting for tasks to finish...");
-    task_receiver.for_each(|task| on_task(task, msg_sender, &mut pending_requests));
+    task_receiver.for_each(|task| on_task(task, msg_sender, &mut pending_requests, &subs, &state));
     log::info!("...tasks have finished");
     log::info!("joining threadpool...");
     drop(pool);
@@ -100,13 +114,13 @@ pub fn main_loop(
 
 fn main_loop_inner(
     internal_mode: bool,
-    publish_decorations: bool,
     ws_root: PathBuf,
     pool: &ThreadPool,
     msg_sender: &Sender<RawMessage>,
     msg_receiver: &Receiver<RawMessage>,
     task_sender: Sender<Task>,
     task_receiver: Receiver<Task>,
+    client_request_receiver: Receiver<ClientRequest>,
     fs_worker: Worker<PathBuf, (PathBuf, Vec<FileEvent>)>,
     ws_worker: Worker<PathBuf, Result<CargoWorkspace>>,
     state: &mut ServerWorldState,
@@ -114,6 +128,20 @@ fn main_loop_inner(
     subs: &mut Subscriptions,
 ) -> Result<()> {
     let (libdata_sender, libdata_receiver) = unbounded();
+    // Requests sent to the client on behalf of handlers, by id. Dropping
+    // this on exit wakes up the handlers which still wait for a response.
+    let mut pending_client_requests = FxHashMap::<u64, Sender<RawResponse>>::default();
+    // `run_server` calls us after the `initialized` notification, which is
+    // when dynamic capabilities may be registered.
+    if state.config.read().unwrap().supports_dynamic_watched_files {
+        if let Some(client_requests) = state.client_requests.clone() {
+            pool.execute(move || {
+                if let Err(e) = register_watched_files(&client_requests) {
+                    log::error!("failed to register watched files: {}", e);
+                }
+            });
+        }
+    }
     ws_worker.send(ws_root.clone());
     fs_worker.send(ws_root.clone());
     loop {
@@ -121,6 +149,7 @@ fn main_loop_inner(
         enum Event {
             Msg(RawMessage),
             Task(Task),
+            ClientRequest(ClientRequest),
             Fs(PathBuf, Vec<FileEvent>),
             Ws(Result<CargoWorkspace>),
             Lib(LibraryData),
@@ -132,6 +161,7 @@ fn main_loop_inner(
                 None => bail!("client exited without shutdown"),
             },
             recv(task_receiver, task) => Event::Task(task.unwrap()),
+            recv(client_request_receiver, req) => Event::ClientRequest(req.unwrap()),
             recv(fs_worker.out, events) => match events {
                 None => bail!("roots watcher died"),
                 Some((pb, events)) => Event::Fs(pb, events),
@@ -144,11 +174,35 @@ fn main_loop_inner(
         };
         let mut state_changed = false;
         match event {
-            Event::Task(task) => on_task(task, msg_sender, pending_requests),
+            Event::Task(task) => on_task(task, msg_sender, pending_requests, subs, state),
+            Event::ClientRequest(ClientRequest::Send {
+                id,
+                method,
+                params,
+                response,
+            }) => {
+                pending_client_requests.insert(id, response);
+                let request = RawRequest {
+                    id,
+                    method: method.to_string(),
+                    params,
+                };
+                msg_sender.send(RawMessage::Request(request));
+            }
+            Event::ClientRequest(ClientRequest::Cancel(id)) => {
+                if pending_client_requests.remove(&id).is_some() {
+                    let params = CancelParams {
+                        id: NumberOrString::Number(id),
+                    };
+                    let not = RawNotification::new::<req::Cancel>(&params);
+                    msg_sender.send(RawMessage::Notification(not));
+                }
+            }
             Event::Fs(root, events) => {
                 log::info!("fs change, {}, {} events", root.display(), events.len());
                 if root == ws_root {
                     state.apply_fs_changes(events);
+                    feedback(internal_mode, "workspace files loaded", msg_sender);
                 } else {
                     let (files, resolver) = state.events_to_files(events);
                     let sender = libdata_sender.clone();
@@ -166,6 +220,7 @@ fn main_loop_inner(
                 Ok(ws) => {
                     let workspaces = vec![ws];
                     feedback(internal_mode, "workspace loaded", msg_sender);
+                    state.lib_roots.clear();
                     for ws in workspaces.iter() {
                         // Add each library as constant input. If library is
                         // within the workspace, don't treat it as a library.
@@ -191,12 +246,14 @@ fn main_loop_inner(
                             .filter(|&(idx, long)| {
                                 !roots[..idx].iter().any(|short| long.starts_with(short))
                             })
-                            .map(|(_idx, root)| root);
+                            .map(|(_idx, root)| root.to_owned())
+                            .collect::<Vec<_>>();
 
-                        for root in unique {
+                        for root in unique.iter() {
                             log::debug!("sending root, {}", root.display());
                             fs_worker.send(root.to_owned());
                         }
+                        state.lib_roots.extend(unique);
                     }
                     state.set_workspaces(workspaces);
                     state_changed = true;
@@ -209,6 +266,9 @@ fn main_loop_inner(
             }
             Event::Msg(msg) => match msg {
                 RawMessage::Request(req) => {
+                    if req.method == <req::Shutdown as req::Request>::METHOD {
+                        clear_diagnostics(state, subs, msg_sender);
+                    }
                     let req = match handle_shutdown(req, msg_sender) {
                         Some(req) => req,
                         None => return Ok(()),
@@ -230,7 +290,10 @@ fn main_loop_inner(
                     on_notification(msg_sender, state, pending_requests, subs, not)?;
                     state_changed = true;
                 }
-                RawMessage::Response(resp) => log::error!("unexpected response: {:?}", resp),
+                RawMessage::Response(resp) => match pending_client_requests.remove(&resp.id) {
+                    Some(handler) => handler.send(resp),
+                    None => log::error!("unexpected response: {:?}", resp),
+                },
             },
         };
 
@@ -238,22 +301,38 @@ fn main_loop_inner(
             update_file_notifications_on_threadpool(
                 pool,
                 state.snapshot(),
-                publish_decorations,
                 task_sender.clone(),
-                subs.subscriptions(),
+                subs.generations(),
             )
         }
     }
 }
 
-fn on_task(task: Task, msg_sender: &Sender<RawMessage>, pending_requests: &mut FxHashSet<u64>) {
+fn on_task(
+    task: Task,
+    msg_sender: &Sender<RawMessage>,
+    pending_requests: &mut FxHashSet<u64>,
+    subs: &Subscriptions,
+    state: &ServerWorldState,
+) {
     match task {
         Task::Respond(response) => {
             if pending_requests.remove(&response.id) {
+                let is_error = response.error.is_some();
+                state
+                    .request_stats
+                    .lock()
+                    .unwrap()
+                    .finish(response.id, is_error);
                 msg_sender.send(RawMessage::Response(response))
             }
         }
-        Task::Notify(n) => msg_sender.send(RawMessage::Notification(n)),
+        Task::NotifyFile(file_id, generation, n) => {
+            // The close already sent empty diagnostics, don't resurrect them.
+            if subs.is_current(file_id, generation) {
+                msg_sender.send(RawMessage::Notification(n))
+            }
+        }
     }
 }
 
@@ -264,6 +343,13 @@ fn on_request(
     sender: &Sender<Task>,
     req: RawRequest,
 ) -> Result<Option<RawRequest>> {
+    {
+        // Registered before dispatching, so that the status request sees
+        // itself as in flight.
+        let mut stats = world.request_stats.lock().unwrap();
+        stats.start(req.id, req.method.clone());
+        stats.queued = pool.queued_count();
+    }
     let mut pool_dispatcher = PoolDispatcher {
         req: Some(req),
         res: None,
@@ -272,8 +358,14 @@ fn on_request(
         sender,
     };
     let req = pool_dispatcher
+        .on::<req::AnalyzerStatus>(handlers::handle_analyzer_status)?
+        .on::<req::MemoryUsage>(handlers::handle_memory_usage)?
+        .on::<req::RequestMetrics>(handlers::handle_request_metrics)?
         .on::<req::SyntaxTree>(handlers::handle_syntax_tree)?
+        .on::<req::ViewHir>(handlers::handle_view_hir)?
+        .on::<req::InterpretFunction>(handlers::handle_interpret_function)?
         .on::<req::ExtendSelection>(handlers::handle_extend_selection)?
+        .on::<req::SelectionRangeRequest>(handlers::handle_selection_range)?
         .on::<req::FindMatchingBrace>(handlers::handle_find_matching_brace)?
         .on::<req::JoinLines>(handlers::handle_join_lines)?
         .on::<req::OnEnter>(handlers::handle_on_enter)?
@@ -281,17 +373,33 @@ fn on_request(
         .on::<req::DocumentSymbolRequest>(handlers::handle_document_symbol)?
         .on::<req::WorkspaceSymbol>(handlers::handle_workspace_symbol)?
         .on::<req::GotoDefinition>(handlers::handle_goto_definition)?
+        .on::<req::GotoTypeDefinition>(handlers::handle_goto_type_definition)?
+        .on::<req::GotoImplementation>(handlers::handle_goto_implementation)?
         .on::<req::ParentModule>(handlers::handle_parent_module)?
+        .on::<req::OpenCargoToml>(handlers::handle_open_cargo_toml)?
+        .on::<req::TraitMethodImpls>(handlers::handle_trait_method_impls)?
+        .on::<req::CallHierarchyPrepare>(handlers::handle_call_hierarchy_prepare)?
+        .on::<req::CallHierarchyIncomingCalls>(handlers::handle_call_hierarchy_incoming)?
+        .on::<req::CallHierarchyOutgoingCalls>(handlers::handle_call_hierarchy_outgoing)?
+        .on::<req::RelatedTests>(handlers::handle_related_tests)?
+        .on::<req::ExternalDocs>(handlers::handle_external_docs)?
         .on::<req::Runnables>(handlers::handle_runnables)?
         .on::<req::DecorationsRequest>(handlers::handle_decorations)?
+        .on::<req::SemanticTokensRequest>(handlers::handle_semantic_tokens)?
+        .on::<req::SemanticTokensDeltaRequest>(handlers::handle_semantic_tokens_delta)?
         .on::<req::Completion>(handlers::handle_completion)?
+        .on::<req::ResolveCompletionItem>(handlers::handle_resolve_completion_item)?
         .on::<req::CodeActionRequest>(handlers::handle_code_action)?
+        .on::<req::ExecuteCommand>(handlers::handle_execute_command)?
         .on::<req::FoldingRangeRequest>(handlers::handle_folding_range)?
         .on::<req::SignatureHelpRequest>(handlers::handle_signature_help)?
         .on::<req::HoverRequest>(handlers::handle_hover)?
         .on::<req::PrepareRenameRequest>(handlers::handle_prepare_rename)?
         .on::<req::Rename>(handlers::handle_rename)?
         .on::<req::References>(handlers::handle_references)?
+        .on::<req::DocumentHighlightRequest>(handlers::handle_document_highlight)?
+        .on::<req::Ssr>(handlers::handle_ssr)?
+        .on::<req::SsrAt>(handlers::handle_ssr_at)?
         .finish();
     match req {
         Ok(id) => {
@@ -299,10 +407,33 @@ fn on_request(
             assert!(inserted, "duplicate request: {}", id);
             Ok(None)
         }
-        Err(req) => Ok(Some(req)),
+        Err(req) => {
+            // Unknown method, answered with an error by the caller.
+            world.request_stats.lock().unwrap().finish(req.id, true);
+            Ok(Some(req))
+        }
     }
 }
 
+fn register_watched_files(client_requests: &ClientRequests) -> Result<()> {
+    let registration = req::Registration {
+        id: "workspace/didChangeWatchedFiles".to_string(),
+        method: "workspace/didChangeWatchedFiles".to_string(),
+        register_options: Some(serde_json::to_value(
+            req::DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![req::FileSystemWatcher {
+                    glob_pattern: "**/*.rs".to_string(),
+                    kind: None,
+                }],
+            },
+        )?),
+    };
+    let params = req::RegistrationParams {
+        registrations: vec![registration],
+    };
+    client_requests.send::<req::RegisterCapability>(&params)
+}
+
 fn on_notification(
     msg_sender: &Sender<RawMessage>,
     state: &mut ServerWorldState,
@@ -319,6 +450,7 @@ fn on_notification(
                 }
             };
             if pending_requests.remove(&id) {
+                state.request_stats.lock().unwrap().finish(id, true);
                 let response = RawResponse::err(
                     id,
                     ErrorCode::RequestCancelled as i32,
@@ -330,13 +462,51 @@ fn on_notification(
         }
         Err(not) => not,
     };
+    let not = match not.cast::<req::DidChangeWatchedFiles>() {
+        Ok(params) => {
+            let paths = params
+                .changes
+                .iter()
+                .map(|change| uri_to_path(&change.uri))
+                .collect::<Result<Vec<_>>>()?;
+            state.change_disk_files(paths);
+            return Ok(());
+        }
+        Err(not) => not,
+    };
+    let not = match not.cast::<req::DidChangeConfiguration>() {
+        Ok(params) => {
+            let was_publishing = state.config.read().unwrap().publish_decorations;
+            state.config.write().unwrap().update(&params.settings);
+            if was_publishing && !state.config.read().unwrap().publish_decorations {
+                // Clear decorations the client still shows for open files.
+                let world = state.snapshot();
+                for file_id in subs.subscriptions() {
+                    let uri = match world.file_id_to_uri(file_id) {
+                        Ok(it) => it,
+                        Err(e) => {
+                            log::error!("can't clear decorations: {}", e);
+                            continue;
+                        }
+                    };
+                    let params = req::PublishDecorationsParams {
+                        uri,
+                        decorations: Vec::new(),
+                    };
+                    let not = RawNotification::new::<req::PublishDecorations>(&params);
+                    msg_sender.send(RawMessage::Notification(not));
+                }
+            }
+            return Ok(());
+        }
+        Err(not) => not,
+    };
     let not = match not.cast::<req::DidOpenTextDocument>() {
         Ok(params) => {
             let uri = params.text_document.uri;
-            let path = uri
-                .to_file_path()
-                .map_err(|()| format_err!("invalid uri: {}", uri))?;
+            let path = uri_to_path(&uri)?;
             let file_id = state.add_mem_file(path, params.text_document.text);
+            state.set_doc_version(file_id, params.text_document.version);
             subs.add_sub(file_id);
             return Ok(());
         }
@@ -345,15 +515,13 @@ fn on_notification(
     let not = match not.cast::<req::DidChangeTextDocument>() {
         Ok(mut params) => {
             let uri = params.text_document.uri;
-            let path = uri
-                .to_file_path()
-                .map_err(|()| format_err!("invalid uri: {}", uri))?;
+            let path = uri_to_path(&uri)?;
             let text = params
                 .content_changes
                 .pop()
                 .ok_or_else(|| format_err!("empty changes"))?
                 .text;
-            state.change_mem_file(path.as_path(), text)?;
+            state.change_mem_file(path.as_path(), text, params.text_document.version)?;
             return Ok(());
         }
         Err(not) => not,
@@ -361,9 +529,7 @@ fn on_notification(
     let not = match not.cast::<req::DidCloseTextDocument>() {
         Ok(params) => {
             let uri = params.text_document.uri;
-            let path = uri
-                .to_file_path()
-                .map_err(|()| format_err!("invalid uri: {}", uri))?;
+            let path = uri_to_path(&uri)?;
             let file_id = state.remove_mem_file(path.as_path())?;
             subs.remove_sub(file_id);
             let params = req::PublishDiagnosticsParams {
@@ -376,10 +542,39 @@ fn on_notification(
         }
         Err(not) => not,
     };
-    log::error!("unhandled notification: {:?}", not);
+    if not.is_implementation_dependent() {
+        log::debug!("ignored notification: {:?}", not);
+    } else {
+        log::error!("unhandled notification: {:?}", not);
+    }
     Ok(())
 }
 
+/// Publishes empty diagnostics for every open file, so that the client isn't
+/// left with errors reported by a server which is going away.
+fn clear_diagnostics(
+    state: &ServerWorldState,
+    subs: &Subscriptions,
+    msg_sender: &Sender<RawMessage>,
+) {
+    let world = state.snapshot();
+    for file_id in subs.subscriptions() {
+        let uri = match world.file_id_to_uri(file_id) {
+            Ok(uri) => uri,
+            Err(e) => {
+                log::error!("failed to clear diagnostics: {:?}", e);
+                continue;
+            }
+        };
+        let params = req::PublishDiagnosticsParams {
+            uri,
+            diagnostics: Vec::new(),
+        };
+        let not = RawNotification::new::<req::PublishDiagnostics>(&params);
+        msg_sender.send(RawMessage::Notification(not));
+    }
+}
+
 struct PoolDispatcher<'a> {
     req: Option<RawRequest>,
     res: Option<u64>,
@@ -452,12 +647,11 @@ impl<'a> PoolDispatcher<'a> {
 fn update_file_notifications_on_threadpool(
     pool: &ThreadPool,
     world: ServerWorld,
-    publish_decorations: bool,
     sender: Sender<Task>,
-    subscriptions: Vec<FileId>,
+    subscriptions: Vec<(FileId, u64)>,
 ) {
     pool.execute(move || {
-        for file_id in subscriptions {
+        for (file_id, generation) in subscriptions {
             match handlers::publish_diagnostics(&world, file_id) {
                 Err(e) => {
                     if !is_canceled(&e) {
@@ -466,19 +660,22 @@ fn update_file_notifications_on_threadpool(
                 }
                 Ok(params) => {
                     let not = RawNotification::new::<req::PublishDiagnostics>(&params);
-                    sender.send(Task::Notify(not));
+                    sender.send(Task::NotifyFile(file_id, generation, not));
                 }
             }
-            if publish_decorations {
+            if world.config.read().unwrap().publish_decorations {
                 match handlers::publish_decorations(&world, file_id) {
                     Err(e) => {
                         if !is_canceled(&e) {
                             log::error!("failed to compute decorations: {:?}", e);
                         }
                     }
+                    // The client might have turned decorations off while we
+                    // were computing them.
+                    Ok(_) if !world.config.read().unwrap().publish_decorations => (),
                     Ok(params) => {
                         let not = RawNotification::new::<req::PublishDecorations>(&params);
-                        sender.send(Task::Notify(not))
+                        sender.send(Task::NotifyFile(file_id, generation, not))
                     }
                 }
             }
@@ -1,33 +1,113 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 use gen_lsp_server::ErrorCode;
 use languageserver_types::{
-    CodeActionResponse, Command, CompletionItem, CompletionItemKind, Diagnostic,
-    DiagnosticSeverity, DocumentSymbol, Documentation, FoldingRange, FoldingRangeKind,
-    FoldingRangeParams, InsertTextFormat, Location, MarkupContent, MarkupKind, MarkedString, Position,
-    PrepareRenameResponse, RenameParams, SymbolInformation, TextDocumentIdentifier, TextEdit,
-    WorkspaceEdit, ParameterInformation, SignatureInformation, Hover, HoverContents,
+    CodeActionResponse, Command, CompletionItem, CompletionItemKind, CreateFile, Diagnostic,
+    DiagnosticSeverity, DocumentChangeOperation, DocumentChanges, DocumentHighlight,
+    DocumentHighlightKind, DocumentSymbol, Documentation, FoldingRange, FoldingRangeKind,
+    FoldingRangeParams, InsertTextFormat, Location, MarkupContent, MarkupKind, Position,
+    PrepareRenameResponse, Range, RenameFile, RenameParams, ResourceOp, SymbolInformation,
+    TextDocumentIdentifier, TextEdit, WorkspaceEdit, ParameterInformation, SignatureInformation,
+    Hover, HoverContents,
+};
+use ra_analysis::{
+    split_range_by_lines, CompletionItemKind as CompletionKind, FileId, FileSymbol, FoldKind,
+    Query, RunnableKind, FilePosition, SsrResult, DocFormat,
+};
+use ra_syntax::{
+    algo::find_leaf_at_offset,
+    ast::{self, AstNode},
+    text_utils::intersect,
+    SourceFileNode, SyntaxKind, SyntaxNodeRef, TextRange, TextUnit,
 };
-use ra_analysis::{FileId, FoldKind, Query, RunnableKind, FilePosition};
-use ra_syntax::{TextUnit, text_utils::intersect};
 use ra_text_edit::text_utils::contains_offset_nonstrict;
 use rustc_hash::FxHashMap;
 use serde_json::to_value;
 
 use crate::{
-    conv::{to_location, Conv, ConvWith, MapConvWith, TryConvWith},
+    conv::{check_edit_conflicts, to_location, Conv, ConvWith, MapConvWith, TryConvWith},
     project_model::TargetKind,
     req::{self, Decoration},
-    server_world::ServerWorld,
+    semantic_tokens,
+    server_world::{path_to_uri, ServerWorld},
     LspError, Result,
 };
 
+pub fn handle_analyzer_status(world: ServerWorld, _: ()) -> Result<req::AnalyzerStatusResult> {
+    let stats = world.request_stats.lock().unwrap();
+    let latest = stats
+        .latest()
+        .map(|(method, duration)| req::RequestTiming {
+            method: method.clone(),
+            duration_ms: duration.as_secs() * 1000 + u64::from(duration.subsec_millis()),
+        })
+        .collect();
+    Ok(req::AnalyzerStatusResult {
+        in_flight: stats.in_flight(),
+        queued: stats.queued,
+        latest,
+    })
+}
+
+pub fn handle_request_metrics(world: ServerWorld, _: ()) -> Result<Vec<req::MethodMetrics>> {
+    let to_ms = |duration: Option<Duration>| {
+        duration.map_or(0, |it| it.as_secs() * 1000 + u64::from(it.subsec_millis()))
+    };
+    let stats = world.request_stats.lock().unwrap();
+    let mut res = stats
+        .per_method()
+        .map(|(method, it)| req::MethodMetrics {
+            method: method.clone(),
+            count: it.count,
+            errors: it.errors,
+            p50_ms: to_ms(it.percentile(50)),
+            p95_ms: to_ms(it.percentile(95)),
+        })
+        .collect::<Vec<_>>();
+    res.sort_by(|a, b| a.method.cmp(&b.method));
+    Ok(res)
+}
+
+pub fn handle_memory_usage(world: ServerWorld, _: ()) -> Result<req::MemoryUsageResult> {
+    let usage = world.analysis().memory_usage()?;
+    let overlays = world
+        .open_files
+        .iter()
+        .map(|&file_id| world.analysis().file_text(file_id).len())
+        .sum::<usize>();
+    Ok(req::MemoryUsageResult {
+        status: world.analysis().status(),
+        syntax_trees: usage.syntax_trees,
+        symbol_index: usage.symbol_index,
+        vfs: usage.file_texts.saturating_sub(overlays),
+        overlays,
+    })
+}
+
 pub fn handle_syntax_tree(world: ServerWorld, params: req::SyntaxTreeParams) -> Result<String> {
     let id = params.text_document.try_conv_with(&world)?;
     let res = world.analysis().syntax_tree(id);
     Ok(res)
 }
 
+pub fn handle_view_hir(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<String> {
+    let position = params.try_conv_with(&world)?;
+    let res = world.analysis().view_hir(position)?;
+    Ok(res)
+}
+
+pub fn handle_interpret_function(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<String> {
+    let position = params.try_conv_with(&world)?;
+    let res = world.analysis().interpret_function(position);
+    Ok(res)
+}
+
 pub fn handle_extend_selection(
     world: ServerWorld,
     params: req::ExtendSelectionParams,
@@ -42,28 +122,63 @@ pub fn handle_extend_selection(
         .map(|r| world.analysis().extend_selection(&file, r))
         .map_conv_with(&line_index)
         .collect();
+    world.check_not_stale(file_id)?;
     Ok(req::ExtendSelectionResult { selections })
 }
 
+pub fn handle_selection_range(
+    world: ServerWorld,
+    params: req::SelectionRangeParams,
+) -> Result<Vec<req::SelectionRange>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let file = world.analysis().file_syntax(file_id);
+    let line_index = world.analysis().file_line_index(file_id);
+    let res = params
+        .positions
+        .into_iter()
+        .map(|position| {
+            let offset = position.conv_with(&line_index);
+            let ranges = world.analysis().selection_ranges(&file, offset);
+            // The outermost range goes first, so that each one can become
+            // the parent of the next.
+            let chain = ranges.into_iter().rev().fold(None, |parent, range| {
+                Some(Box::new(req::SelectionRange {
+                    range: range.conv_with(&line_index),
+                    parent,
+                }))
+            });
+            match chain {
+                Some(it) => *it,
+                None => req::SelectionRange {
+                    range: Range::new(position, position),
+                    parent: None,
+                },
+            }
+        })
+        .collect();
+    world.check_not_stale(file_id)?;
+    Ok(res)
+}
+
 pub fn handle_find_matching_brace(
     world: ServerWorld,
     params: req::FindMatchingBraceParams,
 ) -> Result<Vec<Position>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let file = world.analysis().file_syntax(file_id);
     let line_index = world.analysis().file_line_index(file_id);
-    let res = params
+    let offsets: Vec<TextUnit> = params
         .offsets
         .into_iter()
         .map_conv_with(&line_index)
-        .map(|offset| {
-            world
-                .analysis()
-                .matching_brace(&file, offset)
-                .unwrap_or(offset)
-        })
+        .collect();
+    let matches = world.analysis().matching_brace(file_id, &offsets);
+    let res = offsets
+        .into_iter()
+        .zip(matches)
+        .map(|(offset, matching)| matching.unwrap_or(offset))
         .map_conv_with(&line_index)
         .collect();
+    world.check_not_stale(file_id)?;
     Ok(res)
 }
 
@@ -74,10 +189,9 @@ pub fn handle_join_lines(
     let file_id = params.text_document.try_conv_with(&world)?;
     let line_index = world.analysis().file_line_index(file_id);
     let range = params.range.conv_with(&line_index);
-    world
-        .analysis()
-        .join_lines(file_id, range)
-        .try_conv_with(&world)
+    let change = world.analysis().join_lines(file_id, range);
+    world.check_not_stale(file_id)?;
+    change.try_conv_with(&world)
 }
 
 pub fn handle_on_enter(
@@ -85,7 +199,9 @@ pub fn handle_on_enter(
     params: req::TextDocumentPositionParams,
 ) -> Result<Option<req::SourceChange>> {
     let position = params.try_conv_with(&world)?;
-    match world.analysis().on_enter(position) {
+    let edit = world.analysis().on_enter(position);
+    world.check_not_stale(position.file_id)?;
+    match edit {
         None => Ok(None),
         Some(edit) => Ok(Some(edit.try_conv_with(&world)?)),
     }
@@ -109,6 +225,7 @@ pub fn handle_on_type_formatting(
         None => return Ok(None),
         Some(mut action) => action.source_file_edits.pop().unwrap().edits,
     };
+    world.check_not_stale(file_id)?;
     let edits = edits.into_iter().map_conv_with(&line_index).collect();
     Ok(Some(edits))
 }
@@ -122,10 +239,10 @@ pub fn handle_document_symbol(
 
     let mut parents: Vec<(DocumentSymbol, Option<usize>)> = Vec::new();
 
-    for symbol in world.analysis().file_structure(file_id) {
+    for symbol in world.analysis().file_structure_iter(file_id) {
         let doc_symbol = DocumentSymbol {
             name: symbol.label,
-            detail: Some("".to_string()),
+            detail: Some(symbol.detail.unwrap_or_default()),
             kind: symbol.kind.conv(),
             deprecated: None,
             range: symbol.node_range.conv_with(&line_index),
@@ -147,6 +264,7 @@ pub fn handle_document_symbol(
             }
         }
     }
+    world.check_not_stale(file_id)?;
 
     Ok(Some(req::DocumentSymbolResponse::Nested(res)))
 }
@@ -179,18 +297,21 @@ pub fn handle_workspace_symbol(
         query.limit(128);
         res = exec_query(&world, query)?;
     }
+    world.check_none_stale()?;
 
     return Ok(Some(res));
 
     fn exec_query(world: &ServerWorld, query: Query) -> Result<Vec<SymbolInformation>> {
         let mut res = Vec::new();
         for (file_id, symbol) in world.analysis().symbol_search(query)? {
+            let file = world.analysis().file_syntax(file_id);
             let line_index = world.analysis().file_line_index(file_id);
+            let range = symbol.name_range(&file).unwrap_or(symbol.node_range);
             let info = SymbolInformation {
                 name: symbol.name.to_string(),
                 kind: symbol.kind.conv(),
-                location: to_location(file_id, symbol.node_range, world, &line_index)?,
-                container_name: None,
+                location: to_location(file_id, range, world, &line_index)?,
+                container_name: symbol.container_name(&file),
                 deprecated: None,
             };
             res.push(info);
@@ -214,9 +335,42 @@ pub fn handle_goto_definition(
         let location = to_location(file_id, symbol.node_range, &world, &line_index)?;
         res.push(location)
     }
+    world.check_none_stale()?;
     Ok(Some(req::GotoDefinitionResponse::Array(res)))
 }
 
+pub fn handle_goto_type_definition(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<req::GotoTypeDefinitionResponse>> {
+    let position = params.try_conv_with(&world)?;
+    let targets = world.analysis().goto_type_definition(position)?;
+    let res = to_locations(&world, targets)?;
+    world.check_none_stale()?;
+    Ok(Some(req::GotoTypeDefinitionResponse::Array(res)))
+}
+
+pub fn handle_goto_implementation(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<req::GotoImplementationResponse>> {
+    let position = params.try_conv_with(&world)?;
+    let targets = world.analysis().goto_implementation(position)?;
+    let res = to_locations(&world, targets)?;
+    world.check_none_stale()?;
+    Ok(Some(req::GotoImplementationResponse::Array(res)))
+}
+
+fn to_locations(world: &ServerWorld, symbols: Vec<(FileId, FileSymbol)>) -> Result<Vec<Location>> {
+    symbols
+        .into_iter()
+        .map(|(file_id, symbol)| {
+            let line_index = world.analysis().file_line_index(file_id);
+            to_location(file_id, symbol.node_range, world, &line_index)
+        })
+        .collect()
+}
+
 pub fn handle_parent_module(
     world: ServerWorld,
     params: req::TextDocumentPositionParams,
@@ -228,9 +382,169 @@ pub fn handle_parent_module(
         let location = to_location(file_id, symbol.node_range, &world, &line_index)?;
         res.push(location);
     }
+    world.check_none_stale()?;
+    Ok(res)
+}
+
+pub fn handle_open_cargo_toml(
+    world: ServerWorld,
+    params: req::OpenCargoTomlParams,
+) -> Result<Option<Location>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let &crate_id = match world.analysis().crate_for(file_id)?.first() {
+        Some(crate_id) => crate_id,
+        None => return Ok(None),
+    };
+    let root_file_id = world.analysis().crate_root(crate_id)?;
+    let root_path = world.path_map.get_path(root_file_id);
+    let manifest = world.workspaces.iter().find_map(|ws| {
+        let tgt = ws.target_by_root(root_path)?;
+        Some(tgt.package(ws).manifest(ws).to_path_buf())
+    });
+    let manifest = match manifest {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+    // Point at the `[package]` header, if the manifest has one
+    let range = std::fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|text| {
+            text.lines()
+                .position(|line| line.trim() == "[package]")
+                .map(|line| {
+                    let line = line as u64;
+                    Range::new(Position::new(line, 0), Position::new(line, 9))
+                })
+        })
+        .unwrap_or_default();
+    let uri = path_to_uri(&manifest)?;
+    Ok(Some(Location::new(uri, range)))
+}
+
+pub fn handle_trait_method_impls(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Vec<Location>> {
+    let position = params.try_conv_with(&world)?;
+    let mut res = Vec::new();
+    for (file_id, symbol) in world.analysis().trait_method_impls(position)? {
+        let line_index = world.analysis().file_line_index(file_id);
+        let location = to_location(file_id, symbol.node_range, &world, &line_index)?;
+        res.push(location);
+    }
+    world.check_none_stale()?;
     Ok(res)
 }
 
+pub fn handle_call_hierarchy_prepare(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<Vec<req::CallHierarchyItem>>> {
+    let position = params.try_conv_with(&world)?;
+    let res = match world.analysis().fn_at_position(position)? {
+        None => None,
+        Some((file_id, symbol)) => Some(vec![to_call_hierarchy_item(&world, file_id, symbol)?]),
+    };
+    world.check_none_stale()?;
+    Ok(res)
+}
+
+pub fn handle_call_hierarchy_incoming(
+    world: ServerWorld,
+    params: req::CallHierarchyCallsParams,
+) -> Result<Option<Vec<req::CallHierarchyIncomingCall>>> {
+    let position = call_hierarchy_item_position(&world, &params.item)?;
+    let mut res = Vec::new();
+    for call in world.analysis().incoming_calls(position)? {
+        let line_index = world.analysis().file_line_index(call.file_id);
+        res.push(req::CallHierarchyIncomingCall {
+            from_ranges: call
+                .call_sites
+                .into_iter()
+                .map_conv_with(&line_index)
+                .collect(),
+            from: to_call_hierarchy_item(&world, call.file_id, call.symbol)?,
+        });
+    }
+    world.check_none_stale()?;
+    Ok(Some(res))
+}
+
+pub fn handle_call_hierarchy_outgoing(
+    world: ServerWorld,
+    params: req::CallHierarchyCallsParams,
+) -> Result<Option<Vec<req::CallHierarchyOutgoingCall>>> {
+    let position = call_hierarchy_item_position(&world, &params.item)?;
+    let line_index = world.analysis().file_line_index(position.file_id);
+    let mut res = Vec::new();
+    for call in world.analysis().outgoing_calls(position)? {
+        res.push(req::CallHierarchyOutgoingCall {
+            from_ranges: call
+                .call_sites
+                .into_iter()
+                .map_conv_with(&line_index)
+                .collect(),
+            to: to_call_hierarchy_item(&world, call.file_id, call.symbol)?,
+        });
+    }
+    world.check_none_stale()?;
+    Ok(Some(res))
+}
+
+fn to_call_hierarchy_item(
+    world: &ServerWorld,
+    file_id: FileId,
+    symbol: FileSymbol,
+) -> Result<req::CallHierarchyItem> {
+    let file = world.analysis().file_syntax(file_id);
+    let line_index = world.analysis().file_line_index(file_id);
+    let selection_range = symbol.name_range(&file).unwrap_or(symbol.node_range);
+    Ok(req::CallHierarchyItem {
+        name: symbol.name.to_string(),
+        kind: symbol.kind.conv(),
+        detail: symbol.container_name(&file),
+        uri: file_id.try_conv_with(world)?,
+        range: symbol.node_range.conv_with(&line_index),
+        selection_range: selection_range.conv_with(&line_index),
+    })
+}
+
+/// The client sends back the items we gave it, their name is where the
+/// function can be found again.
+fn call_hierarchy_item_position(
+    world: &ServerWorld,
+    item: &req::CallHierarchyItem,
+) -> Result<FilePosition> {
+    let file_id = item.uri.try_conv_with(world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let offset = item.selection_range.start.conv_with(&line_index);
+    Ok(FilePosition { file_id, offset })
+}
+
+pub fn handle_related_tests(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Vec<Location>> {
+    let position = params.try_conv_with(&world)?;
+    let mut res = Vec::new();
+    for (file_id, symbol) in world.analysis().related_tests(position)? {
+        let line_index = world.analysis().file_line_index(file_id);
+        let location = to_location(file_id, symbol.node_range, &world, &line_index)?;
+        res.push(location);
+    }
+    world.check_none_stale()?;
+    Ok(res)
+}
+
+pub fn handle_external_docs(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<String>> {
+    let position = params.try_conv_with(&world)?;
+    let url = world.analysis().external_docs(position)?;
+    Ok(url)
+}
+
 pub fn handle_runnables(
     world: ServerWorld,
     params: req::RunnablesParams,
@@ -238,6 +552,10 @@ pub fn handle_runnables(
     let file_id = params.text_document.try_conv_with(&world)?;
     let line_index = world.analysis().file_line_index(file_id);
     let offset = params.position.map(|it| it.conv_with(&line_index));
+    // Runnables should be launched from the directory of the crate's manifest,
+    // so that things like `env!("CARGO_MANIFEST_DIR")` work as expected.
+    let cwd = CargoTargetSpec::for_file(&world, file_id)?
+        .map(|spec| spec.manifest_dir.to_string_lossy().to_string());
     let mut res = Vec::new();
     for runnable in world.analysis().runnables(file_id)? {
         if let Some(offset) = offset {
@@ -245,13 +563,24 @@ pub fn handle_runnables(
                 continue;
             }
         }
+        if let Some(kind) = params.kind {
+            let matches = match &runnable.kind {
+                RunnableKind::Test { .. } => kind == req::RunnableKindFilter::Test,
+                RunnableKind::Bench { .. } => kind == req::RunnableKindFilter::Bench,
+                RunnableKind::Bin => kind == req::RunnableKindFilter::Bin,
+            };
+            if !matches {
+                continue;
+            }
+        }
 
-        let args = runnable_args(&world, file_id, &runnable.kind)?;
+        let args = runnable_args(&world, file_id, &runnable.kind, &runnable.features)?;
 
         let r = req::Runnable {
             range: runnable.range.conv_with(&line_index),
             label: match &runnable.kind {
                 RunnableKind::Test { name } => format!("test {}", name),
+                RunnableKind::Bench { name } => format!("bench {}", name),
                 RunnableKind::Bin => "run binary".to_string(),
             },
             bin: "cargo".to_string(),
@@ -261,9 +590,14 @@ pub fn handle_runnables(
                 m.insert("RUST_BACKTRACE".to_string(), "short".to_string());
                 m
             },
+            cwd: cwd.clone(),
         };
         res.push(r);
     }
+    world.check_not_stale(file_id)?;
+    if params.kind.is_some() {
+        return Ok(res);
+    }
     let mut check_args = vec!["check".to_string()];
     let label;
     match CargoTargetSpec::for_file(&world, file_id)? {
@@ -283,6 +617,7 @@ pub fn handle_runnables(
         bin: "cargo".to_string(),
         args: check_args,
         env: FxHashMap::default(),
+        cwd,
     });
     return Ok(res);
 
@@ -290,25 +625,33 @@ pub fn handle_runnables(
         world: &ServerWorld,
         file_id: FileId,
         kind: &RunnableKind,
+        features: &[String],
     ) -> Result<Vec<String>> {
         let spec = CargoTargetSpec::for_file(world, file_id)?;
-        let mut res = Vec::new();
+        let command = match kind {
+            RunnableKind::Test { .. } => "test",
+            RunnableKind::Bench { .. } => "bench",
+            RunnableKind::Bin => "run",
+        };
+        let mut res = vec![command.to_string()];
+        if let Some(spec) = spec {
+            spec.push_to(&mut res);
+        }
+        if !features.is_empty() {
+            res.push("--features".to_string());
+            res.push(features.join(" "));
+        }
         match kind {
             RunnableKind::Test { name } => {
-                res.push("test".to_string());
-                if let Some(spec) = spec {
-                    spec.push_to(&mut res);
-                }
                 res.push("--".to_string());
                 res.push(name.to_string());
                 res.push("--nocapture".to_string());
             }
-            RunnableKind::Bin => {
-                res.push("run".to_string());
-                if let Some(spec) = spec {
-                    spec.push_to(&mut res);
-                }
+            RunnableKind::Bench { name } => {
+                res.push("--".to_string());
+                res.push(name.to_string());
             }
+            RunnableKind::Bin => (),
         }
         Ok(res)
     }
@@ -317,6 +660,7 @@ pub fn handle_runnables(
         package: String,
         target: String,
         target_kind: TargetKind,
+        manifest_dir: PathBuf,
     }
 
     impl CargoTargetSpec {
@@ -333,6 +677,7 @@ pub fn handle_runnables(
                     package: tgt.package(ws).name(ws).to_string(),
                     target: tgt.name(ws).to_string(),
                     target_kind: tgt.kind(ws),
+                    manifest_dir: tgt.package(ws).root(ws).to_path_buf(),
                 };
                 Some(res)
             });
@@ -373,7 +718,45 @@ pub fn handle_decorations(
     params: TextDocumentIdentifier,
 ) -> Result<Vec<Decoration>> {
     let file_id = params.try_conv_with(&world)?;
-    highlight(&world, file_id)
+    let res = highlight(&world, file_id)?;
+    world.check_not_stale(file_id)?;
+    Ok(res)
+}
+
+pub fn handle_semantic_tokens(
+    world: ServerWorld,
+    params: req::SemanticTokensParams,
+) -> Result<req::SemanticTokens> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let data = semantic_tokens::encode(&highlight(&world, file_id)?);
+    world.check_not_stale(file_id)?;
+    let result_id = world
+        .semantic_tokens
+        .lock()
+        .unwrap()
+        .insert(file_id, data.clone());
+    Ok(req::SemanticTokens { result_id, data })
+}
+
+pub fn handle_semantic_tokens_delta(
+    world: ServerWorld,
+    params: req::SemanticTokensDeltaParams,
+) -> Result<req::SemanticTokensDeltaResult> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let data = semantic_tokens::encode(&highlight(&world, file_id)?);
+    world.check_not_stale(file_id)?;
+    let mut cache = world.semantic_tokens.lock().unwrap();
+    let edits = cache
+        .get(file_id, &params.previous_result_id)
+        .map(|previous| semantic_tokens::diff(previous, &data));
+    let result_id = cache.insert(file_id, data.clone());
+    let res = match edits {
+        Some(edits) => {
+            req::SemanticTokensDeltaResult::Delta(req::SemanticTokensDelta { result_id, edits })
+        }
+        None => req::SemanticTokensDeltaResult::Tokens(req::SemanticTokens { result_id, data }),
+    };
+    Ok(res)
 }
 
 pub fn handle_completion(
@@ -386,52 +769,171 @@ pub fn handle_completion(
         let offset = params.position.conv_with(&line_index);
         FilePosition { file_id, offset }
     };
-    let completion_triggered_after_single_colon = {
-        let mut res = false;
-        if let Some(ctx) = params.context {
-            if ctx.trigger_character.unwrap_or_default() == ":" {
-                let source_file = world.analysis().file_syntax(position.file_id);
-                let syntax = source_file.syntax();
-                let text = syntax.text();
-                if let Some(next_char) = text.char_at(position.offset) {
-                    let diff = TextUnit::of_char(next_char) + TextUnit::of_char(':');
-                    let prev_char = position.offset - diff;
-                    if text.char_at(prev_char) != Some(':') {
-                        res = true;
-                    }
-                }
-            }
-        }
-        res
+    // Completion triggered by a character only makes sense right after
+    // the corresponding token: `::` but not a single `:`, and `.` but not
+    // the dot of a float literal.
+    let trigger_token = match params.context.and_then(|it| it.trigger_character) {
+        Some(ref it) if it == ":" => Some(SyntaxKind::COLONCOLON),
+        Some(ref it) if it == "." => Some(SyntaxKind::DOT),
+        _ => None,
     };
-    if completion_triggered_after_single_colon {
-        return Ok(None);
+    if let Some(trigger_token) = trigger_token {
+        let source_file = world.analysis().file_syntax(position.file_id);
+        let prev_token = find_leaf_at_offset(source_file.syntax(), position.offset)
+            .left_biased()
+            .map(|it| it.kind());
+        if prev_token != Some(trigger_token) {
+            return Ok(None);
+        }
     }
 
     let items = match world.analysis().completions(position)? {
-        None => return Ok(None),
         Some(items) => items,
+        // After `.`, an unknown receiver type means no fields or methods.
+        None if trigger_token == Some(SyntaxKind::DOT) => Vec::new(),
+        None => return Ok(None),
     };
+    world.check_not_stale(position.file_id)?;
+    let supports_snippets = world.config.read().unwrap().supports_snippets;
+    let line_index = world.analysis().file_line_index(position.file_id);
+    let mut definition_indices = FxHashMap::default();
     let items = items
         .into_iter()
-        .map(|item| {
-            let mut res = CompletionItem {
+        .filter_map(|item| {
+            // Clients sort by `sort_text`, so more relevant items get a
+            // lexicographically smaller prefix.
+            let sort_text = format!("{:010}{}", std::u32::MAX - item.relevance, item.label);
+            let (insert_text, insert_text_format) = match (item.kind, item.snippet) {
+                (Some(CompletionKind::Function), _) if supports_snippets => (
+                    Some(format!("{}($0)", item.label)),
+                    Some(InsertTextFormat::Snippet),
+                ),
+                (Some(CompletionKind::Function), _) => (Some(format!("{}()", item.label)), None),
+                (_, Some(snip)) if supports_snippets => {
+                    (Some(snip), Some(InsertTextFormat::Snippet))
+                }
+                // Without snippets, keywords are inserted as is and templates
+                // are useless.
+                (Some(CompletionKind::Keyword), Some(_)) => (None, None),
+                (_, Some(_)) => return None,
+                (_, None) => (None, None),
+            };
+            let label = &item.label;
+            let data = item.source.and_then(|(file_id, range)| {
+                let uri = file_id.try_conv_with(&world).ok()?;
+                let index = *definition_indices
+                    .entry(file_id)
+                    .or_insert_with(|| {
+                        definition_indices_in(&world.analysis().file_syntax(file_id))
+                    })
+                    .get(&range)?;
+                let source = req::CompletionItemSource {
+                    text_document: TextDocumentIdentifier::new(uri),
+                    name: label.clone(),
+                    index,
+                };
+                to_value(source).ok()
+            });
+            let kind = item.kind.map(|kind| match kind {
+                CompletionKind::Keyword => CompletionItemKind::Keyword,
+                CompletionKind::Snippet => CompletionItemKind::Snippet,
+                CompletionKind::Function => CompletionItemKind::Function,
+            });
+            let res = CompletionItem {
                 label: item.label,
+                kind,
+                insert_text,
+                insert_text_format,
                 filter_text: item.lookup,
+                sort_text: Some(sort_text),
+                detail: item.detail,
+                additional_text_edits: item.additional_edit.map(|edit| {
+                    edit.into_atoms()
+                        .into_iter()
+                        .map_conv_with(&line_index)
+                        .collect()
+                }),
+                data,
                 ..Default::default()
             };
-            if let Some(snip) = item.snippet {
-                res.insert_text = Some(snip);
-                res.insert_text_format = Some(InsertTextFormat::Snippet);
-                res.kind = Some(CompletionItemKind::Keyword);
-            };
-            res
+            Some(res)
         })
         .collect();
 
     Ok(Some(req::CompletionResponse::Array(items)))
 }
 
+/// Fills in the declaration and the docs of a completion item, from the
+/// definition recorded by `handle_completion`. Items without a definition, or
+/// whose definition is gone, are returned as is.
+pub fn handle_resolve_completion_item(
+    world: ServerWorld,
+    mut item: CompletionItem,
+) -> Result<CompletionItem> {
+    let source = match item
+        .data
+        .clone()
+        .and_then(|it| serde_json::from_value::<req::CompletionItemSource>(it).ok())
+    {
+        Some(it) => it,
+        None => return Ok(item),
+    };
+    let file_id = source.text_document.try_conv_with(&world)?;
+    let file = world.analysis().file_syntax(file_id);
+    let node = match named_definitions(&file, &source.name).nth(source.index) {
+        Some(it) => it,
+        None => return Ok(item),
+    };
+    let symbol = FileSymbol {
+        name: source.name.as_str().into(),
+        node_range: node.range(),
+        kind: node.kind(),
+    };
+    if item.detail.is_none() {
+        item.detail = symbol.description(&file);
+    }
+    if let Some(docs) = symbol.docs(&file) {
+        let markdown = world.config.read().unwrap().supports_markdown_completion;
+        let documentation = if markdown {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: docs,
+            })
+        } else {
+            Documentation::String(docs)
+        };
+        item.documentation = Some(documentation);
+    }
+    Ok(item)
+}
+
+/// Nodes of `file` which have the name `name`, in source order.
+fn named_definitions<'a>(
+    file: &'a SourceFileNode,
+    name: &'a str,
+) -> impl Iterator<Item = SyntaxNodeRef<'a>> + 'a {
+    file.syntax().descendants().filter(move |node| {
+        node.children()
+            .filter_map(ast::Name::cast)
+            .any(|it| it.text().as_str() == name)
+    })
+}
+
+/// For each named node of `file`, its position among the nodes with the same
+/// name, as used by `named_definitions(..).nth(..)`.
+fn definition_indices_in(file: &SourceFileNode) -> FxHashMap<TextRange, usize> {
+    let mut counts = FxHashMap::default();
+    let mut res = FxHashMap::default();
+    for node in file.syntax().descendants() {
+        for name in node.children().filter_map(ast::Name::cast) {
+            let count = counts.entry(name.text()).or_insert(0);
+            res.insert(node.range(), *count);
+            *count += 1;
+        }
+    }
+    res
+}
+
 pub fn handle_folding_range(
     world: ServerWorld,
     params: FoldingRangeParams,
@@ -460,6 +962,7 @@ pub fn handle_folding_range(
             })
             .collect(),
     );
+    world.check_not_stale(file_id)?;
 
     Ok(res)
 }
@@ -516,20 +1019,34 @@ pub fn handle_hover(
         None => return Ok(None),
         Some(it) => it,
     };
+    let markdown = world.config.read().unwrap().supports_markdown_hover;
+    let doc_format = if markdown {
+        DocFormat::Markdown
+    } else {
+        DocFormat::PlainText
+    };
     let mut result = Vec::new();
     for (file_id, symbol) in rr.resolves_to {
-        if let Some(docs) = world.analysis().doc_text_for(file_id, symbol)? {
-            result.push(docs);
+        if let Some(text) = world.analysis().doc_text_for(file_id, symbol, doc_format)? {
+            result.push(text);
         }
     }
-    let range = rr.reference_range.conv_with(&line_index);
-    if result.len() > 0 {
-        return Ok(Some(Hover {
-            contents: HoverContents::Scalar(MarkedString::String(result.join("\n\n---\n"))),
-            range: Some(range),
-        }));
+    if result.is_empty() {
+        return Ok(None);
     }
-    Ok(None)
+    world.check_not_stale(position.file_id)?;
+    let (kind, separator) = if markdown {
+        (MarkupKind::Markdown, "\n\n---\n")
+    } else {
+        (MarkupKind::PlainText, "\n\n")
+    };
+    Ok(Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind,
+            value: result.join(separator),
+        }),
+        range: Some(rr.reference_range.conv_with(&line_index)),
+    }))
 }
 
 /// Test doc comment
@@ -538,19 +1055,15 @@ pub fn handle_prepare_rename(
     params: req::TextDocumentPositionParams,
 ) -> Result<Option<PrepareRenameResponse>> {
     let position = params.try_conv_with(&world)?;
-
-    // We support renaming references like handle_rename does.
-    // In the future we may want to reject the renaming of things like keywords here too.
-    let refs = world.analysis().find_all_refs(position)?;
-    let r = match refs.first() {
-        Some(r) => r,
+    let range = match world.analysis().prepare_rename(position)? {
         None => return Ok(None),
+        Some(it) => it,
     };
-    let file_id = params.text_document.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(file_id);
-    let loc = to_location(r.0, r.1, &world, &line_index)?;
-
-    Ok(Some(PrepareRenameResponse::Range(loc.range)))
+    world.check_not_stale(position.file_id)?;
+    let line_index = world.analysis().file_line_index(position.file_id);
+    Ok(Some(PrepareRenameResponse::Range(
+        range.conv_with(&line_index),
+    )))
 }
 
 pub fn handle_rename(world: ServerWorld, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
@@ -558,32 +1071,24 @@ pub fn handle_rename(world: ServerWorld, params: RenameParams) -> Result<Option<
     let line_index = world.analysis().file_line_index(file_id);
     let offset = params.position.conv_with(&line_index);
 
-    if params.new_name.is_empty() {
-        return Err(LspError::new(
-            ErrorCode::InvalidParams as i32,
-            "New Name cannot be empty".into(),
-        )
-        .into());
-    }
-
-    let refs = world
+    let change = world
         .analysis()
-        .find_all_refs(FilePosition { file_id, offset })?;
-    if refs.is_empty() {
-        return Ok(None);
-    }
+        .rename(FilePosition { file_id, offset }, &params.new_name)?
+        .map_err(|e| LspError::new(ErrorCode::InvalidParams as i32, e.to_string()))?;
+    let change = match change {
+        None => return Ok(None),
+        Some(it) => world.retain_existing_files(it),
+    };
+    world.check_none_stale()?;
 
     let mut changes = HashMap::new();
-    for r in refs {
-        if let Ok(loc) = to_location(r.0, r.1, &world, &line_index) {
-            changes
-                .entry(loc.uri)
-                .or_insert_with(Vec::new)
-                .push(TextEdit {
-                    range: loc.range,
-                    new_text: params.new_name.clone(),
-                });
-        }
+    for edit in change.source_file_edits {
+        let uri = edit.file_id.try_conv_with(&world)?;
+        let mut edits = edit.edits;
+        check_edit_conflicts(edit.file_id, &mut edits)?;
+        let line_index = world.analysis().file_line_index(edit.file_id);
+        let edits: Vec<TextEdit> = edits.into_iter().map_conv_with(&line_index).collect();
+        changes.insert(uri, edits);
     }
 
     Ok(Some(WorkspaceEdit {
@@ -605,14 +1110,122 @@ pub fn handle_references(
     let refs = world
         .analysis()
         .find_all_refs(FilePosition { file_id, offset })?;
+    world.check_none_stale()?;
 
     Ok(Some(
         refs.into_iter()
-            .filter_map(|r| to_location(r.0, r.1, &world, &line_index).ok())
+            .filter_map(|r| {
+                let line_index = world.analysis().file_line_index(r.0);
+                to_location(r.0, r.1, &world, &line_index).ok()
+            })
             .collect(),
     ))
 }
 
+pub fn handle_document_highlight(
+    world: ServerWorld,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<Vec<DocumentHighlight>>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let offset = params.position.conv_with(&line_index);
+
+    let refs = world
+        .analysis()
+        .find_all_refs(FilePosition { file_id, offset })?;
+    world.check_not_stale(file_id)?;
+    let file = world.analysis().file_syntax(file_id);
+
+    Ok(Some(
+        refs.into_iter()
+            .filter(|&(ref_file_id, _)| ref_file_id == file_id)
+            .map(|(_, range)| DocumentHighlight {
+                range: range.conv_with(&line_index),
+                kind: Some(highlight_kind(file.syntax().covering_node(range))),
+            })
+            .collect(),
+    ))
+}
+
+/// Bindings and left-hand sides of assignments are writes, everything else
+/// is a read.
+fn highlight_kind(node: SyntaxNodeRef) -> DocumentHighlightKind {
+    if node.kind() == SyntaxKind::BIND_PAT {
+        return DocumentHighlightKind::Write;
+    }
+    let path_expr = node
+        .ancestors()
+        .take_while(|it| it.range() == node.range())
+        .find(|it| it.kind() == SyntaxKind::PATH_EXPR);
+    let is_assignee = path_expr
+        .and_then(|expr| {
+            let bin_expr = expr.parent()?;
+            if bin_expr.kind() != SyntaxKind::BIN_EXPR || bin_expr.first_child() != Some(expr) {
+                return None;
+            }
+            bin_expr.children().find(|it| is_assignment_op(it.kind()))
+        })
+        .is_some();
+    if is_assignee {
+        DocumentHighlightKind::Write
+    } else {
+        DocumentHighlightKind::Read
+    }
+}
+
+fn is_assignment_op(kind: SyntaxKind) -> bool {
+    use ra_syntax::SyntaxKind::*;
+    match kind {
+        EQ | PLUSEQ | MINUSEQ | STAREQ | SLASHEQ | PIPEEQ | AMPEQ | CARETEQ | SHLEQ | SHREQ => true,
+        _ => false,
+    }
+}
+
+pub fn handle_ssr(world: ServerWorld, params: req::SsrParams) -> Result<req::SsrResult> {
+    let res = world
+        .analysis()
+        .ssr(&params.pattern, &params.replacement, params.dry_run)?
+        .map_err(|e| LspError::new(ErrorCode::InvalidParams as i32, e.to_string()))?;
+    world.check_none_stale()?;
+    match res {
+        SsrResult::Matches(matches) => {
+            let matches = matches
+                .into_iter()
+                .map(|(file_id, range)| {
+                    let line_index = world.analysis().file_line_index(file_id);
+                    to_location(file_id, range, &world, &line_index)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(req::SsrResult {
+                matches: Some(matches),
+                change: None,
+            })
+        }
+        SsrResult::Change(change) => Ok(req::SsrResult {
+            matches: None,
+            change: Some(world.retain_existing_files(change).try_conv_with(&world)?),
+        }),
+    }
+}
+
+pub fn handle_ssr_at(
+    world: ServerWorld,
+    params: req::SsrAtParams,
+) -> Result<Option<req::SourceChange>> {
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let range = params.range.conv_with(&line_index);
+    let change = world
+        .analysis()
+        .ssr_at(file_id, range, &params.pattern, &params.replacement)?
+        .map_err(|e| LspError::new(ErrorCode::InvalidParams as i32, e.to_string()))?;
+    world.check_not_stale(file_id)?;
+    match change {
+        None => Ok(None),
+        Some(change) => Ok(Some(change.try_conv_with(&world)?)),
+    }
+}
+
 pub fn handle_code_action(
     world: ServerWorld,
     params: req::CodeActionParams,
@@ -628,7 +1241,9 @@ pub fn handle_code_action(
         .into_iter()
         .filter_map(|d| Some((d.range, d.fix?)))
         .filter(|(diag_range, _fix)| intersect(*diag_range, range).is_some())
-        .map(|(_range, fix)| fix);
+        .map(|(_range, fix)| fix)
+        .collect::<Vec<_>>();
+    world.check_none_stale()?;
 
     let mut res = Vec::new();
     for source_edit in assists.chain(fixes) {
@@ -645,11 +1260,99 @@ pub fn handle_code_action(
     Ok(Some(CodeActionResponse::Commands(res)))
 }
 
+pub fn handle_execute_command(
+    world: ServerWorld,
+    params: req::ExecuteCommandParams,
+) -> Result<Option<serde_json::Value>> {
+    if params.command != "apply_code_action" {
+        return Err(LspError::new(
+            ErrorCode::InvalidParams as i32,
+            format!("unknown command: {}", params.command),
+        )
+        .into());
+    }
+    let change: req::SourceChange = match params.arguments.into_iter().next() {
+        Some(it) => serde_json::from_value(it)?,
+        None => {
+            return Err(LspError::new(
+                ErrorCode::InvalidParams as i32,
+                "apply_code_action expects a source change".to_string(),
+            )
+            .into());
+        }
+    };
+    let document_changes = if change.file_system_edits.is_empty() {
+        DocumentChanges::Edits(change.source_file_edits)
+    } else {
+        // Files are created before they are edited, and moved after, as the
+        // edits refer to the files by their old path.
+        let (creates, moves): (Vec<_>, Vec<_>) = change
+            .file_system_edits
+            .into_iter()
+            .map(|edit| match edit {
+                req::FileSystemEdit::CreateFile { uri } => ResourceOp::Create(CreateFile {
+                    uri: uri.to_string(),
+                    options: None,
+                }),
+                req::FileSystemEdit::MoveFile { src, dst } => ResourceOp::Rename(RenameFile {
+                    old_uri: src.to_string(),
+                    new_uri: dst.to_string(),
+                    options: None,
+                }),
+            })
+            .partition(|op| match op {
+                ResourceOp::Create(_) => true,
+                _ => false,
+            });
+        let edits = change
+            .source_file_edits
+            .into_iter()
+            .map(DocumentChangeOperation::Edit);
+        let operations = creates
+            .into_iter()
+            .map(DocumentChangeOperation::Op)
+            .chain(edits)
+            .chain(moves.into_iter().map(DocumentChangeOperation::Op))
+            .collect();
+        DocumentChanges::Operations(operations)
+    };
+    let edit = WorkspaceEdit {
+        changes: None,
+        document_changes: Some(document_changes),
+    };
+    let client_requests = match world.client_requests.clone() {
+        Some(it) => it,
+        None => failure::bail!("can't apply {} without a main loop", change.label),
+    };
+    // The snapshot is released before waiting for the client.
+    drop(world);
+    let response =
+        client_requests.send::<req::ApplyWorkspaceEdit>(&req::ApplyWorkspaceEditParams { edit })?;
+    if !response.applied {
+        failure::bail!("client failed to apply {}", change.label);
+    }
+    // `workspace/applyEdit` can't move the cursor, so the position is left
+    // to the client.
+    Ok(change.cursor_position.map(|it| to_value(it).unwrap()))
+}
+
 pub fn publish_diagnostics(
     world: &ServerWorld,
     file_id: FileId,
 ) -> Result<req::PublishDiagnosticsParams> {
     let uri = world.file_id_to_uri(file_id)?;
+    if world.too_large_files.contains(&file_id) {
+        let diagnostic = Diagnostic {
+            range: Range::default(),
+            severity: Some(DiagnosticSeverity::Information),
+            code: None,
+            source: Some("rust-analyzer".to_string()),
+            message: "file too large, analysis disabled".to_string(),
+            related_information: None,
+        };
+        let diagnostics = vec![diagnostic];
+        return Ok(req::PublishDiagnosticsParams { uri, diagnostics });
+    }
     let line_index = world.analysis().file_line_index(file_id);
     let diagnostics = world
         .analysis()
@@ -680,14 +1383,15 @@ pub fn publish_decorations(
 
 fn highlight(world: &ServerWorld, file_id: FileId) -> Result<Vec<Decoration>> {
     let line_index = world.analysis().file_line_index(file_id);
-    let res = world
-        .analysis()
-        .highlight(file_id)?
-        .into_iter()
-        .map(|h| Decoration {
-            range: h.range.conv_with(&line_index),
-            tag: h.tag,
-        })
-        .collect();
+    let mut res = Vec::new();
+    for h in world.analysis().highlight(file_id)?.iter() {
+        for range in split_range_by_lines(h.range, &line_index) {
+            res.push(Decoration {
+                range: range.conv_with(&line_index),
+                tag: h.tag,
+                modifiers: h.modifiers.clone(),
+            });
+        }
+    }
     Ok(res)
 }
@@ -1,23 +1,38 @@
 use ra_analysis::FileId;
-use rustc_hash::FxHashSet;
+use rustc_hash::FxHashMap;
 
+/// Files the client has open, each tagged with the generation in which it
+/// was opened. Notifications computed for an older generation are stale: the
+/// file was closed (and maybe reopened) since.
 pub struct Subscriptions {
-    subs: FxHashSet<FileId>,
+    subs: FxHashMap<FileId, u64>,
+    generation: u64,
 }
 
 impl Subscriptions {
     pub fn new() -> Subscriptions {
         Subscriptions {
-            subs: FxHashSet::default(),
+            subs: FxHashMap::default(),
+            generation: 0,
         }
     }
     pub fn add_sub(&mut self, file_id: FileId) {
-        self.subs.insert(file_id);
+        self.generation += 1;
+        self.subs.insert(file_id, self.generation);
     }
     pub fn remove_sub(&mut self, file_id: FileId) {
         self.subs.remove(&file_id);
     }
     pub fn subscriptions(&self) -> Vec<FileId> {
-        self.subs.iter().cloned().collect()
+        self.subs.keys().cloned().collect()
+    }
+    pub fn generations(&self) -> Vec<(FileId, u64)> {
+        self.subs
+            .iter()
+            .map(|(&file_id, &gen)| (file_id, gen))
+            .collect()
+    }
+    pub fn is_current(&self, file_id: FileId, generation: u64) -> bool {
+        self.subs.get(&file_id) == Some(&generation)
     }
 }
@@ -19,6 +19,8 @@ pub struct PathMap {
     path2id: im::HashMap<PathBuf, FileId>,
     id2path: im::HashMap<FileId, PathBuf>,
     id2root: im::HashMap<FileId, Root>,
+    /// Lowercased paths, for `get_id_ignoring_case`.
+    lowercase2id: im::HashMap<String, FileId>,
 }
 
 impl fmt::Debug for PathMap {
@@ -45,6 +47,21 @@ impl PathMap {
     pub fn get_id(&self, path: &Path) -> Option<FileId> {
         self.path2id.get(path).cloned()
     }
+    /// Finds a known path which differs from `path` only by case.
+    pub fn get_id_ignoring_case(&self, path: &Path) -> Option<FileId> {
+        self.lowercase2id.get(&lowercase(path)).cloned()
+    }
+    /// Moves `file_id` to `new_path`, keeping its id and root.
+    pub fn rename(&mut self, file_id: FileId, new_path: PathBuf) {
+        let old_path = self.get_path(file_id).to_owned();
+        self.path2id.remove(&old_path);
+        let old_lowercase = lowercase(&old_path);
+        if self.lowercase2id.get(&old_lowercase) == Some(&file_id) {
+            self.lowercase2id.remove(&old_lowercase);
+        }
+        let root = self.get_root(file_id);
+        self.insert(new_path, file_id, root);
+    }
     pub fn get_path(&self, file_id: FileId) -> &Path {
         self.id2path.get(&file_id).unwrap().as_path()
     }
@@ -52,6 +69,7 @@ impl PathMap {
         self.id2root[&file_id]
     }
     fn insert(&mut self, path: PathBuf, file_id: FileId, root: Root) {
+        self.lowercase2id.insert(lowercase(&path), file_id);
         self.path2id.insert(path.clone(), file_id);
         self.id2path.insert(file_id, path.clone());
         self.id2root.insert(file_id, root);
@@ -85,6 +103,10 @@ impl FileResolver for PathMap {
     }
 }
 
+fn lowercase(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
 fn normalize(path: &Path) -> PathBuf {
     let mut components = path.components().peekable();
     let mut ret = if let Some(c @ Component::Prefix(..)) = components.peek().cloned() {
@@ -123,4 +145,19 @@ mod test {
         let (_, id2) = m.get_or_insert(PathBuf::from("/foo/bar.rs"), Root::Workspace);
         assert_eq!(m.resolve(id1, &RelativePath::new("bar.rs")), Some(id2),)
     }
+
+    #[test]
+    fn test_rename() {
+        let mut m = PathMap::default();
+        let (_, id) = m.get_or_insert(PathBuf::from("/foo.rs"), Root::Workspace);
+        assert_eq!(m.get_id_ignoring_case(Path::new("/Foo.rs")), Some(id));
+        m.rename(id, PathBuf::from("/Foo.rs"));
+        assert_eq!(m.get_id(Path::new("/foo.rs")), None);
+        assert_eq!(m.get_id(Path::new("/Foo.rs")), Some(id));
+        assert_eq!(m.get_path(id), Path::new("/Foo.rs"));
+        assert_eq!(m.get_root(id), Root::Workspace);
+        m.rename(id, PathBuf::from("/bar.rs"));
+        assert_eq!(m.get_id_ignoring_case(Path::new("/FOO.rs")), None);
+        assert_eq!(m.get_id_ignoring_case(Path::new("/Bar.rs")), Some(id));
+    }
 }
@@ -29,6 +29,7 @@ pub struct Target(usize);
 #[derive(Debug, Clone)]
 struct PackageData {
     name: SmolStr,
+    version: SmolStr,
     manifest: PathBuf,
     targets: Vec<Target>,
     is_member: bool,
@@ -63,9 +64,15 @@ impl Package {
     pub fn name(self, ws: &CargoWorkspace) -> &str {
         ws.pkg(self).name.as_str()
     }
+    pub fn version(self, ws: &CargoWorkspace) -> &str {
+        ws.pkg(self).version.as_str()
+    }
     pub fn root(self, ws: &CargoWorkspace) -> &Path {
         ws.pkg(self).manifest.parent().unwrap()
     }
+    pub fn manifest(self, ws: &CargoWorkspace) -> &Path {
+        ws.pkg(self).manifest.as_path()
+    }
     pub fn targets<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = Target> + 'a {
         ws.pkg(self).targets.iter().cloned()
     }
@@ -120,6 +127,7 @@ impl CargoWorkspace {
             pkg_by_id.insert(meta_pkg.id.clone(), pkg);
             let mut pkg_data = PackageData {
                 name: meta_pkg.name.into(),
+                version: meta_pkg.version.into(),
                 manifest: PathBuf::from(meta_pkg.manifest_path),
                 targets: Vec::new(),
                 is_member,
@@ -5,10 +5,11 @@ use url_serde;
 
 pub use languageserver_types::{
     notification::*, request::*, ApplyWorkspaceEditParams, CodeActionParams, CompletionParams,
-    CompletionResponse, DocumentOnTypeFormattingParams, DocumentSymbolParams,
-    DocumentSymbolResponse, ExecuteCommandParams, Hover, InitializeResult,
-    PublishDiagnosticsParams, ReferenceParams, SignatureHelp, TextDocumentEdit,
-    TextDocumentPositionParams, TextEdit, WorkspaceSymbolParams,
+    CompletionResponse, DidChangeWatchedFilesRegistrationOptions, DocumentOnTypeFormattingParams,
+    DocumentSymbolParams, DocumentSymbolResponse, ExecuteCommandParams, FileSystemWatcher, Hover,
+    InitializeResult, PublishDiagnosticsParams, ReferenceParams, Registration, RegistrationParams,
+    SignatureHelp, SymbolKind, TextDocumentEdit, TextDocumentPositionParams, TextEdit,
+    WorkspaceSymbolParams,
 };
 
 pub enum SyntaxTree {}
@@ -25,6 +26,90 @@ pub struct SyntaxTreeParams {
     pub text_document: TextDocumentIdentifier,
 }
 
+pub enum ViewHir {}
+
+impl Request for ViewHir {
+    type Params = TextDocumentPositionParams;
+    type Result = String;
+    const METHOD: &'static str = "m/viewHir";
+}
+
+pub enum InterpretFunction {}
+
+impl Request for InterpretFunction {
+    type Params = TextDocumentPositionParams;
+    type Result = String;
+    const METHOD: &'static str = "m/interpretFunction";
+}
+
+pub enum AnalyzerStatus {}
+
+impl Request for AnalyzerStatus {
+    type Params = ();
+    type Result = AnalyzerStatusResult;
+    const METHOD: &'static str = "m/analyzerStatus";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzerStatusResult {
+    /// Requests which were received but not yet responded to, including the
+    /// status request itself.
+    pub in_flight: usize,
+    /// Jobs waiting for a free thread in the pool.
+    pub queued: usize,
+    /// The most recently completed requests, latest first.
+    pub latest: Vec<RequestTiming>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestTiming {
+    pub method: String,
+    pub duration_ms: u64,
+}
+
+pub enum RequestMetrics {}
+
+impl Request for RequestMetrics {
+    type Params = ();
+    type Result = Vec<MethodMetrics>;
+    const METHOD: &'static str = "m/requestMetrics";
+}
+
+/// Totals for a single request method, over the whole session.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodMetrics {
+    pub method: String,
+    pub count: usize,
+    pub errors: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+pub enum MemoryUsage {}
+
+impl Request for MemoryUsage {
+    type Params = ();
+    type Result = MemoryUsageResult;
+    const METHOD: &'static str = "m/memoryUsage";
+}
+
+/// Approximate memory usage in bytes, by category, for charting, together
+/// with the human-readable analysis status.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsageResult {
+    pub status: String,
+    pub syntax_trees: usize,
+    pub symbol_index: usize,
+    /// Texts of the files read from disk.
+    pub vfs: usize,
+    /// Texts of the files opened in the editor.
+    pub overlays: usize,
+}
+
 pub enum ExtendSelection {}
 
 impl Request for ExtendSelection {
@@ -46,6 +131,29 @@ pub struct ExtendSelectionResult {
     pub selections: Vec<Range>,
 }
 
+pub enum SelectionRangeRequest {}
+
+impl Request for SelectionRangeRequest {
+    type Params = SelectionRangeParams;
+    type Result = Vec<SelectionRange>;
+    const METHOD: &'static str = "textDocument/selectionRange";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionRangeParams {
+    pub text_document: TextDocumentIdentifier,
+    pub positions: Vec<Position>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionRange {
+    pub range: Range,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Box<SelectionRange>>,
+}
+
 pub enum FindMatchingBrace {}
 
 impl Request for FindMatchingBrace {
@@ -89,6 +197,74 @@ pub struct PublishDecorationsParams {
 pub struct Decoration {
     pub range: Range,
     pub tag: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<&'static str>,
+}
+
+pub enum SemanticTokensRequest {}
+
+impl Request for SemanticTokensRequest {
+    type Params = SemanticTokensParams;
+    type Result = SemanticTokens;
+    const METHOD: &'static str = "textDocument/semanticTokens/full";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    pub result_id: String,
+    pub data: Vec<u32>,
+}
+
+pub enum SemanticTokensDeltaRequest {}
+
+impl Request for SemanticTokensDeltaRequest {
+    type Params = SemanticTokensDeltaParams;
+    type Result = SemanticTokensDeltaResult;
+    const METHOD: &'static str = "textDocument/semanticTokens/full/delta";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDeltaParams {
+    pub text_document: TextDocumentIdentifier,
+    pub previous_result_id: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum SemanticTokensDeltaResult {
+    Tokens(SemanticTokens),
+    Delta(SemanticTokensDelta),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDelta {
+    pub result_id: String,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    pub data: Vec<u32>,
+}
+
+pub enum ExternalDocs {}
+
+impl Request for ExternalDocs {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<String>;
+    const METHOD: &'static str = "m/externalDocs";
 }
 
 pub enum ParentModule {}
@@ -99,6 +275,151 @@ impl Request for ParentModule {
     const METHOD: &'static str = "m/parentModule";
 }
 
+pub enum OpenCargoToml {}
+
+impl Request for OpenCargoToml {
+    type Params = OpenCargoTomlParams;
+    type Result = Option<Location>;
+    const METHOD: &'static str = "m/openCargoToml";
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenCargoTomlParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// Stored in the `data` of a completion item by `textDocument/completion`,
+/// so that `completionItem/resolve` can find the item's definition and docs.
+/// The definition is the `index`-th node named `name` in the document, which
+/// stays valid when the text around it is edited, unlike offsets.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionItemSource {
+    pub text_document: TextDocumentIdentifier,
+    pub name: String,
+    pub index: usize,
+}
+
+pub enum TraitMethodImpls {}
+
+impl Request for TraitMethodImpls {
+    type Params = TextDocumentPositionParams;
+    type Result = Vec<Location>;
+    const METHOD: &'static str = "m/traitMethodImpls";
+}
+
+pub enum RelatedTests {}
+
+impl Request for RelatedTests {
+    type Params = TextDocumentPositionParams;
+    type Result = Vec<Location>;
+    const METHOD: &'static str = "m/relatedTests";
+}
+
+pub enum CallHierarchyPrepare {}
+
+impl Request for CallHierarchyPrepare {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<Vec<CallHierarchyItem>>;
+    const METHOD: &'static str = "textDocument/prepareCallHierarchy";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(with = "url_serde")]
+    pub uri: Url,
+    pub range: Range,
+    pub selection_range: Range,
+}
+
+pub enum CallHierarchyIncomingCalls {}
+
+impl Request for CallHierarchyIncomingCalls {
+    type Params = CallHierarchyCallsParams;
+    type Result = Option<Vec<CallHierarchyIncomingCall>>;
+    const METHOD: &'static str = "callHierarchy/incomingCalls";
+}
+
+pub enum CallHierarchyOutgoingCalls {}
+
+impl Request for CallHierarchyOutgoingCalls {
+    type Params = CallHierarchyCallsParams;
+    type Result = Option<Vec<CallHierarchyOutgoingCall>>;
+    const METHOD: &'static str = "callHierarchy/outgoingCalls";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyCallsParams {
+    pub item: CallHierarchyItem,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyIncomingCall {
+    pub from: CallHierarchyItem,
+    /// Ranges of the calls, inside `from`.
+    pub from_ranges: Vec<Range>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyOutgoingCall {
+    pub to: CallHierarchyItem,
+    /// Ranges of the calls, inside the item the request was made for.
+    pub from_ranges: Vec<Range>,
+}
+
+pub enum Ssr {}
+
+impl Request for Ssr {
+    type Params = SsrParams;
+    type Result = SsrResult;
+    const METHOD: &'static str = "m/ssr";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SsrParams {
+    pub pattern: String,
+    pub replacement: String,
+    /// Only report the matches, without computing the change.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SsrResult {
+    /// Set for a dry run.
+    pub matches: Option<Vec<Location>>,
+    pub change: Option<SourceChange>,
+}
+
+pub enum SsrAt {}
+
+impl Request for SsrAt {
+    type Params = SsrAtParams;
+    type Result = Option<SourceChange>;
+    const METHOD: &'static str = "m/ssrAt";
+}
+
+/// Applies the rule only to the match which contains `range`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SsrAtParams {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+    pub pattern: String,
+    pub replacement: String,
+}
+
 pub enum JoinLines {}
 
 impl Request for JoinLines {
@@ -107,7 +428,7 @@ impl Request for JoinLines {
     const METHOD: &'static str = "m/joinLines";
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct JoinLinesParams {
     pub text_document: TextDocumentIdentifier,
@@ -135,6 +456,18 @@ impl Request for Runnables {
 pub struct RunnablesParams {
     pub text_document: TextDocumentIdentifier,
     pub position: Option<Position>,
+    /// When set, only runnables of this kind are returned, and the
+    /// catch-all `cargo check` runnable is omitted.
+    #[serde(default)]
+    pub kind: Option<RunnableKindFilter>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RunnableKindFilter {
+    Test,
+    Bin,
+    Bench,
 }
 
 #[derive(Serialize, Debug)]
@@ -145,9 +478,10 @@ pub struct Runnable {
     pub bin: String,
     pub args: Vec<String>,
     pub env: FxHashMap<String, String>,
+    pub cwd: Option<String>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SourceChange {
     pub label: String,
@@ -156,7 +490,7 @@ pub struct SourceChange {
     pub cursor_position: Option<TextDocumentPositionParams>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum FileSystemEdit {
     CreateFile {
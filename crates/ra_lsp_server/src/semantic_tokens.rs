@@ -0,0 +1,221 @@
+use languageserver_types::Range;
+use ra_analysis::FileId;
+use rustc_hash::FxHashMap;
+
+use crate::req::{Decoration, SemanticTokensEdit};
+
+/// Token types, in the order of their indices in the encoded token stream.
+/// These are the standard LSP names, see `token_type` for how highlighting
+/// tags map onto them.
+pub const TOKEN_TYPES: &[&str] = &[
+    "comment",
+    "string",
+    "decorator",
+    "variable",
+    "function",
+    "number",
+    "typeParameter",
+    "keyword",
+];
+
+/// Token modifiers; bit `i` of the encoded modifier set stands for the `i`th
+/// one.
+pub const TOKEN_MODIFIERS: &[&str] = &["mutable", "unsafe", "static"];
+
+fn token_type(tag: &str) -> Option<u32> {
+    let name = match tag {
+        "attribute" => "decorator",
+        "text" => "variable",
+        "literal" => "number",
+        "parameter" => "typeParameter",
+        _ => tag,
+    };
+    TOKEN_TYPES
+        .iter()
+        .position(|&it| it == name)
+        .map(|idx| idx as u32)
+}
+
+/// Encodes decorations as a stream of `(delta_line, delta_start, length,
+/// token_type, token_modifiers)` integer quintuples, as per the
+/// `textDocument/semanticTokens` protocol. Multiline decorations can't be
+/// represented in this format and are skipped, split them with
+/// `split_range_by_lines` beforehand.
+pub fn encode(decorations: &[Decoration]) -> Vec<u32> {
+    let mut ranges: Vec<(Range, u32, u32)> = decorations
+        .iter()
+        .filter(|d| d.range.start.line == d.range.end.line)
+        .filter_map(|d| {
+            let token_type = token_type(d.tag)?;
+            let token_modifiers = d
+                .modifiers
+                .iter()
+                .filter_map(|m| TOKEN_MODIFIERS.iter().position(|it| it == m))
+                .fold(0, |acc, idx| acc | 1 << idx);
+            Some((d.range, token_type, token_modifiers))
+        })
+        .collect();
+    ranges.sort_by_key(|(range, _, _)| (range.start.line, range.start.character));
+
+    let mut res = Vec::with_capacity(ranges.len() * 5);
+    let (mut prev_line, mut prev_start) = (0, 0);
+    for (range, token_type, token_modifiers) in ranges {
+        let line = range.start.line as u32;
+        let start = range.start.character as u32;
+        let delta_start = if line == prev_line {
+            start - prev_start
+        } else {
+            start
+        };
+        res.push(line - prev_line);
+        res.push(delta_start);
+        res.push((range.end.character - range.start.character) as u32);
+        res.push(token_type);
+        res.push(token_modifiers);
+        prev_line = line;
+        prev_start = start;
+    }
+    res
+}
+
+/// Computes the edits which turn `old` into `new`. The common prefix and
+/// suffix are left intact, so a change to a single token yields a single
+/// small edit.
+pub fn diff(old: &[u32], new: &[u32]) -> Vec<SemanticTokensEdit> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    if old.is_empty() && new.is_empty() {
+        return Vec::new();
+    }
+    vec![SemanticTokensEdit {
+        start: prefix as u32,
+        delete_count: old.len() as u32,
+        data: new.to_vec(),
+    }]
+}
+
+/// Remembers the last token stream sent for each file, so that the next
+/// request can be answered with a delta.
+#[derive(Debug, Default)]
+pub struct SemanticTokensCache {
+    next_result_id: u64,
+    results: FxHashMap<FileId, (String, Vec<u32>)>,
+}
+
+impl SemanticTokensCache {
+    pub fn insert(&mut self, file_id: FileId, data: Vec<u32>) -> String {
+        self.next_result_id += 1;
+        let result_id = self.next_result_id.to_string();
+        self.results.insert(file_id, (result_id.clone(), data));
+        result_id
+    }
+
+    pub fn get(&self, file_id: FileId, result_id: &str) -> Option<&[u32]> {
+        match self.results.get(&file_id) {
+            Some((id, data)) if id == result_id => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn remove(&mut self, file_id: FileId) {
+        self.results.remove(&file_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use languageserver_types::Position;
+
+    use super::*;
+
+    fn decoration(line: u64, start: u64, end: u64, tag: &'static str) -> Decoration {
+        Decoration {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            tag,
+            modifiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let data = encode(&[
+            decoration(1, 4, 7, "function"),
+            decoration(0, 0, 2, "keyword"),
+            decoration(1, 0, 2, "keyword"),
+        ]);
+        assert_eq!(data, vec![0, 0, 2, 7, 0, 1, 0, 2, 7, 0, 0, 4, 3, 4, 0]);
+    }
+
+    #[test]
+    fn test_encode_modifiers() {
+        let mut mutable_static = decoration(0, 11, 18, "function");
+        mutable_static.modifiers = vec!["static", "mutable"];
+        let data = encode(&[mutable_static]);
+        assert_eq!(data, vec![0, 11, 7, 4, 0b101]);
+    }
+
+    #[test]
+    fn test_single_token_change_produces_minimal_delta() {
+        let old = encode(&[
+            decoration(0, 0, 2, "keyword"),
+            decoration(0, 3, 6, "function"),
+            decoration(1, 4, 7, "text"),
+        ]);
+        let new = encode(&[
+            decoration(0, 0, 2, "keyword"),
+            decoration(0, 3, 6, "text"),
+            decoration(1, 4, 7, "text"),
+        ]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![SemanticTokensEdit {
+                start: 8,
+                delete_count: 1,
+                data: vec![3],
+            }]
+        );
+        assert_eq!(diff(&new, &new), vec![]);
+    }
+
+    #[test]
+    fn test_cache_checks_result_id() {
+        let mut cache = SemanticTokensCache::default();
+        let file_id = FileId(1);
+        let first = cache.insert(file_id, vec![1, 2, 3, 4, 5]);
+        assert_eq!(cache.get(file_id, &first), Some(&[1, 2, 3, 4, 5][..]));
+        let second = cache.insert(file_id, vec![]);
+        assert_eq!(cache.get(file_id, &first), None);
+        assert_eq!(cache.get(file_id, &second), Some(&[][..]));
+        cache.remove(file_id);
+        assert_eq!(cache.get(file_id, &second), None);
+    }
+
+    #[test]
+    fn test_tags_map_to_standard_token_types() {
+        let data = encode(&[
+            decoration(0, 0, 1, "text"),
+            decoration(0, 2, 3, "literal"),
+            decoration(0, 4, 5, "attribute"),
+            decoration(0, 6, 7, "parameter"),
+        ]);
+        let types: Vec<&str> = data
+            .chunks(5)
+            .map(|it| TOKEN_TYPES[it[3] as usize])
+            .collect();
+        assert_eq!(
+            types,
+            vec!["variable", "number", "decorator", "typeParameter"]
+        );
+    }
+}
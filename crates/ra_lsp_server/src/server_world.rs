@@ -1,35 +1,213 @@
 use std::{
+    collections::VecDeque,
     fs,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
-use languageserver_types::Url;
+use crossbeam_channel::{after, select, unbounded, Sender};
+use gen_lsp_server::{ErrorCode, RawResponse};
+use languageserver_types::{request::Request, Url};
 use ra_analysis::{
     Analysis, AnalysisChange, AnalysisHost, CrateGraph, FileId, FileResolver, LibraryData,
+    SourceChange,
 };
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use failure::{bail, format_err};
 
 use crate::{
     path_map::{PathMap, Root},
     project_model::{CargoWorkspace, TargetKind},
+    semantic_tokens::SemanticTokensCache,
     vfs::{FileEvent, FileEventKind},
-    Result,
+    LspError, Result,
 };
 
+/// Settings which the client may change while the server is running, via
+/// `workspace/didChangeConfiguration`.
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    pub publish_decorations: bool,
+    /// Files larger than this many bytes are not analyzed at all.
+    pub max_file_size: Option<usize>,
+    /// Whether the client advertised snippet support for completions. This
+    /// is a client capability, so it is fixed at initialization.
+    pub supports_snippets: bool,
+    /// Whether the client can render Markdown in hovers, fixed at
+    /// initialization as well.
+    pub supports_markdown_hover: bool,
+    /// Whether the client can render Markdown in the documentation of
+    /// completion items, fixed at initialization as well.
+    pub supports_markdown_completion: bool,
+    /// Whether the client lets us register `workspace/didChangeWatchedFiles`
+    /// dynamically.
+    pub supports_dynamic_watched_files: bool,
+    /// How long handlers wait for the client to answer requests such as
+    /// `workspace/applyEdit`, `CLIENT_REQUEST_TIMEOUT_SECS` if not set.
+    pub client_request_timeout_secs: Option<u64>,
+}
+
+impl ServerConfig {
+    /// Applies the settings present in `settings`, leaving the rest as is.
+    pub fn update(&mut self, settings: &serde_json::Value) {
+        if let Some(it) = settings
+            .get("publishDecorations")
+            .and_then(|it| it.as_bool())
+        {
+            self.publish_decorations = it;
+        }
+        if let Some(it) = settings.get("maxFileSize") {
+            self.max_file_size = it.as_u64().map(|it| it as usize);
+        }
+    }
+}
+
+/// Bookkeeping about the requests handled by the main loop, shared with
+/// snapshots for `m/analyzerStatus`.
+#[derive(Debug, Default)]
+pub struct RequestStats {
+    in_flight: FxHashMap<u64, (String, Instant)>,
+    pub queued: usize,
+    latest: VecDeque<(String, Duration)>,
+    per_method: FxHashMap<String, MethodStats>,
+}
+
+/// Totals for a single method, accumulated over the whole session.
+#[derive(Debug, Default)]
+pub struct MethodStats {
+    pub count: usize,
+    pub errors: usize,
+    /// Durations of the most recent requests, used for percentiles.
+    durations: VecDeque<Duration>,
+}
+
+impl MethodStats {
+    const DURATIONS_LEN: usize = 1024;
+
+    /// The duration below which `percent` percents of the recent requests
+    /// completed.
+    pub fn percentile(&self, percent: usize) -> Option<Duration> {
+        let mut durations = self.durations.iter().cloned().collect::<Vec<_>>();
+        durations.sort();
+        let idx = (durations.len() * percent.min(100) + 99) / 100;
+        durations.get(idx.saturating_sub(1)).cloned()
+    }
+}
+
+impl RequestStats {
+    const LATEST_LEN: usize = 10;
+
+    pub fn start(&mut self, id: u64, method: String) {
+        self.in_flight.insert(id, (method, Instant::now()));
+    }
+
+    pub fn finish(&mut self, id: u64, is_error: bool) {
+        if let Some((method, start)) = self.in_flight.remove(&id) {
+            let duration = start.elapsed();
+            let method_stats = self.per_method.entry(method.clone()).or_default();
+            method_stats.count += 1;
+            if is_error {
+                method_stats.errors += 1;
+            }
+            if method_stats.durations.len() == MethodStats::DURATIONS_LEN {
+                method_stats.durations.pop_front();
+            }
+            method_stats.durations.push_back(duration);
+
+            if self.latest.len() == Self::LATEST_LEN {
+                self.latest.pop_back();
+            }
+            self.latest.push_front((method, duration));
+        }
+    }
+
+    /// Completed requests by method.
+    pub fn per_method(&self) -> impl Iterator<Item = (&String, &MethodStats)> {
+        self.per_method.iter()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Recently completed requests, latest first.
+    pub fn latest(&self) -> impl Iterator<Item = &(String, Duration)> {
+        self.latest.iter()
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct ServerWorldState {
     pub workspaces: Arc<Vec<CargoWorkspace>>,
     pub analysis_host: AnalysisHost,
     pub path_map: PathMap,
     pub mem_map: FxHashMap<FileId, Option<String>>,
+    pub semantic_tokens: Arc<Mutex<SemanticTokensCache>>,
+    pub config: Arc<RwLock<ServerConfig>>,
+    pub request_stats: Arc<Mutex<RequestStats>>,
+    /// Files over `ServerConfig::max_file_size`, which are given to the
+    /// analysis as empty. Shared with the snapshots, and copied on write.
+    pub too_large_files: Arc<FxHashSet<FileId>>,
+    /// Directories with the sources of the libraries, which are never
+    /// changed through the workspace.
+    pub lib_roots: Vec<PathBuf>,
+    /// Latest versions of the open documents, shared with the snapshots so
+    /// that they can tell when they are outdated.
+    pub doc_versions: Arc<RwLock<FxHashMap<FileId, u64>>>,
+    /// How handlers send their requests to the client, set by the main
+    /// loop.
+    pub client_requests: Option<ClientRequests>,
+}
+
+/// How long `ClientRequests::send` waits for the client by default, in
+/// seconds.
+const CLIENT_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Sends requests to the client through the main loop.
+///
+/// This is kept apart from `ServerWorld`, as waiting for the client while
+/// holding a snapshot of the analysis would block the main loop on the next
+/// change, and with it the client's response.
+#[derive(Debug, Clone)]
+pub struct ClientRequests {
+    sender: Sender<ClientRequest>,
+    next_id: Arc<AtomicUsize>,
+    timeout_secs: u64,
+}
+
+/// A message from a handler to the main loop about a request to the client.
+#[derive(Debug)]
+pub enum ClientRequest {
+    /// Sends the request and forwards the client's response to `response`.
+    Send {
+        id: u64,
+        method: &'static str,
+        params: serde_json::Value,
+        response: Sender<RawResponse>,
+    },
+    /// Forgets request `id`, which its handler stopped waiting for, and asks
+    /// the client to cancel it.
+    Cancel(u64),
 }
 
 pub struct ServerWorld {
     pub workspaces: Arc<Vec<CargoWorkspace>>,
     pub analysis: Analysis,
     pub path_map: PathMap,
+    pub semantic_tokens: Arc<Mutex<SemanticTokensCache>>,
+    pub config: Arc<RwLock<ServerConfig>>,
+    pub request_stats: Arc<Mutex<RequestStats>>,
+    pub too_large_files: Arc<FxHashSet<FileId>>,
+    /// Files opened in the editor, whose text comes from the client.
+    pub open_files: FxHashSet<FileId>,
+    /// Versions of the open documents when the snapshot was taken.
+    pub doc_versions: FxHashMap<FileId, u64>,
+    pub latest_doc_versions: Arc<RwLock<FxHashMap<FileId, u64>>>,
+    pub client_requests: Option<ClientRequests>,
 }
 
 impl ServerWorldState {
@@ -39,6 +217,8 @@ impl ServerWorldState {
         {
             let pm = &mut self.path_map;
             let mm = &mut self.mem_map;
+            let max_file_size = self.config.read().unwrap().max_file_size;
+            let too_large_files = &mut self.too_large_files;
             events
                 .into_iter()
                 .map(|event| {
@@ -60,7 +240,10 @@ impl ServerWorldState {
                         Some((file_id, text))
                     }
                 })
-                .for_each(|(file_id, text)| change.add_file(file_id, text));
+                .for_each(|(file_id, text)| {
+                    let text = skip_if_too_large(too_large_files, max_file_size, file_id, text);
+                    change.add_file(file_id, text)
+                });
         }
         if inserted {
             change.set_file_resolver(Arc::new(self.path_map.clone()))
@@ -94,6 +277,7 @@ impl ServerWorldState {
     pub fn add_mem_file(&mut self, path: PathBuf, text: String) -> FileId {
         let (inserted, file_id) = self.path_map.get_or_insert(path, Root::Workspace);
         if self.path_map.get_root(file_id) != Root::Lib {
+            let text = self.skip_if_too_large(file_id, text);
             let mut change = AnalysisChange::new();
             if inserted {
                 change.add_file(file_id, text);
@@ -107,12 +291,27 @@ impl ServerWorldState {
         file_id
     }
 
-    pub fn change_mem_file(&mut self, path: &Path, text: String) -> Result<()> {
+    pub fn set_doc_version(&mut self, file_id: FileId, version: u64) {
+        self.doc_versions.write().unwrap().insert(file_id, version);
+    }
+
+    pub fn change_mem_file(
+        &mut self,
+        path: &Path,
+        text: String,
+        version: Option<u64>,
+    ) -> Result<()> {
         let file_id = self
             .path_map
             .get_id(path)
             .ok_or_else(|| format_err!("change to unknown file: {}", path.display()))?;
+        // Applying the change waits for the running requests to finish, so
+        // bump the version first to let them notice they are outdated.
+        if let Some(version) = version {
+            self.set_doc_version(file_id, version);
+        }
         if self.path_map.get_root(file_id) != Root::Lib {
+            let text = self.skip_if_too_large(file_id, text);
             let mut change = AnalysisChange::new();
             change.change_file(file_id, text);
             self.analysis_host.apply_change(change);
@@ -120,6 +319,67 @@ impl ServerWorldState {
         Ok(())
     }
 
+    /// Re-reads workspace files which were changed on disk, skipping files
+    /// open in the editor.
+    pub fn change_disk_files(&mut self, paths: Vec<PathBuf>) {
+        let mut change = AnalysisChange::new();
+        for path in paths {
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    if self.path_map.get_id(&path).is_none() {
+                        // A case-only rename keeps the `FileId`, so that the
+                        // file isn't dropped and re-added.
+                        if !exists_with_exact_name(&path) {
+                            // Another spelling of a known file, on a
+                            // case-insensitive file system.
+                            continue;
+                        }
+                        if let Some(file_id) = self.path_map.get_id_ignoring_case(&path) {
+                            if !exists_with_exact_name(self.path_map.get_path(file_id)) {
+                                self.path_map.rename(file_id, path.clone());
+                            }
+                        }
+                    }
+                    let root = self.root_of(&path);
+                    let (_, file_id) = self.path_map.get_or_insert(path, root);
+                    if self.path_map.get_root(file_id) != Root::Lib
+                        && !self.mem_map.contains_key(&file_id)
+                    {
+                        let text = self.skip_if_too_large(file_id, text);
+                        change.add_file(file_id, text);
+                    }
+                }
+                Err(_) => {
+                    if let Some(file_id) = self.path_map.get_id(&path) {
+                        let new_path =
+                            case_variant(&path).filter(|it| self.path_map.get_id(it).is_none());
+                        if let Some(new_path) = new_path {
+                            // The event for `new_path` re-reads the text.
+                            self.path_map.rename(file_id, new_path);
+                            continue;
+                        }
+                        if self.path_map.get_root(file_id) != Root::Lib
+                            && !self.mem_map.contains_key(&file_id)
+                        {
+                            change.remove_file(file_id);
+                        }
+                    }
+                }
+            }
+        }
+        change.set_file_resolver(Arc::new(self.path_map.clone()));
+        self.analysis_host.apply_change(change);
+    }
+
+    /// The root a file at `path` belongs to, for files not known yet.
+    fn root_of(&self, path: &Path) -> Root {
+        if self.lib_roots.iter().any(|it| path.starts_with(it)) {
+            Root::Lib
+        } else {
+            Root::Workspace
+        }
+    }
+
     pub fn remove_mem_file(&mut self, path: &Path) -> Result<FileId> {
         let file_id = self
             .path_map
@@ -129,17 +389,24 @@ impl ServerWorldState {
             Some(_) => (),
             None => bail!("unmatched close notification"),
         };
+        self.doc_versions.write().unwrap().remove(&file_id);
+        self.semantic_tokens.lock().unwrap().remove(file_id);
         // Do this via file watcher ideally.
         let text = fs::read_to_string(path).ok();
         if self.path_map.get_root(file_id) != Root::Lib {
             let mut change = AnalysisChange::new();
             if let Some(text) = text {
+                let text = self.skip_if_too_large(file_id, text);
                 change.change_file(file_id, text);
             }
             self.analysis_host.apply_change(change);
         }
         Ok(file_id)
     }
+    fn skip_if_too_large(&mut self, file_id: FileId, text: String) -> String {
+        let max_file_size = self.config.read().unwrap().max_file_size;
+        skip_if_too_large(&mut self.too_large_files, max_file_size, file_id, text)
+    }
     pub fn set_workspaces(&mut self, ws: Vec<CargoWorkspace>) {
         let mut crate_graph = CrateGraph::default();
         let mut pkg_to_lib_crate = FxHashMap::default();
@@ -150,6 +417,7 @@ impl ServerWorldState {
                     let root = tgt.root(ws);
                     if let Some(file_id) = self.path_map.get_id(root) {
                         let crate_id = crate_graph.add_crate_root(file_id);
+                        crate_graph.set_crate_version(crate_id, pkg.version(ws).into());
                         if tgt.kind(ws) == TargetKind::Lib {
                             pkg_to_lib_crate.insert(pkg, crate_id);
                         }
@@ -180,28 +448,389 @@ impl ServerWorldState {
             workspaces: Arc::clone(&self.workspaces),
             analysis: self.analysis_host.analysis(),
             path_map: self.path_map.clone(),
+            semantic_tokens: Arc::clone(&self.semantic_tokens),
+            config: Arc::clone(&self.config),
+            request_stats: Arc::clone(&self.request_stats),
+            too_large_files: Arc::clone(&self.too_large_files),
+            open_files: self.mem_map.keys().cloned().collect(),
+            doc_versions: self.doc_versions.read().unwrap().clone(),
+            latest_doc_versions: Arc::clone(&self.doc_versions),
+            client_requests: self.client_requests.clone(),
         }
     }
 }
 
+impl ClientRequests {
+    pub fn new(sender: Sender<ClientRequest>, timeout_secs: Option<u64>) -> ClientRequests {
+        ClientRequests {
+            sender,
+            next_id: Arc::new(AtomicUsize::new(0)),
+            timeout_secs: timeout_secs.unwrap_or(CLIENT_REQUEST_TIMEOUT_SECS),
+        }
+    }
+
+    /// Sends a request to the client and blocks until it responds, or until
+    /// the timeout, after which the request is canceled.
+    pub fn send<R>(&self, params: &R::Params) -> Result<R::Result>
+    where
+        R: Request,
+        R::Params: serde::Serialize,
+        R::Result: serde::de::DeserializeOwned,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) as u64;
+        let timeout = Duration::from_secs(self.timeout_secs);
+        let (response, receiver) = unbounded();
+        self.sender.send(ClientRequest::Send {
+            id,
+            method: R::METHOD,
+            params: serde_json::to_value(params)?,
+            response,
+        });
+        let response = select! {
+            recv(receiver, response) => match response {
+                Some(it) => it,
+                None => bail!("no response to {}", R::METHOD),
+            },
+            recv(after(timeout), _) => {
+                self.sender.send(ClientRequest::Cancel(id));
+                bail!("no response to {} in {:?}", R::METHOD, timeout);
+            }
+        };
+        if let Some(error) = response.error {
+            bail!("{} failed: {}", R::METHOD, error.message);
+        }
+        let result = response.result.unwrap_or(serde_json::Value::Null);
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
 impl ServerWorld {
     pub fn analysis(&self) -> &Analysis {
         &self.analysis
     }
 
     pub fn uri_to_file_id(&self, uri: &Url) -> Result<FileId> {
-        let path = uri
-            .to_file_path()
-            .map_err(|()| format_err!("invalid uri: {}", uri))?;
-        self.path_map
+        let path = uri_to_path(uri)?;
+        let file_id = self
+            .path_map
             .get_id(&path)
-            .ok_or_else(|| format_err!("unknown file: {}", path.display()))
+            .ok_or_else(|| format_err!("unknown file: {}", path.display()))?;
+        if self.too_large_files.contains(&file_id) {
+            return Err(LspError::new(
+                ErrorCode::InvalidRequest as i32,
+                format!("file too large, analysis disabled: {}", path.display()),
+            )
+            .into());
+        }
+        Ok(file_id)
     }
 
     pub fn file_id_to_uri(&self, id: FileId) -> Result<Url> {
-        let path = self.path_map.get_path(id);
-        let url = Url::from_file_path(path)
-            .map_err(|()| format_err!("can't convert path to url: {}", path.display()))?;
-        Ok(url)
+        path_to_uri(self.path_map.get_path(id))
+    }
+
+    /// Checks if `file_id` is open in the editor, or else is still on disk.
+    /// The analysis only learns about deleted files from the watcher, which
+    /// may lag behind.
+    pub fn file_exists(&self, file_id: FileId) -> bool {
+        self.open_files.contains(&file_id) || self.path_map.get_path(file_id).exists()
+    }
+
+    /// Drops the edits of `change` to files which were deleted while it was
+    /// computed, rather than asking the client to edit missing files.
+    pub fn retain_existing_files(&self, mut change: SourceChange) -> SourceChange {
+        change.source_file_edits.retain(|edit| {
+            let exists = self.file_exists(edit.file_id);
+            if !exists {
+                log::warn!(
+                    "dropping edits to deleted {}",
+                    self.path_map.get_path(edit.file_id).display()
+                );
+            }
+            exists
+        });
+        if let Some(position) = change.cursor_position {
+            if !self.file_exists(position.file_id) {
+                change.cursor_position = None;
+            }
+        }
+        change
+    }
+
+    /// Fails with `ContentModified` if the client has edited `file_id` since
+    /// the snapshot was taken: the results computed for the old text would
+    /// point to wrong places, and the client should retry instead.
+    pub fn check_not_stale(&self, file_id: FileId) -> Result<()> {
+        let latest = self
+            .latest_doc_versions
+            .read()
+            .unwrap()
+            .get(&file_id)
+            .cloned();
+        if latest != self.doc_versions.get(&file_id).cloned() {
+            return Err(content_modified());
+        }
+        Ok(())
+    }
+
+    /// Like `check_not_stale`, but for results which may point into any
+    /// file, such as references or workspace edits.
+    pub fn check_none_stale(&self) -> Result<()> {
+        if *self.latest_doc_versions.read().unwrap() != self.doc_versions {
+            return Err(content_modified());
+        }
+        Ok(())
+    }
+}
+
+fn content_modified() -> failure::Error {
+    LspError::new(
+        ErrorCode::ContentModified as i32,
+        "content modified".to_string(),
+    )
+    .into()
+}
+
+/// Unlike `Path::exists`, takes case into account even on case-insensitive
+/// file systems.
+fn exists_with_exact_name(path: &Path) -> bool {
+    let (dir, name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => (dir, name),
+        _ => return path.exists(),
+    };
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|it| it.ok())
+            .any(|it| it.file_name() == name),
+        Err(_) => false,
+    }
+}
+
+/// Finds a file next to `path` whose name differs from it only by case.
+fn case_variant(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    fs::read_dir(path.parent()?)
+        .ok()?
+        .filter_map(|it| it.ok())
+        .map(|it| it.path())
+        .find(|it| {
+            it.as_path() != path
+                && it
+                    .file_name()
+                    .map_or(false, |it| it.to_string_lossy().to_lowercase() == name)
+        })
+}
+
+/// Replaces the text of a file over `max_file_size` with an empty string, so
+/// that it is not parsed, and remembers it in `too_large_files`.
+fn skip_if_too_large(
+    too_large_files: &mut Arc<FxHashSet<FileId>>,
+    max_file_size: Option<usize>,
+    file_id: FileId,
+    text: String,
+) -> String {
+    let too_large = match max_file_size {
+        Some(max) => text.len() > max,
+        None => false,
+    };
+    // Only copy the set if it actually changes.
+    if too_large != too_large_files.contains(&file_id) {
+        let too_large_files = Arc::make_mut(too_large_files);
+        if too_large {
+            too_large_files.insert(file_id);
+        } else {
+            too_large_files.remove(&file_id);
+        }
+    }
+    if too_large {
+        String::new()
+    } else {
+        text
+    }
+}
+
+/// Documents which don't live on disk, like `untitled:` buffers, get a
+/// synthetic path inside of this (nonexistent) directory.
+const NON_FILE_URI_ROOT: &str = "/$non-file-uri$";
+
+pub fn uri_to_path(uri: &Url) -> Result<PathBuf> {
+    if uri.scheme() == "file" {
+        return uri
+            .to_file_path()
+            .map_err(|()| format_err!("invalid uri: {}", uri));
+    }
+    // Escape slashes, so that the whole uri is a single path component.
+    let name = uri.as_str().replace('%', "%25").replace('/', "%2F");
+    Ok(Path::new(NON_FILE_URI_ROOT).join(name))
+}
+
+pub fn path_to_uri(path: &Path) -> Result<Url> {
+    if let Ok(name) = path.strip_prefix(NON_FILE_URI_ROOT) {
+        let name = name
+            .to_str()
+            .ok_or_else(|| format_err!("invalid path: {}", path.display()))?;
+        let uri = Url::parse(&name.replace("%2F", "/").replace("%25", "%"))?;
+        return Ok(uri);
+    }
+    Url::from_file_path(path)
+        .map_err(|()| format_err!("can't convert path to url: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use ra_analysis::FilePosition;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_request_stats_per_method() {
+        let mut stats = RequestStats::default();
+        stats.start(1, "textDocument/hover".to_string());
+        stats.start(2, "textDocument/hover".to_string());
+        stats.start(3, "m/syntaxTree".to_string());
+        stats.finish(1, false);
+        stats.finish(2, true);
+        stats.finish(3, false);
+        // Already finished, e.g. canceled and then answered.
+        stats.finish(3, false);
+
+        let mut per_method = stats
+            .per_method()
+            .map(|(method, it)| (method.as_str(), it.count, it.errors))
+            .collect::<Vec<_>>();
+        per_method.sort();
+        assert_eq!(
+            per_method,
+            vec![("m/syntaxTree", 1, 0), ("textDocument/hover", 2, 1)]
+        );
+        assert_eq!(stats.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_method_stats_percentile() {
+        let mut method_stats = MethodStats::default();
+        assert_eq!(method_stats.percentile(50), None);
+        for ms in (1..=100).rev() {
+            method_stats.durations.push_back(Duration::from_millis(ms));
+        }
+        assert_eq!(method_stats.percentile(50), Some(Duration::from_millis(50)));
+        assert_eq!(method_stats.percentile(95), Some(Duration::from_millis(95)));
+        assert_eq!(
+            method_stats.percentile(100),
+            Some(Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_is_stale_after_edit() {
+        let mut state = ServerWorldState::default();
+        let file_id = state.add_mem_file(PathBuf::from("/foo.rs"), "fn foo() {}".to_string());
+        state.set_doc_version(file_id, 1);
+        let world = state.snapshot();
+        assert!(world.check_not_stale(file_id).is_ok());
+
+        // An edit arrives while a request with `world` is still running. The
+        // change itself blocks until `world` is dropped.
+        let latest_doc_versions = Arc::clone(&state.doc_versions);
+        let edit = thread::spawn(move || {
+            state
+                .change_mem_file(Path::new("/foo.rs"), "fn bar() {}".to_string(), Some(2))
+                .unwrap();
+            state
+        });
+        while latest_doc_versions.read().unwrap().get(&file_id) != Some(&2) {
+            thread::yield_now();
+        }
+        let err = world.check_not_stale(file_id).unwrap_err();
+        let err = err.downcast::<LspError>().unwrap();
+        assert_eq!(err.code, ErrorCode::ContentModified as i32);
+        drop(world);
+
+        let state = edit.join().unwrap();
+        assert!(state.snapshot().check_not_stale(file_id).is_ok());
+    }
+
+    #[test]
+    fn test_edits_to_deleted_files_are_dropped() {
+        let dir = TempDir::new("ra-deleted-file").unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("lib.rs"), "mod foo;\nfn main() { foo::bar(); }").unwrap();
+        fs::write(path("foo.rs"), "pub fn bar() {}").unwrap();
+        let mut state = ServerWorldState::default();
+        state.change_disk_files(vec![path("lib.rs"), path("foo.rs")]);
+        let lib_file = state.path_map.get_id(&path("lib.rs")).unwrap();
+        let foo_file = state.path_map.get_id(&path("foo.rs")).unwrap();
+
+        let world = state.snapshot();
+        let position = FilePosition {
+            file_id: lib_file,
+            offset: 26.into(),
+        };
+        let change = world
+            .analysis()
+            .rename(position, "baz")
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let edited_files = |change: &SourceChange| {
+            let mut files = change
+                .source_file_edits
+                .iter()
+                .map(|it| it.file_id)
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        let mut expected = vec![lib_file, foo_file];
+        expected.sort();
+        assert_eq!(edited_files(&change), expected);
+
+        // Deleted before the watcher tells the analysis about it.
+        fs::remove_file(path("foo.rs")).unwrap();
+        let change = world.retain_existing_files(change);
+        assert_eq!(edited_files(&change), vec![lib_file]);
+    }
+
+    #[test]
+    fn test_case_only_rename_keeps_file_id() {
+        let dir = TempDir::new("ra-case-rename").unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("foo.rs"), "fn foo() {}").unwrap();
+        let mut state = ServerWorldState::default();
+        state.change_disk_files(vec![path("foo.rs")]);
+        let file_id = state.path_map.get_id(&path("foo.rs")).unwrap();
+
+        // Depending on the order of events, the file is first noticed either
+        // under its new name, or as missing under the old one.
+        fs::rename(path("foo.rs"), path("Foo.rs")).unwrap();
+        state.change_disk_files(vec![path("Foo.rs"), path("foo.rs")]);
+        fs::rename(path("Foo.rs"), path("FOO.rs")).unwrap();
+        fs::write(path("FOO.rs"), "fn bar() {}").unwrap();
+        state.change_disk_files(vec![path("Foo.rs"), path("FOO.rs")]);
+
+        assert_eq!(state.path_map.get_id(&path("FOO.rs")), Some(file_id));
+        assert_eq!(state.path_map.get_id(&path("Foo.rs")), None);
+        assert_eq!(state.path_map.get_id(&path("foo.rs")), None);
+        assert_eq!(
+            &*state.analysis_host.analysis().file_text(file_id),
+            "fn bar() {}"
+        );
+    }
+
+    #[test]
+    fn test_changed_library_files_are_not_analyzed_as_workspace() {
+        let dir = TempDir::new("ra-lib-change").unwrap();
+        let lib_root = dir.path().join("dep");
+        fs::create_dir(&lib_root).unwrap();
+        let path = lib_root.join("lib.rs");
+        fs::write(&path, "fn foo() {}").unwrap();
+        let mut state = ServerWorldState::default();
+        state.lib_roots.push(lib_root);
+        state.change_disk_files(vec![path.clone()]);
+        let file_id = state.path_map.get_id(&path).unwrap();
+        assert_eq!(state.path_map.get_root(file_id), Root::Lib);
     }
 }
@@ -1,12 +1,39 @@
 mod support;
 
+use std::cell::{Cell, RefCell};
+
 use serde_json::json;
 
-use ra_lsp_server::req::{Runnables, RunnablesParams, CodeActionRequest, CodeActionParams};
+use ra_lsp_server::req::{
+    Runnables, RunnablesParams, RunnableKindFilter, CodeActionRequest, CodeActionParams,
+    GotoDefinition, TextDocumentPositionParams, References, ReferenceParams, DidOpenTextDocument,
+    DocumentSymbolRequest, DocumentSymbolParams, PublishDecorations, Rename,
+    RegisterCapability, DidChangeConfiguration, AnalyzerStatus, PublishDiagnostics,
+    TraitMethodImpls, DidCloseTextDocument, MemoryUsage, RelatedTests, RequestMetrics, Ssr,
+    SsrParams, SsrAt, SsrAtParams, DocumentHighlightRequest, SelectionRangeRequest,
+    SelectionRangeParams,
+    GotoImplementation, CallHierarchyPrepare, CallHierarchyIncomingCalls,
+    CallHierarchyOutgoingCalls, CallHierarchyCallsParams, CallHierarchyItem, OpenCargoToml,
+    OpenCargoTomlParams, Completion, CompletionParams, ExecuteCommand, ExecuteCommandParams,
+    ApplyWorkspaceEdit, HoverRequest, ResolveCompletionItem, JoinLines, JoinLinesParams,
+    DidChangeTextDocument,
+};
+
+use languageserver_types::{
+    notification::Notification, request::Request, Position, Range, CodeActionContext,
+    ReferenceContext, DidOpenTextDocumentParams, DidChangeConfigurationParams,
+    RenameParams, TextDocumentIdentifier, TextDocumentItem, Url, DidCloseTextDocumentParams,
+    CompletionContext, CompletionTriggerKind, ApplyWorkspaceEditResponse,
+    DidChangeTextDocumentParams, VersionedTextDocumentIdentifier, TextDocumentContentChangeEvent,
+};
 
-use languageserver_types::{Position, Range, CodeActionContext};
+use gen_lsp_server::{ErrorCode, RawMessage, RawRequest, RawResponse};
 
-use crate::support::project;
+use ra_lsp_server::ServerConfig;
+
+use crate::support::{
+    default_config, logged_errors, project, project_with_config, project_with_root, Server,
+};
 
 const LOG: &'static str = "";
 
@@ -24,11 +51,13 @@ fn foo() {
         RunnablesParams {
             text_document: server.doc_id("lib.rs"),
             position: None,
+            kind: None,
         },
         json!([
           {
             "args": [ "test", "--", "foo", "--nocapture" ],
             "bin": "cargo",
+            "cwd": null,
             "env": { "RUST_BACKTRACE": "short" },
             "label": "test foo",
             "range": {
@@ -42,6 +71,7 @@ fn foo() {
               "--all"
             ],
             "bin": "cargo",
+            "cwd": null,
             "env": {},
             "label": "cargo check --all",
             "range": {
@@ -81,11 +111,13 @@ fn test_eggs() {}
         RunnablesParams {
             text_document: server.doc_id("tests/spam.rs"),
             position: None,
+            kind: None,
         },
         json!([
           {
             "args": [ "test", "--package", "foo", "--test", "spam", "--", "test_eggs", "--nocapture" ],
             "bin": "cargo",
+            "cwd": "[..]",
             "env": { "RUST_BACKTRACE": "short" },
             "label": "test test_eggs",
             "range": {
@@ -102,6 +134,7 @@ fn test_eggs() {}
               "spam"
             ],
             "bin": "cargo",
+            "cwd": "[..]",
             "env": {},
             "label": "cargo check -p foo",
             "range": {
@@ -119,6 +152,410 @@ fn test_eggs() {}
     );
 }
 
+#[test]
+fn test_open_cargo_toml() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[workspace]
+members = ["foo"]
+
+//- foo/Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- foo/src/lib.rs
+mod bar;
+
+//- foo/src/bar.rs
+pub fn bar() {}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<OpenCargoToml>(
+        OpenCargoTomlParams {
+            text_document: server.doc_id("foo/src/bar.rs"),
+        },
+        json!({
+            "uri": "file:///[..]/foo/Cargo.toml",
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 0, "character": 9 }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_runnables_sub_crate() {
+    let server = project(
+        r#"
+//- Cargo.toml
+[workspace]
+members = [ "bar" ]
+
+//- bar/Cargo.toml
+[package]
+name = "bar"
+version = "0.0.0"
+
+//- bar/src/lib.rs
+#[test]
+fn test_bar() {}
+"#,
+    );
+    server.wait_for_feedback("workspace loaded");
+    server.request::<Runnables>(
+        RunnablesParams {
+            text_document: server.doc_id("bar/src/lib.rs"),
+            position: Some(Position::new(0, 0)),
+            kind: None,
+        },
+        json!([
+          {
+            "args": [ "test", "--package", "bar", "--lib", "--", "test_bar", "--nocapture" ],
+            "bin": "cargo",
+            "cwd": "[..]/bar",
+            "env": { "RUST_BACKTRACE": "short" },
+            "label": "test test_bar",
+            "range": {
+              "end": { "character": 16, "line": 1 },
+              "start": { "character": 0, "line": 0 }
+            }
+          },
+          {
+            "args": [ "check", "--package", "bar", "--lib" ],
+            "bin": "cargo",
+            "cwd": "[..]/bar",
+            "env": {},
+            "label": "cargo check -p bar",
+            "range": {
+              "end": { "character": 0, "line": 0 },
+              "start": { "character": 0, "line": 0 }
+            }
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_runnables_at_cursor() {
+    let server = project(
+        r"
+//- lib.rs
+#[test]
+fn foo() {
+}
+
+#[test]
+fn bar() {
+    let x = 92;
+}
+
+#[test]
+fn baz() {
+}
+",
+    );
+    server.request::<Runnables>(
+        RunnablesParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Some(Position::new(6, 4)),
+            kind: Some(RunnableKindFilter::Test),
+        },
+        json!([
+          {
+            "args": [ "test", "--", "bar", "--nocapture" ],
+            "bin": "cargo",
+            "cwd": null,
+            "env": { "RUST_BACKTRACE": "short" },
+            "label": "test bar",
+            "range": {
+              "end": { "character": 1, "line": 7 },
+              "start": { "character": 0, "line": 4 }
+            }
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_runnables_kind_filter() {
+    let server = project(
+        r"
+//- lib.rs
+fn main() {}
+
+#[test]
+fn foo() {}
+
+#[bench]
+fn bench_foo(b: &mut Bencher) {}
+",
+    );
+    server.request::<Runnables>(
+        RunnablesParams {
+            text_document: server.doc_id("lib.rs"),
+            position: None,
+            kind: Some(RunnableKindFilter::Bench),
+        },
+        json!([
+          {
+            "args": [ "bench", "--", "bench_foo" ],
+            "bin": "cargo",
+            "cwd": null,
+            "env": { "RUST_BACKTRACE": "short" },
+            "label": "bench bench_foo",
+            "range": {
+              "end": { "character": 32, "line": 6 },
+              "start": { "character": 0, "line": 5 }
+            }
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_runnables_features() {
+    let server = project(
+        r#"
+//- lib.rs
+#[test]
+#[cfg(feature = "serde")]
+fn foo() {}
+"#,
+    );
+    server.request::<Runnables>(
+        RunnablesParams {
+            text_document: server.doc_id("lib.rs"),
+            position: None,
+            kind: Some(RunnableKindFilter::Test),
+        },
+        json!([
+          {
+            "args": [ "test", "--features", "serde", "--", "foo", "--nocapture" ],
+            "bin": "cargo",
+            "cwd": null,
+            "env": { "RUST_BACKTRACE": "short" },
+            "label": "test foo",
+            "range": {
+              "end": { "character": 11, "line": 2 },
+              "start": { "character": 0, "line": 0 }
+            }
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_trait_method_impls() {
+    let server = project(
+        r"
+//- lib.rs
+mod a;
+mod b;
+pub trait Shape {
+    fn area(&self) -> f64;
+}
+//- a.rs
+use crate::Shape;
+struct Square;
+impl Shape for Square {
+    fn area(&self) -> f64 { 1.0 }
+}
+//- b.rs
+use crate::Shape;
+struct Circle;
+impl Circle {
+    fn area(&self) -> f64 { 0.0 }
+}
+impl crate::Shape for Circle {
+    fn area(&self) -> f64 { 3.14 }
+}
+",
+    );
+    server.request::<TraitMethodImpls>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(3, 8),
+        },
+        json!([
+          {
+            "range": {
+              "end": { "character": 11, "line": 3 },
+              "start": { "character": 7, "line": 3 }
+            },
+            "uri": "file:///[..]/a.rs"
+          },
+          {
+            "range": {
+              "end": { "character": 11, "line": 6 },
+              "start": { "character": 7, "line": 6 }
+            },
+            "uri": "file:///[..]/b.rs"
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_goto_implementation() {
+    let server = project(
+        r"
+//- lib.rs
+mod a;
+pub trait Shape {}
+pub struct Square;
+impl Square {}
+//- a.rs
+use crate::{Shape, Square};
+impl Shape for Square {}
+",
+    );
+    server.request::<GotoImplementation>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(1, 11),
+        },
+        json!([{
+          "range": {
+            "end": { "character": 24, "line": 1 },
+            "start": { "character": 0, "line": 1 }
+          },
+          "uri": "file:///[..]/a.rs"
+        }]),
+    );
+    server.request::<GotoImplementation>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(2, 12),
+        },
+        json!([
+          {
+            "range": {
+              "end": { "character": 24, "line": 1 },
+              "start": { "character": 0, "line": 1 }
+            },
+            "uri": "file:///[..]/a.rs"
+          },
+          {
+            "range": {
+              "end": { "character": 14, "line": 3 },
+              "start": { "character": 0, "line": 3 }
+            },
+            "uri": "file:///[..]/lib.rs"
+          }
+        ]),
+    );
+    server.request::<GotoImplementation>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(0, 0),
+        },
+        json!([]),
+    );
+}
+
+#[test]
+fn test_call_hierarchy() {
+    let server = project(
+        r"
+//- lib.rs
+fn main() {
+    foo();
+}
+fn foo() {}
+",
+    );
+    let foo = server.request_value::<CallHierarchyPrepare>(TextDocumentPositionParams {
+        text_document: server.doc_id("lib.rs"),
+        position: Position::new(1, 5),
+    });
+    assert_eq!(foo[0]["name"], "foo");
+    let foo: CallHierarchyItem = serde_json::from_value(foo[0].clone()).unwrap();
+    server.request::<CallHierarchyIncomingCalls>(
+        CallHierarchyCallsParams { item: foo },
+        json!([{
+          "from": {
+            "kind": 12,
+            "name": "main",
+            "range": {
+              "end": { "character": 1, "line": 2 },
+              "start": { "character": 0, "line": 0 }
+            },
+            "selectionRange": {
+              "end": { "character": 7, "line": 0 },
+              "start": { "character": 3, "line": 0 }
+            },
+            "uri": "file:///[..]/lib.rs"
+          },
+          "fromRanges": [{
+            "end": { "character": 7, "line": 1 },
+            "start": { "character": 4, "line": 1 }
+          }]
+        }]),
+    );
+
+    let main = server.request_value::<CallHierarchyPrepare>(TextDocumentPositionParams {
+        text_document: server.doc_id("lib.rs"),
+        position: Position::new(0, 4),
+    });
+    let main: CallHierarchyItem = serde_json::from_value(main[0].clone()).unwrap();
+    server.request::<CallHierarchyOutgoingCalls>(
+        CallHierarchyCallsParams { item: main },
+        json!([{
+          "to": {
+            "kind": 12,
+            "name": "foo",
+            "range": {
+              "end": { "character": 11, "line": 3 },
+              "start": { "character": 0, "line": 3 }
+            },
+            "selectionRange": {
+              "end": { "character": 6, "line": 3 },
+              "start": { "character": 3, "line": 3 }
+            },
+            "uri": "file:///[..]/lib.rs"
+          },
+          "fromRanges": [{
+            "end": { "character": 7, "line": 1 },
+            "start": { "character": 4, "line": 1 }
+          }]
+        }]),
+    );
+}
+
+#[test]
+fn test_related_tests() {
+    let server = project(
+        r"
+//- lib.rs
+fn helper() -> u32 { 92 }
+fn wrapper() -> u32 { helper() }
+#[test]
+fn test_helper() { let x = wrapper(); }
+#[test]
+fn test_other() {}
+",
+    );
+    server.request::<RelatedTests>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(0, 4),
+        },
+        json!([
+          {
+            "range": {
+              "end": { "character": 39, "line": 3 },
+              "start": { "character": 0, "line": 2 }
+            },
+            "uri": "file:///[..]/lib.rs"
+          }
+        ]),
+    );
+}
+
 #[test]
 fn test_missing_module_code_action() {
     let server = project(
@@ -175,3 +612,1094 @@ fn main() {}
         json!([]),
     );
 }
+
+#[test]
+fn test_execute_command_applies_edit_on_client() {
+    let server = project(
+        r"
+//- lib.rs
+fn main() {}
+",
+    );
+    let uri = server.doc_id("lib.rs").uri.to_string();
+    let change = json!({
+        "label": "rename main",
+        "sourceFileEdits": [
+            {
+                "textDocument": { "uri": uri, "version": null },
+                "edits": [
+                    {
+                        "range": {
+                            "start": { "line": 0, "character": 3 },
+                            "end": { "line": 0, "character": 7 }
+                        },
+                        "newText": "start"
+                    }
+                ]
+            }
+        ],
+        "fileSystemEdits": [],
+        "cursorPosition": null
+    });
+    let command_id = 92;
+    server.send(RawMessage::Request(RawRequest::new::<ExecuteCommand>(
+        command_id,
+        &ExecuteCommandParams {
+            command: "apply_code_action".to_string(),
+            arguments: vec![change],
+        },
+    )));
+
+    let apply_edit = match server.wait_for(|msg| match msg {
+        RawMessage::Request(req) => req.method == ApplyWorkspaceEdit::METHOD,
+        _ => false,
+    }) {
+        RawMessage::Request(req) => req,
+        _ => unreachable!(),
+    };
+    let edit = &apply_edit.params["edit"]["documentChanges"][0];
+    assert_eq!(edit["textDocument"]["uri"], json!(uri));
+    assert_eq!(edit["edits"][0]["newText"], json!("start"));
+
+    server.send(RawMessage::Response(RawResponse::ok::<ApplyWorkspaceEdit>(
+        apply_edit.id,
+        &ApplyWorkspaceEditResponse { applied: true },
+    )));
+    match server.wait_for(|msg| match msg {
+        RawMessage::Response(res) => res.id == command_id,
+        _ => false,
+    }) {
+        RawMessage::Response(res) => assert!(res.error.is_none(), "{:?}", res.error),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_execute_command_creates_files_and_returns_cursor() {
+    let server = project(
+        r"
+//- lib.rs
+mod foo;
+",
+    );
+    let lib_uri = server.doc_id("lib.rs").uri.to_string();
+    let foo_uri = lib_uri.replace("lib.rs", "foo.rs");
+    let change = json!({
+        "label": "create module",
+        "sourceFileEdits": [
+            {
+                "textDocument": { "uri": foo_uri, "version": null },
+                "edits": [
+                    {
+                        "range": {
+                            "start": { "line": 0, "character": 0 },
+                            "end": { "line": 0, "character": 0 }
+                        },
+                        "newText": "fn foo() {}"
+                    }
+                ]
+            }
+        ],
+        "fileSystemEdits": [{ "type": "createFile", "uri": foo_uri }],
+        "cursorPosition": {
+            "textDocument": { "uri": foo_uri },
+            "position": { "line": 0, "character": 3 }
+        }
+    });
+    let command_id = 92;
+    server.send(RawMessage::Request(RawRequest::new::<ExecuteCommand>(
+        command_id,
+        &ExecuteCommandParams {
+            command: "apply_code_action".to_string(),
+            arguments: vec![change.clone()],
+        },
+    )));
+
+    let apply_edit = match server.wait_for(|msg| match msg {
+        RawMessage::Request(req) => req.method == ApplyWorkspaceEdit::METHOD,
+        _ => false,
+    }) {
+        RawMessage::Request(req) => req,
+        _ => unreachable!(),
+    };
+    let changes = &apply_edit.params["edit"]["documentChanges"];
+    assert_eq!(changes[0], json!({ "kind": "create", "uri": foo_uri }));
+    assert_eq!(changes[1]["textDocument"]["uri"], json!(foo_uri));
+    assert_eq!(changes[1]["edits"][0]["newText"], json!("fn foo() {}"));
+
+    server.send(RawMessage::Response(RawResponse::ok::<ApplyWorkspaceEdit>(
+        apply_edit.id,
+        &ApplyWorkspaceEditResponse { applied: true },
+    )));
+    match server.wait_for(|msg| match msg {
+        RawMessage::Response(res) => res.id == command_id,
+        _ => false,
+    }) {
+        RawMessage::Response(res) => assert_eq!(res.result, Some(change["cursorPosition"].clone())),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_request_queued_behind_edit_is_stale() {
+    let server = project_with_config(
+        r"
+//- lib.rs
+fn foo() {
+    92
+}
+",
+        ServerConfig {
+            client_request_timeout_secs: Some(1),
+            ..default_config()
+        },
+    );
+    // Commands waiting for the client to apply their edits occupy all of
+    // the server's threads, so that the next request is queued.
+    let noop = json!({
+        "label": "noop",
+        "sourceFileEdits": [],
+        "fileSystemEdits": [],
+        "cursorPosition": null
+    });
+    for id in 1000..1008 {
+        server.send(RawMessage::Request(RawRequest::new::<ExecuteCommand>(
+            id,
+            &ExecuteCommandParams {
+                command: "apply_code_action".to_string(),
+                arguments: vec![noop.clone()],
+            },
+        )));
+    }
+    for _ in 0..8 {
+        server.wait_for(|msg| match msg {
+            RawMessage::Request(req) => req.method == ApplyWorkspaceEdit::METHOD,
+            _ => false,
+        });
+    }
+    let join_lines_id = 2000;
+    server.send(RawMessage::Request(RawRequest::new::<JoinLines>(
+        join_lines_id,
+        &JoinLinesParams {
+            text_document: server.doc_id("lib.rs"),
+            range: Range::new(Position::new(0, 10), Position::new(0, 10)),
+        },
+    )));
+    server.notification::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier {
+            uri: server.doc_id("lib.rs").uri,
+            version: Some(1),
+        },
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "fn bar() {\n    92\n}\n".to_string(),
+        }],
+    });
+    // The commands give up on the client, and the request runs against the
+    // text from before the change.
+    let join_lines_error = RefCell::new(None);
+    let responses = Cell::new(0);
+    server.wait_for(|msg| {
+        if let RawMessage::Response(res) = msg {
+            if res.id == join_lines_id {
+                *join_lines_error.borrow_mut() = Some(res.error.clone());
+            }
+            responses.set(responses.get() + 1);
+        }
+        responses.get() == 9
+    });
+    let error = join_lines_error
+        .into_inner()
+        .unwrap()
+        .expect("stale edits were returned");
+    assert_eq!(error.code, ErrorCode::ContentModified as i32);
+}
+
+#[test]
+fn test_ssr_at() {
+    let server = project(
+        r#"
+//- lib.rs
+fn main() {
+    foo(1, 2);
+}
+"#,
+    );
+    let ssr_at = |line, character, pattern: &str| {
+        server.request_value::<SsrAt>(SsrAtParams {
+            text_document: server.doc_id("lib.rs"),
+            range: Range::new(
+                Position::new(line, character),
+                Position::new(line, character),
+            ),
+            pattern: pattern.to_string(),
+            replacement: "foo($b, $a)".to_string(),
+        })
+    };
+    let res = server.request_value::<Ssr>(SsrParams {
+        pattern: "foo($a, $b)".to_string(),
+        replacement: "foo($b, $a)".to_string(),
+        dry_run: true,
+    });
+    assert_eq!(res["matches"].as_array().unwrap().len(), 1);
+    assert_eq!(res["change"], json!(null));
+    assert_eq!(ssr_at(0, 1, "foo($a, $b)"), json!(null));
+    let change = ssr_at(1, 5, "foo($a, $b)");
+    assert_eq!(change["label"], "apply SSR here");
+    assert_eq!(
+        change["sourceFileEdits"][0]["edits"],
+        json!([{
+            "newText": "foo(2, 1)",
+            "range": {
+                "start": { "line": 1, "character": 4 },
+                "end": { "line": 1, "character": 13 }
+            }
+        }])
+    );
+}
+
+#[test]
+fn test_document_highlight() {
+    let server = project(
+        r#"
+//- lib.rs
+fn main() {
+    let mut x = 1;
+    x += 2;
+    foo(x);
+}
+"#,
+    );
+    server.request::<DocumentHighlightRequest>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(3, 8),
+        },
+        json!([
+            {
+              "kind": 3,
+              "range": {
+                "end": { "character": 13, "line": 1 },
+                "start": { "character": 8, "line": 1 }
+              }
+            },
+            {
+              "kind": 3,
+              "range": {
+                "end": { "character": 5, "line": 2 },
+                "start": { "character": 4, "line": 2 }
+              }
+            },
+            {
+              "kind": 2,
+              "range": {
+                "end": { "character": 9, "line": 3 },
+                "start": { "character": 8, "line": 3 }
+              }
+            }
+        ]),
+    );
+    server.request::<DocumentHighlightRequest>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(0, 2),
+        },
+        json!([]),
+    );
+}
+
+#[test]
+fn test_selection_range() {
+    let server = project(
+        r#"
+//- lib.rs
+fn main() { foo(1 + 2); }
+"#,
+    );
+    server.request::<SelectionRangeRequest>(
+        SelectionRangeParams {
+            text_document: server.doc_id("lib.rs"),
+            positions: vec![Position::new(0, 20)],
+        },
+        json!([{
+          "range": {
+            "end": { "character": 21, "line": 0 },
+            "start": { "character": 20, "line": 0 }
+          },
+          "parent": {
+            "range": {
+              "end": { "character": 21, "line": 0 },
+              "start": { "character": 16, "line": 0 }
+            },
+            "parent": {
+              "range": {
+                "end": { "character": 22, "line": 0 },
+                "start": { "character": 15, "line": 0 }
+              },
+              "parent": {
+                "range": {
+                  "end": { "character": 22, "line": 0 },
+                  "start": { "character": 12, "line": 0 }
+                },
+                "parent": {
+                  "range": {
+                    "end": { "character": 23, "line": 0 },
+                    "start": { "character": 12, "line": 0 }
+                  },
+                  "parent": {
+                    "range": {
+                      "end": { "character": 25, "line": 0 },
+                      "start": { "character": 10, "line": 0 }
+                    },
+                    "parent": {
+                      "range": {
+                        "end": { "character": 25, "line": 0 },
+                        "start": { "character": 0, "line": 0 }
+                      },
+                      "parent": {
+                        "range": {
+                          "end": { "character": 0, "line": 1 },
+                          "start": { "character": 0, "line": 0 }
+                        }
+                      }
+                    }
+                  }
+                }
+              }
+            }
+          }
+        }]),
+    );
+}
+
+#[test]
+fn test_goto_definition_into_dependency() {
+    let server = project_with_root(
+        r#"
+//- foo/Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+[dependencies]
+dep = { path = "../dep" }
+
+//- foo/src/lib.rs
+fn main() {
+    dep::spam();
+}
+
+//- dep/Cargo.toml
+[package]
+name = "dep"
+version = "0.0.0"
+
+//- dep/src/lib.rs
+pub fn spam() {}
+"#,
+        "foo",
+    );
+    server.wait_for_feedback("library loaded");
+    server.request::<GotoDefinition>(
+        TextDocumentPositionParams {
+            text_document: server.doc_id("foo/src/lib.rs"),
+            position: Position::new(1, 10),
+        },
+        json!([
+            {
+              "range": {
+                "end": { "character": 16, "line": 0 },
+                "start": { "character": 0, "line": 0 }
+              },
+              "uri": "file:///[..]/dep/src/lib.rs"
+            }
+        ]),
+    );
+}
+
+#[test]
+fn test_find_all_refs_across_files() {
+    let server = project(
+        r"
+//- lib.rs
+mod foo;
+mod bar;
+
+pub fn spam() {}
+
+fn main() {
+    spam();
+}
+//- foo.rs
+fn foo() {
+    super::spam();
+}
+//- bar.rs
+fn bar() {
+    let spam = 92;
+    super::spam();
+    spam;
+}
+",
+    );
+    server.request::<References>(
+        ReferenceParams {
+            text_document: server.doc_id("foo.rs"),
+            position: Position::new(1, 12),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        },
+        json!([
+            {
+              "range": {
+                "end": { "character": 11, "line": 3 },
+                "start": { "character": 7, "line": 3 }
+              },
+              "uri": "file:///[..]/lib.rs"
+            },
+            {
+              "range": {
+                "end": { "character": 8, "line": 6 },
+                "start": { "character": 4, "line": 6 }
+              },
+              "uri": "file:///[..]/lib.rs"
+            },
+            {
+              "range": {
+                "end": { "character": 15, "line": 1 },
+                "start": { "character": 11, "line": 1 }
+              },
+              "uri": "file:///[..]/foo.rs"
+            },
+            {
+              "range": {
+                "end": { "character": 15, "line": 2 },
+                "start": { "character": 11, "line": 2 }
+              },
+              "uri": "file:///[..]/bar.rs"
+            }
+        ]),
+    );
+}
+
+#[test]
+fn test_implementation_dependent_notifications_are_ignored() {
+    enum Unknown {}
+    impl Notification for Unknown {
+        type Params = ();
+        const METHOD: &'static str = "$/unknown";
+    }
+
+    let server = project(
+        r"
+//- lib.rs
+#[test]
+fn foo() {
+}
+",
+    );
+    server.notification::<Unknown>(());
+    server.request::<Runnables>(
+        RunnablesParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Some(Position::new(0, 0)),
+            kind: None,
+        },
+        json!([
+          {
+            "args": [ "test", "--", "foo", "--nocapture" ],
+            "bin": "cargo",
+            "cwd": null,
+            "env": { "RUST_BACKTRACE": "short" },
+            "label": "test foo",
+            "range": {
+              "end": { "character": 1, "line": 2 },
+              "start": { "character": 0, "line": 0 }
+            }
+          },
+          {
+            "args": [ "check", "--all" ],
+            "bin": "cargo",
+            "cwd": null,
+            "env": {},
+            "label": "cargo check --all",
+            "range": {
+              "end": { "character": 0, "line": 0 },
+              "start": { "character": 0, "line": 0 }
+            }
+          }
+        ]),
+    );
+    assert!(logged_errors().iter().all(|it| !it.contains("$/unknown")));
+}
+
+#[test]
+fn test_untitled_document() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo() {}
+",
+    );
+    let uri = Url::parse("untitled:Untitled-1").unwrap();
+    server.notification::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: uri.clone(),
+            language_id: "rust".to_string(),
+            version: 0,
+            text: "fn bar() {}\n".to_string(),
+        },
+    });
+    server.request::<DocumentSymbolRequest>(
+        DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(uri),
+        },
+        json!([
+          {
+            "detail": "fn()",
+            "kind": 12,
+            "name": "bar",
+            "range": {
+              "end": { "character": 11, "line": 0 },
+              "start": { "character": 0, "line": 0 }
+            },
+            "selectionRange": {
+              "end": { "character": 6, "line": 0 },
+              "start": { "character": 3, "line": 0 }
+            }
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_too_large_file_is_not_analyzed() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo() {}
+",
+    );
+    server.notification::<DidChangeConfiguration>(DidChangeConfigurationParams {
+        settings: json!({ "maxFileSize": 100 }),
+    });
+    let uri = Url::parse("untitled:Untitled-1").unwrap();
+    server.notification::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: uri.clone(),
+            language_id: "rust".to_string(),
+            version: 0,
+            text: "fn bar() { 92 }\n".repeat(10),
+        },
+    });
+    let msg = server.wait_for(|msg| match msg {
+        RawMessage::Notification(n) => {
+            n.method == PublishDiagnostics::METHOD && n.params["uri"] == uri.as_str()
+        }
+        _ => false,
+    });
+    let params = match msg {
+        RawMessage::Notification(n) => n.params,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        params["diagnostics"],
+        json!([{
+            "message": "file too large, analysis disabled",
+            "range": {
+                "end": { "character": 0, "line": 0 },
+                "start": { "character": 0, "line": 0 }
+            },
+            "severity": 3,
+            "source": "rust-analyzer"
+        }])
+    );
+    server.request::<DocumentSymbolRequest>(
+        DocumentSymbolParams {
+            text_document: server.doc_id("lib.rs"),
+        },
+        json!([
+          {
+            "detail": "fn()",
+            "kind": 12,
+            "name": "foo",
+            "range": {
+              "end": { "character": 11, "line": 0 },
+              "start": { "character": 0, "line": 0 }
+            },
+            "selectionRange": {
+              "end": { "character": 6, "line": 0 },
+              "start": { "character": 3, "line": 0 }
+            }
+          }
+        ]),
+    );
+}
+
+#[test]
+fn test_wait_for_decorations() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo() {}
+",
+    );
+    let uri = server.doc_id("lib.rs").uri;
+    let msg = server.wait_for(|msg| match msg {
+        RawMessage::Notification(n) => {
+            n.method == PublishDecorations::METHOD && n.params["uri"] == uri.as_str()
+        }
+        _ => false,
+    });
+    let params = match msg {
+        RawMessage::Notification(n) => n.params,
+        _ => unreachable!(),
+    };
+    assert_eq!(params["decorations"][0]["tag"], "keyword");
+}
+
+#[test]
+fn test_turning_decorations_off_clears_them() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo() {}
+",
+    );
+    let uri = server.doc_id("lib.rs").uri;
+    let is_decorations = |msg: &RawMessage, empty: bool| match msg {
+        RawMessage::Notification(n) => {
+            n.method == PublishDecorations::METHOD
+                && n.params["uri"] == uri.as_str()
+                && n.params["decorations"].as_array().unwrap().is_empty() == empty
+        }
+        _ => false,
+    };
+    server.wait_for(|msg| is_decorations(msg, false));
+    server.notification::<DidChangeConfiguration>(DidChangeConfigurationParams {
+        settings: json!({ "publishDecorations": false }),
+    });
+    server.wait_for(|msg| is_decorations(msg, true));
+}
+
+#[test]
+fn test_analyzer_status_reports_in_flight_request() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo() {}
+",
+    );
+    server.request_value::<DocumentSymbolRequest>(DocumentSymbolParams {
+        text_document: server.doc_id("lib.rs"),
+    });
+    let status = server.request_value::<AnalyzerStatus>(());
+    assert_eq!(status["inFlight"], 1);
+    assert!(status["queued"].is_u64());
+    assert_eq!(status["latest"][0]["method"], "textDocument/documentSymbol");
+    assert!(status["latest"][0]["durationMs"].is_u64());
+}
+
+#[test]
+fn test_request_metrics_count_requests_by_method() {
+    let server = project(
+        r"
+//- lib.rs
+struct S;
+",
+    );
+    for _ in 0..2 {
+        server.request_value::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: server.doc_id("lib.rs"),
+        });
+    }
+    server.request_value::<AnalyzerStatus>(());
+    let metrics = server.request_value::<RequestMetrics>(());
+    let metrics = metrics.as_array().unwrap();
+    let methods = metrics
+        .iter()
+        .map(|it| {
+            (
+                it["method"].as_str().unwrap(),
+                it["count"].as_u64().unwrap(),
+                it["errors"].as_u64().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        methods,
+        vec![
+            ("m/analyzerStatus", 1, 0),
+            ("textDocument/documentSymbol", 2, 0),
+        ]
+    );
+    assert!(metrics[1]["p50Ms"].is_u64());
+    assert!(metrics[1]["p95Ms"].is_u64());
+}
+
+#[test]
+fn test_memory_usage_is_reported_by_category() {
+    let server = project(
+        r"
+//- lib.rs
+mod foo;
+struct S;
+//- foo.rs
+fn foo() {}
+",
+    );
+    server.wait_for_feedback("workspace files loaded");
+    server.notification::<DidCloseTextDocument>(DidCloseTextDocumentParams {
+        text_document: server.doc_id("foo.rs"),
+    });
+    let usage = server.request_value::<MemoryUsage>(());
+    assert!(usage["status"].is_string());
+    for category in &["syntaxTrees", "symbolIndex", "vfs", "overlays"] {
+        assert!(
+            usage[category].as_u64().unwrap() > 0,
+            "{} is zero",
+            category
+        );
+    }
+}
+
+#[test]
+fn test_rename_to_keyword_uses_raw_ident() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo() {
+    let r#type = 92;
+    r#type;
+}
+",
+    );
+    let doc_id = server.doc_id("lib.rs");
+    let edits = json!([
+        {
+          "newText": "r#match",
+          "range": {
+            "end": { "character": 14, "line": 1 },
+            "start": { "character": 8, "line": 1 }
+          }
+        },
+        {
+          "newText": "r#match",
+          "range": {
+            "end": { "character": 10, "line": 2 },
+            "start": { "character": 4, "line": 2 }
+          }
+        }
+    ]);
+    let mut changes = serde_json::Map::new();
+    changes.insert(doc_id.uri.to_string(), edits);
+    server.request::<Rename>(
+        RenameParams {
+            text_document: doc_id,
+            position: Position::new(2, 6),
+            new_name: "match".to_string(),
+        },
+        json!({ "changes": changes }),
+    );
+}
+
+#[test]
+fn test_register_capability_when_supported() {
+    let server = project_with_config(
+        r"
+//- lib.rs
+fn foo() {}
+",
+        ServerConfig {
+            supports_dynamic_watched_files: true,
+            ..default_config()
+        },
+    );
+    let msg = server.wait_for(|msg| match msg {
+        RawMessage::Request(req) => req.method == RegisterCapability::METHOD,
+        _ => false,
+    });
+    let req = match msg {
+        RawMessage::Request(req) => req,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        req.params["registrations"][0]["method"],
+        "workspace/didChangeWatchedFiles"
+    );
+    server.send(RawMessage::Response(RawResponse::ok::<RegisterCapability>(
+        req.id,
+        &(),
+    )));
+    // Messages are handled in order, so the response is handled by now.
+    server.request_value::<DocumentSymbolRequest>(DocumentSymbolParams {
+        text_document: server.doc_id("lib.rs"),
+    });
+    assert!(logged_errors()
+        .iter()
+        .all(|it| !it.contains("unexpected response") && !it.contains("register")));
+}
+
+#[test]
+fn test_close_clears_pending_diagnostics() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo() {}
+",
+    );
+    let lib_uri = server.doc_id("lib.rs").uri;
+    let uri = Url::parse("untitled:Untitled-1").unwrap();
+    server.notification::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: uri.clone(),
+            language_id: "rust".to_string(),
+            version: 0,
+            text: "fn bar( {}\n".to_string(),
+        },
+    });
+    server.notification::<DidCloseTextDocument>(DidCloseTextDocumentParams {
+        text_document: TextDocumentIdentifier::new(uri.clone()),
+    });
+    // Both the open and the close schedule a pass over the open files, wait
+    // for them to publish.
+    for _ in 0..2 {
+        server.wait_for(|msg| match msg {
+            RawMessage::Notification(n) => {
+                n.method == PublishDecorations::METHOD && n.params["uri"] == lib_uri.as_str()
+            }
+            _ => false,
+        });
+    }
+    server.request_value::<DocumentSymbolRequest>(DocumentSymbolParams {
+        text_document: server.doc_id("lib.rs"),
+    });
+    let last = server
+        .seen_notifications(PublishDiagnostics::METHOD)
+        .into_iter()
+        .rfind(|params| params["uri"] == uri.as_str())
+        .map(|params| params["diagnostics"].clone());
+    assert_eq!(last, Some(json!([])));
+}
+
+#[test]
+fn test_shutdown_clears_diagnostics() {
+    let server = project(
+        r"
+//- lib.rs
+fn foo( {}
+",
+    );
+    let lib_uri = server.doc_id("lib.rs").uri;
+    server.wait_for(|msg| match msg {
+        RawMessage::Notification(n) => {
+            n.method == PublishDiagnostics::METHOD && n.params["uri"] == lib_uri.as_str()
+        }
+        _ => false,
+    });
+    let last_diagnostics = |server: &Server| {
+        server
+            .seen_notifications(PublishDiagnostics::METHOD)
+            .into_iter()
+            .rfind(|params| params["uri"] == lib_uri.as_str())
+            .map(|params| params["diagnostics"].clone())
+            .unwrap()
+    };
+    assert_ne!(last_diagnostics(&server), json!([]));
+    server.shutdown();
+    assert_eq!(last_diagnostics(&server), json!([]));
+}
+
+fn completion_item(completions: &serde_json::Value, label: &str) -> serde_json::Value {
+    completions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|it| it["label"] == label)
+        .unwrap_or_else(|| panic!("no completion {:?} in {}", label, completions))
+        .clone()
+}
+
+#[test]
+fn test_completion_snippets() {
+    let code = r#"
+//- lib.rs
+fn foo() {}
+fn main() {
+    f
+}
+"#;
+    let params = |server: &Server| CompletionParams {
+        text_document: server.doc_id("lib.rs"),
+        position: Position::new(2, 5),
+        context: None,
+    };
+
+    let server = project_with_config(
+        code,
+        ServerConfig {
+            supports_snippets: true,
+            ..default_config()
+        },
+    );
+    let completions = server.request_value::<Completion>(params(&server));
+    let foo = completion_item(&completions, "foo");
+    assert_eq!(foo["insertText"], "foo($0)");
+    assert_eq!(foo["insertTextFormat"], 2);
+    let if_ = completion_item(&completions, "if");
+    assert_eq!(if_["insertText"], "if $1 { $0 }");
+    assert_eq!(if_["insertTextFormat"], 2);
+
+    let server = project(code);
+    let completions = server.request_value::<Completion>(params(&server));
+    let foo = completion_item(&completions, "foo");
+    assert_eq!(foo["insertText"], "foo()");
+    assert!(foo.get("insertTextFormat").is_none());
+    let if_ = completion_item(&completions, "if");
+    assert!(if_.get("insertText").is_none());
+    assert!(completions
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|it| it["label"] != "pd"));
+}
+
+#[test]
+fn test_hover_markdown() {
+    let code = r#"
+//- lib.rs
+/// Does nothing.
+fn foo() {}
+fn main() {
+    foo();
+}
+"#;
+    let params = |server: &Server| TextDocumentPositionParams {
+        text_document: server.doc_id("lib.rs"),
+        position: Position::new(3, 5),
+    };
+
+    let server = project_with_config(
+        code,
+        ServerConfig {
+            supports_markdown_hover: true,
+            ..default_config()
+        },
+    );
+    server.request::<HoverRequest>(
+        params(&server),
+        json!({
+            "contents": {
+                "kind": "markdown",
+                "value": "```rust\nfn foo\n```\n\nDoes nothing."
+            },
+            "range": {
+                "start": { "line": 3, "character": 4 },
+                "end": { "line": 3, "character": 7 }
+            }
+        }),
+    );
+
+    let server = project(code);
+    server.request::<HoverRequest>(
+        params(&server),
+        json!({
+            "contents": {
+                "kind": "plaintext",
+                "value": "fn foo\n\nDoes nothing."
+            },
+            "range": {
+                "start": { "line": 3, "character": 4 },
+                "end": { "line": 3, "character": 7 }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_completion_after_dot() {
+    let server = project(
+        r#"
+//- lib.rs
+struct Foo { bar: u32 }
+fn main(foo: Foo) {
+    foo.
+    let x = 1.
+}
+"#,
+    );
+    let params = |line, character| CompletionParams {
+        text_document: server.doc_id("lib.rs"),
+        position: Position::new(line, character),
+        context: Some(CompletionContext {
+            trigger_kind: CompletionTriggerKind::TriggerCharacter,
+            trigger_character: Some(".".to_string()),
+        }),
+    };
+    let completions = server.request_value::<Completion>(params(2, 8));
+    let bar = completion_item(&completions, "bar");
+    assert_eq!(bar["detail"], "u32");
+    assert_eq!(completions.as_array().unwrap().len(), 1);
+
+    server.request::<Completion>(params(3, 14), json!(null));
+}
+
+#[test]
+fn test_resolve_completion_item() {
+    let code = r#"
+//- lib.rs
+/// Does nothing.
+fn foo() {}
+fn main() {
+    f
+}
+"#;
+    let completions = |server: &Server| {
+        server.request_value::<Completion>(CompletionParams {
+            text_document: server.doc_id("lib.rs"),
+            position: Position::new(3, 5),
+            context: None,
+        })
+    };
+
+    let server = project(code);
+    let foo = completion_item(&completions(&server), "foo");
+    assert!(foo.get("documentation").is_none());
+    let resolved =
+        server.request_value::<ResolveCompletionItem>(serde_json::from_value(foo).unwrap());
+    assert_eq!(resolved["detail"], "fn foo");
+    assert_eq!(resolved["documentation"], json!("Does nothing."));
+
+    let server = project_with_config(
+        code,
+        ServerConfig {
+            supports_markdown_completion: true,
+            ..default_config()
+        },
+    );
+    let completions = completions(&server);
+    let foo = completion_item(&completions, "foo");
+    // The definition is still found after an edit in front of it.
+    server.notification::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: server.doc_id("lib.rs").uri,
+            language_id: "rust".to_string(),
+            version: 0,
+            text: "fn bar() {}\n/// Does nothing.\nfn foo() {}\nfn main() {\n    f\n}\n"
+                .to_string(),
+        },
+    });
+    let resolved =
+        server.request_value::<ResolveCompletionItem>(serde_json::from_value(foo).unwrap());
+    assert_eq!(resolved["detail"], "fn foo");
+    assert_eq!(
+        resolved["documentation"],
+        json!({ "kind": "markdown", "value": "Does nothing." })
+    );
+
+    let if_ = completion_item(&completions, "if");
+    assert!(if_.get("data").is_none());
+    server.request::<ResolveCompletionItem>(serde_json::from_value(if_.clone()).unwrap(), if_);
+}
@@ -1,8 +1,9 @@
 use std::{
     cell::{Cell, RefCell},
     fs,
+    env,
     path::PathBuf,
-    sync::Once,
+    sync::{Mutex, Once},
     time::Duration,
 };
 
@@ -10,7 +11,7 @@ use crossbeam_channel::{after, select, Receiver};
 use flexi_logger::Logger;
 use gen_lsp_server::{RawMessage, RawNotification, RawRequest};
 use languageserver_types::{
-    notification::DidOpenTextDocument,
+    notification::{DidOpenTextDocument, Notification},
     request::{Request, Shutdown},
     DidOpenTextDocumentParams, TextDocumentIdentifier, TextDocumentItem, Url,
 };
@@ -21,27 +22,92 @@ use thread_worker::{WorkerHandle, Worker};
 use test_utils::{parse_fixture, find_mismatch};
 
 use ra_lsp_server::{
-    main_loop, req,
+    main_loop, req, ServerConfig,
 };
 
 pub fn project(fixture: &str) -> Server {
+    project_inner(fixture, None, default_config())
+}
+
+/// Like `project`, but the server is started in the `root` subdirectory.
+/// Files outside of `root` are written to disk, but are not opened, so they
+/// can be used as out-of-workspace dependencies.
+pub fn project_with_root(fixture: &str, root: &str) -> Server {
+    project_inner(fixture, Some(root), default_config())
+}
+
+/// Like `project`, but with `config` instead of the defaults used by tests.
+pub fn project_with_config(fixture: &str, config: ServerConfig) -> Server {
+    project_inner(fixture, None, config)
+}
+
+/// The config used by tests, which want decorations.
+pub fn default_config() -> ServerConfig {
+    ServerConfig {
+        publish_decorations: true,
+        ..ServerConfig::default()
+    }
+}
+
+fn project_inner(fixture: &str, root: Option<&str>, config: ServerConfig) -> Server {
     static INIT: Once = Once::new();
-    INIT.call_once(|| Logger::with_env_or_str(crate::LOG).start().unwrap());
+    INIT.call_once(|| {
+        if crate::LOG.is_empty() && env::var_os("RUST_LOG").is_none() {
+            log::set_logger(&ErrorRecorder).unwrap();
+            log::set_max_level(log::LevelFilter::Error);
+        } else {
+            Logger::with_env_or_str(crate::LOG).start().unwrap()
+        }
+    });
 
     let tmp_dir = TempDir::new("test-project").unwrap();
+    let root = match root {
+        Some(root) => tmp_dir.path().join(root),
+        None => tmp_dir.path().to_path_buf(),
+    };
     let mut paths = vec![];
 
     for entry in parse_fixture(fixture) {
         let path = tmp_dir.path().join(entry.meta);
         fs::create_dir_all(path.parent().unwrap()).unwrap();
         fs::write(path.as_path(), entry.text.as_bytes()).unwrap();
-        paths.push((path, entry.text));
+        if path.starts_with(&root) {
+            paths.push((path, entry.text));
+        }
     }
-    Server::new(tmp_dir, paths)
+    Server::new(tmp_dir, root, paths, config)
+}
+
+static LOGGED_ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The logger used unless logging is turned on: it prints nothing, but keeps
+/// the errors for `logged_errors`.
+struct ErrorRecorder;
+
+impl log::Log for ErrorRecorder {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Error
+    }
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            LOGGED_ERRORS
+                .lock()
+                .unwrap()
+                .push(record.args().to_string());
+        }
+    }
+    fn flush(&self) {}
+}
+
+/// Errors logged so far by all the servers of the test binary. Nothing is
+/// recorded if logging is turned on with `LOG` or `RUST_LOG`.
+pub fn logged_errors() -> Vec<String> {
+    LOGGED_ERRORS.lock().unwrap().clone()
 }
 
 pub struct Server {
     req_id: Cell<u64>,
+    shut_down: Cell<bool>,
     messages: RefCell<Vec<RawMessage>>,
     dir: TempDir,
     worker: Option<Worker<RawMessage, RawMessage>>,
@@ -49,17 +115,22 @@ pub struct Server {
 }
 
 impl Server {
-    fn new(dir: TempDir, files: Vec<(PathBuf, String)>) -> Server {
-        let path = dir.path().to_path_buf();
+    fn new(
+        dir: TempDir,
+        path: PathBuf,
+        files: Vec<(PathBuf, String)>,
+        config: ServerConfig,
+    ) -> Server {
         let (worker, watcher) = thread_worker::spawn::<RawMessage, RawMessage, _>(
             "test server",
             128,
-            move |mut msg_receiver, mut msg_sender| {
-                main_loop(true, path, true, &mut msg_receiver, &mut msg_sender).unwrap()
+            move |msg_receiver, msg_sender| {
+                main_loop(true, path, config, &msg_receiver, &msg_sender).unwrap()
             },
         );
         let res = Server {
             req_id: Cell::new(1),
+            shut_down: Cell::new(false),
             dir,
             messages: Default::default(),
             worker: Some(worker),
@@ -108,6 +179,24 @@ impl Server {
         }
     }
 
+    /// Like `request`, but returns the response for the caller to inspect.
+    pub fn request_value<R>(&self, params: R::Params) -> Value
+    where
+        R: Request,
+        R::Params: Serialize,
+    {
+        let id = self.req_id.get();
+        self.req_id.set(id + 1);
+        self.send_request::<R>(id, params)
+    }
+
+    /// Sends `shutdown` and waits for the response, so the test can inspect
+    /// what the server sent while going away.
+    pub fn shutdown(&self) {
+        self.send_request::<Shutdown>(666, ());
+        self.shut_down.set(true);
+    }
+
     fn send_request<R>(&self, id: u64, params: R::Params) -> Value
     where
         R: Request,
@@ -151,18 +240,58 @@ impl Server {
             }
         }
         while total < n {
-            let msg = self.recv().expect("no response");
-            if f(&msg) {
-                total += 1;
+            self.wait_for(f);
+            total += 1;
+        }
+    }
+    /// Receives messages until one matches `pred` and returns it. Panics on
+    /// timeout, listing the messages seen while waiting.
+    pub fn wait_for<F: Fn(&RawMessage) -> bool>(&self, pred: F) -> RawMessage {
+        let start = self.messages.borrow().len();
+        let receiver = &self.worker.as_ref().unwrap().out;
+        let timeout = Duration::from_secs(5);
+        loop {
+            let msg = select! {
+                recv(receiver, msg) => msg.expect("server stopped"),
+                recv(after(timeout)) => panic!(
+                    "timed out, seen messages:\n{:#?}",
+                    &self.messages.borrow()[start..],
+                ),
+            };
+            self.messages.borrow_mut().push(msg.clone());
+            if pred(&msg) {
+                return msg;
             }
         }
     }
+    /// Params of the notifications with the given method received so far.
+    pub fn seen_notifications(&self, method: &str) -> Vec<Value> {
+        self.messages
+            .borrow()
+            .iter()
+            .filter_map(|msg| match msg {
+                RawMessage::Notification(n) if n.method == method => Some(n.params.clone()),
+                _ => None,
+            })
+            .collect()
+    }
     fn recv(&self) -> Option<RawMessage> {
         recv_timeout(&self.worker.as_ref().unwrap().out).map(|msg| {
             self.messages.borrow_mut().push(msg.clone());
             msg
         })
     }
+    pub fn notification<N>(&self, params: N::Params)
+    where
+        N: Notification,
+        N::Params: Serialize,
+    {
+        self.send_notification(RawNotification::new::<N>(&params))
+    }
+    /// Sends `msg` as is, without waiting for anything in return.
+    pub fn send(&self, msg: RawMessage) {
+        self.worker.as_ref().unwrap().send(msg);
+    }
     fn send_notification(&self, not: RawNotification) {
         self.worker
             .as_ref()
@@ -173,7 +302,9 @@ impl Server {
 
 impl Drop for Server {
     fn drop(&mut self) {
-        self.send_request::<Shutdown>(666, ());
+        if !self.shut_down.get() {
+            self.send_request::<Shutdown>(666, ());
+        }
         let receiver = self.worker.take().unwrap().stop();
         while let Some(msg) = recv_timeout(&receiver) {
             drop(msg);
@@ -12,8 +12,70 @@ pub fn find_leaf_at_offset(node: SyntaxNodeRef, offset: TextUnit) -> LeafAtOffse
     }
 }
 
+/// Which of the two leaves to prefer when an offset falls exactly on the
+/// boundary between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bias {
+    Left,
+    Right,
+}
+
+/// Like `find_leaf_at_offset`, but resolves the `Between` case according to
+/// `bias`.
+pub fn find_token_at_offset(
+    node: SyntaxNodeRef,
+    offset: TextUnit,
+    bias: Bias,
+) -> Option<SyntaxNodeRef> {
+    match find_leaf_at_offset(node, offset) {
+        LeafAtOffset::None => None,
+        LeafAtOffset::Single(n) => Some(n),
+        LeafAtOffset::Between(l, r) => match bias {
+            Bias::Left => Some(l),
+            Bias::Right => Some(r),
+        },
+    }
+}
+
+/// Returns the deepest node in `root`'s subtree which contains `range`, or
+/// `root` itself if `range` is not inside of it.
 pub fn find_covering_node(root: SyntaxNodeRef, range: TextRange) -> SyntaxNodeRef {
-    SyntaxNode(root.0.covering_node(range))
+    let mut res = root;
+    while let Some(child) = child_covering(res, range) {
+        res = child;
+    }
+    res
+}
+
+/// Children are sorted and contiguous, so the only child which can contain
+/// `range` is the first one reaching up to its end, or equivalently the last
+/// one starting before it. Rowan gives no random access to the children,
+/// only iteration and links to the siblings, so instead of a binary search
+/// the children are scanned from the end of `node` which is closer to
+/// `range`.
+fn child_covering(node: SyntaxNodeRef, range: TextRange) -> Option<SyntaxNodeRef> {
+    let node_range = node.range();
+    if !range.is_subrange(&node_range) {
+        return None;
+    }
+    let from_start = range.start() - node_range.start();
+    let from_end = node_range.end() - range.end();
+    let child = if from_start <= from_end {
+        node.children()
+            .find(|child| child.range().end() >= range.end())?
+    } else {
+        // An empty range between two children belongs to the left one.
+        let starts_before = |child: &SyntaxNodeRef| {
+            let start = child.range().start();
+            start < range.start() || (start == range.start() && !range.is_empty())
+        };
+        generate(node.last_child(), |child| child.prev_sibling()).find(starts_before)?
+    };
+    if range.is_subrange(&child.range()) {
+        Some(child)
+    } else {
+        None
+    }
 }
 
 pub fn generate<T>(seed: Option<T>, step: impl Fn(&T) -> Option<T>) -> impl Iterator<Item = T> {
@@ -24,3 +86,96 @@ pub fn generate<T>(seed: Option<T>, step: impl Fn(&T) -> Option<T>) -> impl Iter
         })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{SourceFileNode, SyntaxKind::*};
+
+    #[test]
+    fn test_find_token_at_offset() {
+        let file = SourceFileNode::parse("fn foo(x: i32) {}");
+        let check = |offset: u32, bias, kind| {
+            let token = find_token_at_offset(file.syntax(), offset.into(), bias).unwrap();
+            assert_eq!(token.kind(), kind);
+        };
+        // Between `foo` and `(`.
+        check(6, Bias::Left, IDENT);
+        check(6, Bias::Right, L_PAREN);
+        // Inside `foo`, the bias doesn't matter.
+        check(4, Bias::Left, IDENT);
+        check(4, Bias::Right, IDENT);
+        // At the very start and end of the file there's a single leaf.
+        check(0, Bias::Left, FN_KW);
+        check(17, Bias::Right, R_CURLY);
+    }
+
+    #[test]
+    fn test_find_covering_node_agrees_with_rowan() {
+        let text = "fn foo(x: i32) -> [u8; 3] {\n    let a = [1, 2, 3];\n    bar(a, {x})\n}\n";
+        let file = SourceFileNode::parse(text);
+        let len = text.len() as u32;
+        for start in 0..=len {
+            for end in start..=len {
+                let range = TextRange::from_to(start.into(), end.into());
+                let expected = SyntaxNode(file.syntax().0.covering_node(range));
+                assert_eq!(find_covering_node(file.syntax(), range), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_covering_node_in_wide_node() {
+        let elements = (0..1000).map(|i| i.to_string()).collect::<Vec<_>>();
+        let text = format!("const A: [u32; 1000] = [{}];", elements.join(", "));
+        let file = SourceFileNode::parse(&text);
+        let start = TextUnit::of_str(&text[..text.find("998").unwrap()]);
+        let single = TextRange::offset_len(start, 3.into());
+        assert_eq!(find_covering_node(file.syntax(), single).kind(), INT_NUMBER);
+        let two = TextRange::offset_len(start, 8.into());
+        let node = find_covering_node(file.syntax(), two);
+        assert_eq!(node.kind(), ARRAY_EXPR);
+        assert_eq!(node.children_of_kind(LITERAL).count(), 1000);
+        let end = TextUnit::of_str(&text) + TextUnit::from(1);
+        let outside = TextRange::from_to(start, end);
+        assert_eq!(find_covering_node(file.syntax(), outside), file.syntax());
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_find_covering_node_in_wide_node() {
+        let elements = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        let text = format!("const A: [u32; 100000] = [{}];", elements.join(", "));
+        let file = SourceFileNode::parse(&text);
+        for &elem in ["10", "50000", "99990"].iter() {
+            let pat = format!(" {},", elem);
+            let start = TextUnit::of_str(&text[..text.find(&pat).unwrap() + 1]);
+            let range = TextRange::offset_len(start, TextUnit::of_str(elem));
+            let linear = || {
+                let mut node = file.syntax();
+                while let Some(child) = node.children().find(|it| range.is_subrange(&it.range())) {
+                    node = child;
+                }
+                node
+            };
+            assert_eq!(find_covering_node(file.syntax(), range), linear());
+            let t = Instant::now();
+            for _ in 0..100 {
+                find_covering_node(file.syntax(), range);
+            }
+            let fast = t.elapsed();
+            let t = Instant::now();
+            for _ in 0..100 {
+                linear();
+            }
+            let slow = t.elapsed();
+            eprintln!(
+                "element {:>5}: {:?} vs {:?} for a linear scan",
+                elem, fast, slow
+            );
+        }
+    }
+}
@@ -91,6 +91,52 @@ pub trait DocCommentsOwner<'a>: AstNode<'a> {
             })
             .join("\n")
     }
+
+    /// Returns the documentation of the item, suitable for rendering on hover:
+    /// the `///` lines preceding the item followed by the leading `//!` lines
+    /// of its body. Unlike `doc_comment_text`, plain comments are skipped and
+    /// only the whitespace common to all lines is stripped.
+    fn docs(self) -> Option<RustString> {
+        let outer = self
+            .doc_comments()
+            .filter(|comment| comment.flavor() == CommentFlavor::Doc);
+        let lines = outer
+            .chain(inner_doc_comments(self.syntax()))
+            .map(|comment| {
+                let text = comment.text().as_str().trim_end();
+                text[comment.prefix().len()..].to_owned()
+            })
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return None;
+        }
+        let indent = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let docs = lines
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or(""))
+            .join("\n");
+        Some(docs)
+    }
+}
+
+/// Leading `//!` comments of an item's body, be it a block or an item list.
+fn inner_doc_comments<'a>(item: SyntaxNodeRef<'a>) -> impl Iterator<Item = Comment<'a>> {
+    let body = item
+        .children()
+        .find(|child| child.kind() == ITEM_LIST || child.kind() == BLOCK);
+    body.into_iter()
+        .flat_map(|body| body.descendants())
+        .filter(|node| node.leaf_text().is_some())
+        .skip_while(|node| node.kind() == L_CURLY)
+        .filter(|node| node.kind() != WHITESPACE)
+        .take_while(|node| node.kind() == COMMENT)
+        .filter_map(Comment::cast)
+        .take_while(|comment| comment.flavor() == CommentFlavor::ModuleDoc)
 }
 
 impl<'a> FnDef<'a> {
@@ -110,6 +156,28 @@ impl<'a> Attr<'a> {
         }
     }
 
+    /// Splits `#[key = "value"]` into the key and the contents of the string.
+    /// Escapes in the string are left as is.
+    pub fn as_key_value(&self) -> Option<(SmolStr, SmolStr)> {
+        let tt = self.value()?;
+        let tokens = tt
+            .syntax()
+            .children()
+            .filter(|it| !it.kind().is_trivia())
+            .collect::<Vec<_>>();
+        let (key, value) = match tokens.as_slice() {
+            [_bra, key, eq, value, _ket]
+                if key.kind() == IDENT && eq.kind() == EQ && value.kind() == STRING =>
+            {
+                (key, value)
+            }
+            _ => return None,
+        };
+        let value = value.leaf_text().unwrap();
+        let value = &value[1..value.len() - 1];
+        Some((key.leaf_text().unwrap().clone(), SmolStr::new(value)))
+    }
+
     pub fn as_call(&self) -> Option<(SmolStr, TokenTree<'a>)> {
         let tt = self.value()?;
         let (_bra, attr, args, _ket) = tt.syntax().children().collect_tuple()?;
@@ -218,16 +286,26 @@ impl<'a> Whitespace<'a> {
 }
 
 impl<'a> Name<'a> {
+    /// The identifier, without the `r#` prefix of raw identifiers.
     pub fn text(&self) -> SmolStr {
         let ident = self.syntax().first_child().unwrap();
-        ident.leaf_text().unwrap().clone()
+        strip_raw_prefix(ident.leaf_text().unwrap())
     }
 }
 
 impl<'a> NameRef<'a> {
+    /// The identifier, without the `r#` prefix of raw identifiers.
     pub fn text(&self) -> SmolStr {
         let ident = self.syntax().first_child().unwrap();
-        ident.leaf_text().unwrap().clone()
+        strip_raw_prefix(ident.leaf_text().unwrap())
+    }
+}
+
+fn strip_raw_prefix(text: &SmolStr) -> SmolStr {
+    if text.starts_with("r#") {
+        SmolStr::new(&text[2..])
+    } else {
+        text.clone()
     }
 }
 
@@ -254,6 +332,15 @@ impl<'a> ImplItem<'a> {
     }
 }
 
+impl<'a> StructDef<'a> {
+    /// Fields of a `struct S { .. }`; empty for tuple and unit structs.
+    pub fn named_fields(self) -> impl Iterator<Item = NamedFieldDef<'a>> + 'a {
+        self.named_field_def_list()
+            .into_iter()
+            .flat_map(|it| it.fields())
+    }
+}
+
 impl<'a> Module<'a> {
     pub fn has_semi(self) -> bool {
         match self.syntax().last_child() {
@@ -363,3 +450,69 @@ impl<'a, N: AstNode<'a>> Iterator for AstChildren<'a, N> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SourceFileNode;
+
+    #[test]
+    fn test_docs() {
+        let file = SourceFileNode::parse(
+            r#"
+// not a doc comment
+/// Adds two numbers.
+///
+/// ```
+///     assert_eq!(add(1, 2), 3);
+/// ```
+fn add(a: i32, b: i32) -> i32 {
+    //! Inner docs.
+    // not a doc comment either
+    a + b
+}
+"#,
+        );
+        let func = file.ast().functions().next().unwrap();
+        assert_eq!(
+            func.docs().unwrap(),
+            "Adds two numbers.\n\n```\n    assert_eq!(add(1, 2), 3);\n```\nInner docs."
+        );
+    }
+
+    #[test]
+    fn test_docs_module() {
+        let file = SourceFileNode::parse(
+            r#"
+mod foo {
+    //!   Module docs
+    //!     indented
+    fn bar() {}
+}
+"#,
+        );
+        let module = file.ast().modules().next().unwrap();
+        assert_eq!(module.docs().unwrap(), "Module docs\n  indented");
+    }
+
+    #[test]
+    fn test_attr_as_key_value() {
+        let file = SourceFileNode::parse(
+            r#"
+#[path = "foo/bar.rs"]
+#[cfg(test)]
+#[inline]
+mod foo;
+"#,
+        );
+        let module = file.ast().modules().next().unwrap();
+        let attrs = module
+            .attrs()
+            .map(|it| it.as_key_value())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attrs,
+            vec![Some(("path".into(), "foo/bar.rs".into())), None, None]
+        );
+    }
+}
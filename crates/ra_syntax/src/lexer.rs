@@ -71,6 +71,13 @@ fn next_token_inner(c: char, ptr: &mut Ptr) -> SyntaxKind {
         _ => (),
     }
 
+    if c == 'r' && ptr.at('#') && ptr.nth_is_p(1, is_ident_start) {
+        // raw identifier, like `r#match`; never a keyword
+        ptr.bump();
+        ptr.bump_while(is_ident_continue);
+        return IDENT;
+    }
+
     let ident_start = is_ident_start(c) && !is_string_literal_start(c, ptr.current(), ptr.nth(1));
     if ident_start {
         return scan_ident(c, ptr);
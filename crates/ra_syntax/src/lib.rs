@@ -47,14 +47,19 @@ pub use crate::{
     },
 };
 
+use std::mem;
+
 use ra_text_edit::AtomTextEdit;
-use crate::yellow::GreenNode;
+use crate::yellow::{GreenNode, SyntaxErrorKind};
 
 /// `SourceFileNode` represents a parse tree for a single Rust file.
 pub use crate::ast::SourceFileNode;
 
 impl SourceFileNode {
-    fn new(green: GreenNode, errors: Vec<SyntaxError>) -> SourceFileNode {
+    fn new(green: GreenNode, mut errors: Vec<SyntaxError>) -> SourceFileNode {
+        // Incremental reparsing appends the errors of the reparsed node last,
+        // `SyntaxNodeRef::parse_errors` relies on them being sorted.
+        errors.sort_by_key(|e| e.range().start());
         let root = SyntaxNode::new(green, errors);
         if cfg!(debug_assertions) {
             utils::validate_block_structure(root.borrowed());
@@ -64,8 +69,15 @@ impl SourceFileNode {
     }
     pub fn parse(text: &str) -> SourceFileNode {
         let tokens = tokenize(&text);
-        let (green, errors) =
+        let res =
             parser_impl::parse_with(yellow::GreenBuilder::new(), text, &tokens, grammar::root);
+        let (green, errors) = match res {
+            Ok(it) => it,
+            Err(err) => {
+                debug_assert!(false, "unbalanced syntax tree: {}", err);
+                yellow::unparsed_tree(text, &err)
+            }
+        };
         SourceFileNode::new(green, errors)
     }
     pub fn reparse(&self, edit: &AtomTextEdit) -> SourceFileNode {
@@ -73,12 +85,14 @@ impl SourceFileNode {
             .unwrap_or_else(|| self.full_reparse(edit))
     }
     pub fn incremental_reparse(&self, edit: &AtomTextEdit) -> Option<SourceFileNode> {
-        reparsing::incremental_reparse(self.syntax(), edit, self.errors())
+        // Only parse errors are stored in the tree, validation errors are
+        // recomputed by `errors`.
+        let parse_errors = self.syntax.root_data().clone();
+        reparsing::incremental_reparse(self.syntax(), edit, parse_errors)
             .map(|(green_node, errors)| SourceFileNode::new(green_node, errors))
     }
     fn full_reparse(&self, edit: &AtomTextEdit) -> SourceFileNode {
-        let text =
-            text_utils::replace_range(self.syntax().text().to_string(), edit.delete, &edit.insert);
+        let text = text_utils::replace_range(self.text(), edit.delete, &edit.insert);
         SourceFileNode::parse(&text)
     }
     /// Typed AST representation of the parse tree.
@@ -89,9 +103,153 @@ impl SourceFileNode {
     pub fn syntax(&self) -> SyntaxNodeRef {
         self.syntax.borrowed()
     }
+    /// The full source text, reassembled from the leaves of the tree.
+    pub fn text(&self) -> String {
+        self.syntax().text().to_string()
+    }
+    /// Length of the source text, the same as `syntax().range().end()`.
+    pub fn len(&self) -> TextUnit {
+        self.syntax().range().end()
+    }
+    /// Parse errors, followed by the errors found by validation. Validation
+    /// walks the whole tree, so this is linear in the size of the file.
     pub fn errors(&self) -> Vec<SyntaxError> {
         let mut errors = self.syntax.root_data().clone();
         errors.extend(validation::validate(self));
         errors
     }
+    /// The tree and its errors as an indented S-expression, for debugging and
+    /// golden tests. See `utils::dump_sexp`.
+    pub fn debug_dump(&self) -> String {
+        utils::dump_sexp(self.syntax(), &self.errors())
+    }
+    /// Approximate amount of memory, in bytes, held by this file: the text of
+    /// the leaves, the tree nodes and the parse errors.
+    pub fn memory_size(&self) -> usize {
+        let nodes = self
+            .syntax()
+            .descendants()
+            .map(|node| {
+                let text_size = node.leaf_text().map_or(0, |text| text.len());
+                mem::size_of::<GreenNode>() + text_size
+            })
+            .sum::<usize>();
+        let errors = self.syntax.root_data();
+        let errors = errors.capacity() * mem::size_of::<SyntaxError>()
+            + errors
+                .iter()
+                .map(|error| match error.kind() {
+                    SyntaxErrorKind::ParseError(e) => e.0.capacity(),
+                    _ => 0,
+                })
+                .sum::<usize>();
+        nodes + errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_size_grows_with_input() {
+        let small = SourceFileNode::parse("fn foo() {}");
+        let large = SourceFileNode::parse(&"fn foo() { let x = 92; }\n".repeat(100));
+        assert!(small.memory_size() > 0);
+        assert!(large.memory_size() > small.memory_size());
+
+        let broken = SourceFileNode::parse("fn foo() {");
+        assert!(!broken.errors().is_empty());
+        assert!(broken.memory_size() > SourceFileNode::parse("fn foo() {}").memory_size());
+    }
+
+    #[test]
+    fn test_text_and_len() {
+        let text = "fn foo() {\n    1 +\n}\n// кириллица\n";
+        let file = SourceFileNode::parse(text);
+        assert_eq!(file.text(), text);
+        assert_eq!(file.len(), TextUnit::of_str(text));
+        assert_eq!(file.len(), file.syntax().range().end());
+
+        let empty = SourceFileNode::parse("");
+        assert_eq!(empty.text(), "");
+        assert_eq!(empty.len(), 0.into());
+    }
+
+    #[test]
+    fn test_errors_include_trailing_error() {
+        // Two errors inside the parameter list, then one at the end of file.
+        let file = SourceFileNode::parse("fn foo(a: u32 b: u32 c: u32) {}\nfn bar() {");
+        let errors = file
+            .errors()
+            .into_iter()
+            .map(|it| (it.offset(), it.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (13.into(), "expected COMMA".to_string()),
+                (20.into(), "expected COMMA".to_string()),
+                (42.into(), "expected R_CURLY".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_range() {
+        let file = SourceFileNode::parse("fn foo() { let x = 1 }\nfn bar() { 'ab'; }");
+        let ranges = file
+            .errors()
+            .into_iter()
+            .map(|it| (it.range(), it.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            vec![
+                (
+                    TextRange::from_to(20.into(), 20.into()),
+                    "expected SEMI".to_string()
+                ),
+                (
+                    TextRange::from_to(34.into(), 38.into()),
+                    "Char literal should be one character long".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debug_dump() {
+        let file = SourceFileNode::parse("fn main() {}");
+        assert_eq!(
+            file.debug_dump(),
+            r#"(SOURCE_FILE
+  (FN_DEF
+    (FN_KW "fn")
+    (WHITESPACE " ")
+    (NAME
+      (IDENT "main"))
+    (PARAM_LIST
+      (L_PAREN "(")
+      (R_PAREN ")"))
+    (WHITESPACE " ")
+    (BLOCK
+      (L_CURLY "{")
+      (R_CURLY "}"))))"#
+        );
+
+        let file = SourceFileNode::parse("fn f() { 'ab'; }\nfn");
+        let dump = file.debug_dump();
+        assert!(dump.contains(
+            r#"(CHAR "'ab'")
+          (error "Char literal should be one character long")"#
+        ));
+        assert!(dump.ends_with(
+            r#"(FN_KW "fn")
+    (error "expected a name")
+    (error "expected function arguments")
+    (error "expected a block")
+    (ERROR)))"#
+        ));
+    }
 }
@@ -1,4 +1,3 @@
-use crate::algo;
 use crate::grammar;
 use crate::lexer::{tokenize, Token};
 use crate::parser_api::Parser;
@@ -24,7 +23,7 @@ fn reparse_leaf<'node>(
     node: SyntaxNodeRef<'node>,
     edit: &AtomTextEdit,
 ) -> Option<(SyntaxNodeRef<'node>, GreenNode, Vec<SyntaxError>)> {
-    let node = algo::find_covering_node(node, edit.delete);
+    let node = node.covering_node(edit.delete);
     match node.kind() {
         WHITESPACE | COMMENT | IDENT | STRING | RAW_STRING => {
             let text = get_text_after_edit(node, &edit);
@@ -56,8 +55,15 @@ fn reparse_block<'node>(
     if !is_balanced(&tokens) {
         return None;
     }
-    let (green, new_errors) =
-        parser_impl::parse_with(yellow::GreenBuilder::new(), &text, &tokens, reparser);
+    let res = parser_impl::parse_with(yellow::GreenBuilder::new(), &text, &tokens, reparser);
+    let (green, new_errors) = match res {
+        Ok(it) => it,
+        Err(err) => {
+            // The full reparse gets a chance to do better.
+            debug_assert!(false, "unbalanced syntax tree: {}", err);
+            return None;
+        }
+    };
     Some((node, green, new_errors))
 }
 
@@ -81,7 +87,7 @@ fn find_reparsable_node(
     node: SyntaxNodeRef<'_>,
     range: TextRange,
 ) -> Option<(SyntaxNodeRef<'_>, ParseFn)> {
-    let node = algo::find_covering_node(node, range);
+    let node = node.covering_node(range);
     return node
         .ancestors()
         .filter_map(|node| reparser(node).map(|r| (node, r)))
@@ -182,7 +188,8 @@ mod tests {
             let (node, green, new_errors) =
                 reparser(f.syntax(), &edit).expect("cannot incrementally reparse");
             let green_root = node.replace_with(green);
-            let errors = super::merge_errors(f.errors(), new_errors, node, &edit);
+            let errors =
+                super::merge_errors(f.syntax().root_data().to_vec(), new_errors, node, &edit);
             SourceFileNode::new(green_root, errors)
         };
 
@@ -373,4 +380,29 @@ enum Foo {
             "Clone",
         );
     }
+
+    #[test]
+    fn reparse_matches_full_parse() {
+        fn check(before: &str, replace_with: &str, is_incremental: bool) {
+            let (range, before) = extract_range(before);
+            let edit = AtomTextEdit {
+                delete: range,
+                insert: replace_with.to_string(),
+            };
+            let file = SourceFileNode::parse(&before);
+            assert_eq!(file.incremental_reparse(&edit).is_some(), is_incremental);
+            let reparsed = file.reparse(&edit);
+            let fully_reparsed = SourceFileNode::parse(&replace_range(before, range, replace_with));
+            assert_eq_text!(
+                &dump_tree(fully_reparsed.syntax()),
+                &dump_tree(reparsed.syntax()),
+            );
+            assert_eq!(fully_reparsed.errors(), reparsed.errors());
+        }
+
+        check("fn foo() { 'ab'; <|>92<|> }", "62", true);
+        check("fn bar() { 'ab'; } fn foo() { <|>92<|> }", "62", true);
+        check("fn foo() { 'ab'; let x = <|><|> }", "1;", true);
+        check("fn foo() { <|>92<|> }", "{", false);
+    }
 }
@@ -23,4 +23,57 @@ impl SyntaxKind {
             _ => false,
         }
     }
+
+    /// Number, character and string literal tokens. `true` and `false` are
+    /// keywords.
+    pub fn is_literal(self) -> bool {
+        match self {
+            INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE | STRING | RAW_STRING | BYTE_STRING
+            | RAW_BYTE_STRING => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_groups() {
+        assert!(CALL_EXPR.is_expr());
+        assert!(LITERAL.is_expr());
+        assert!(!MATCH_ARM.is_expr());
+        assert!(!PATH_TYPE.is_expr());
+
+        assert!(FN_DEF.is_item());
+        assert!(MODULE.is_item());
+        assert!(!BLOCK_EXPR.is_item());
+
+        assert!(BIND_PAT.is_pat());
+        assert!(TUPLE_STRUCT_PAT.is_pat());
+        assert!(!FIELD_PAT_LIST.is_pat());
+
+        assert!(REFERENCE_TYPE.is_type());
+        assert!(!PATH_EXPR.is_type());
+        assert!(!IDENT.is_type());
+    }
+
+    #[test]
+    fn test_token_classes() {
+        assert!(WHITESPACE.is_trivia());
+        assert!(COMMENT.is_trivia());
+        assert!(!SEMI.is_trivia());
+
+        assert!(FN_KW.is_keyword());
+        assert!(TRUE_KW.is_keyword());
+        assert!(UNION_KW.is_keyword());
+        assert!(!IDENT.is_keyword());
+
+        assert!(INT_NUMBER.is_literal());
+        assert!(RAW_BYTE_STRING.is_literal());
+        assert!(CHAR.is_literal());
+        assert!(!TRUE_KW.is_literal());
+        assert!(!LITERAL.is_literal());
+    }
 }
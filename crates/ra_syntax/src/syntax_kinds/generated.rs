@@ -273,6 +273,93 @@ impl SyntaxKind {
         }
     }
 
+    pub fn is_expr(self) -> bool {
+        match self {
+            | TUPLE_EXPR
+            | ARRAY_EXPR
+            | PAREN_EXPR
+            | PATH_EXPR
+            | LAMBDA_EXPR
+            | IF_EXPR
+            | LOOP_EXPR
+            | FOR_EXPR
+            | WHILE_EXPR
+            | CONTINUE_EXPR
+            | BREAK_EXPR
+            | BLOCK_EXPR
+            | RETURN_EXPR
+            | MATCH_EXPR
+            | STRUCT_LIT
+            | CALL_EXPR
+            | INDEX_EXPR
+            | METHOD_CALL_EXPR
+            | FIELD_EXPR
+            | TRY_EXPR
+            | CAST_EXPR
+            | REF_EXPR
+            | PREFIX_EXPR
+            | RANGE_EXPR
+            | BIN_EXPR
+            | LITERAL
+                => true,
+            _ => false
+        }
+    }
+
+    pub fn is_item(self) -> bool {
+        match self {
+            | STRUCT_DEF
+            | ENUM_DEF
+            | FN_DEF
+            | TRAIT_DEF
+            | TYPE_DEF
+            | IMPL_ITEM
+            | USE_ITEM
+            | EXTERN_CRATE_ITEM
+            | CONST_DEF
+            | STATIC_DEF
+            | MODULE
+                => true,
+            _ => false
+        }
+    }
+
+    pub fn is_pat(self) -> bool {
+        match self {
+            | REF_PAT
+            | BIND_PAT
+            | PLACEHOLDER_PAT
+            | PATH_PAT
+            | STRUCT_PAT
+            | TUPLE_STRUCT_PAT
+            | TUPLE_PAT
+            | SLICE_PAT
+            | RANGE_PAT
+                => true,
+            _ => false
+        }
+    }
+
+    pub fn is_type(self) -> bool {
+        match self {
+            | PAREN_TYPE
+            | TUPLE_TYPE
+            | NEVER_TYPE
+            | PATH_TYPE
+            | POINTER_TYPE
+            | ARRAY_TYPE
+            | SLICE_TYPE
+            | REFERENCE_TYPE
+            | PLACEHOLDER_TYPE
+            | FN_POINTER_TYPE
+            | FOR_TYPE
+            | IMPL_TRAIT_TYPE
+            | DYN_TRAIT_TYPE
+                => true,
+            _ => false
+        }
+    }
+
     pub(crate) fn info(self) -> &'static SyntaxInfo {
         match self {
             SEMI => &SyntaxInfo { name: "SEMI" },
@@ -489,7 +576,7 @@ impl SyntaxKind {
             EOF => &SyntaxInfo { name: "EOF" },
         }
     }
-    pub(crate) fn from_keyword(ident: &str) -> Option<SyntaxKind> {
+    pub fn from_keyword(ident: &str) -> Option<SyntaxKind> {
         let kw = match ident {
             "use" => USE_KW,
             "fn" => FN_KW,
@@ -1,4 +1,4 @@
-use crate::{SourceFileNode, SyntaxKind, SyntaxNodeRef, WalkEvent};
+use crate::{SourceFileNode, SyntaxError, SyntaxKind, SyntaxNodeRef, WalkEvent};
 use std::fmt::Write;
 use std::str;
 
@@ -44,6 +44,63 @@ pub fn dump_tree(syntax: SyntaxNodeRef) -> String {
     buf
 }
 
+/// Renders the tree as an S-expression, like `(SOURCE_FILE (FN_KW "fn") ...)`,
+/// with one node per line. `errors` are shown as `(error "...")` after the
+/// leaf where they end.
+pub fn dump_sexp(syntax: SyntaxNodeRef, errors: &[SyntaxError]) -> String {
+    let mut errors: Vec<_> = errors.to_vec();
+    errors.sort_by_key(|e| e.range().end());
+    let mut err_pos = 0;
+    let mut level = 0;
+    let mut buf = String::new();
+    macro_rules! newline {
+        () => {
+            if !buf.is_empty() {
+                buf.push('\n');
+            }
+            for _ in 0..level {
+                buf.push_str("  ");
+            }
+        };
+    }
+
+    for event in syntax.preorder() {
+        match event {
+            WalkEvent::Enter(node) => {
+                newline!();
+                write!(buf, "({:?}", node.kind()).unwrap();
+                match node.leaf_text() {
+                    Some(text) => {
+                        write!(buf, " {:?})", text.as_str()).unwrap();
+                        let off = node.range().end();
+                        while err_pos < errors.len() && errors[err_pos].range().end() <= off {
+                            newline!();
+                            write!(buf, "(error {:?})", errors[err_pos].to_string()).unwrap();
+                            err_pos += 1;
+                        }
+                    }
+                    None => level += 1,
+                }
+            }
+            WalkEvent::Leave(node) => {
+                if node.leaf_text().is_none() {
+                    if node == syntax {
+                        for err in errors[err_pos..].iter() {
+                            newline!();
+                            write!(buf, "(error {:?})", err.to_string()).unwrap();
+                        }
+                    }
+                    level -= 1;
+                    buf.push(')');
+                }
+            }
+        }
+    }
+
+    assert_eq!(level, 0);
+    buf
+}
+
 pub fn check_fuzz_invariants(text: &str) {
     let file = SourceFileNode::parse(text);
     let root = file.syntax();
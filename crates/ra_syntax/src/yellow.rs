@@ -3,14 +3,18 @@ pub mod syntax_error;
 mod syntax_text;
 
 use self::syntax_text::SyntaxText;
-use crate::{SmolStr, SyntaxKind, TextRange};
+use crate::{
+    algo::{Bias, LeafAtOffset},
+    SmolStr, SyntaxKind, TextRange, TextUnit,
+};
 use rowan::Types;
 use std::{
+    cmp::Ordering,
     fmt,
     hash::{Hash, Hasher},
 };
 
-pub(crate) use self::builder::GreenBuilder;
+pub(crate) use self::builder::{unparsed_tree, GreenBuilder};
 pub use self::syntax_error::{SyntaxError, SyntaxErrorKind, Location};
 pub use rowan::{TreeRoot, WalkEvent};
 
@@ -30,6 +34,10 @@ pub type GreenNode = ::rowan::GreenNode<RaTypes>;
 pub struct SyntaxNode<R: TreeRoot<RaTypes> = OwnedRoot>(pub(crate) ::rowan::SyntaxNode<RaTypes, R>);
 pub type SyntaxNodeRef<'a> = SyntaxNode<RefRoot<'a>>;
 
+/// Nodes are compared by identity: two nodes are equal only if they are the
+/// same node of the same tree. Nodes of separately parsed trees are never
+/// equal, even if the text is the same. `Hash` is consistent with this, so
+/// nodes can be used to dedupe visited nodes in a `HashSet`.
 impl<R1, R2> PartialEq<SyntaxNode<R1>> for SyntaxNode<R2>
 where
     R1: TreeRoot<RaTypes>,
@@ -72,18 +80,146 @@ impl<'a> SyntaxNodeRef<'a> {
             WalkEvent::Leave(_) => None,
         })
     }
+    /// Ascends from `self` while `predicate` holds, returning the topmost
+    /// ancestor (`self` included) for which it does.
+    pub fn last_ancestor_while(
+        self,
+        predicate: impl Fn(SyntaxNodeRef<'a>) -> bool,
+    ) -> Option<SyntaxNodeRef<'a>> {
+        self.ancestors().take_while(|&node| predicate(node)).last()
+    }
+    /// Returns the leaves of this subtree, left to right. Whitespace and
+    /// comments are included.
+    pub fn tokens(self) -> impl Iterator<Item = SyntaxNodeRef<'a>> {
+        self.descendants().filter(|node| node.is_leaf())
+    }
+    /// Returns the first direct child of the given kind.
+    pub fn child_of_kind(self, kind: SyntaxKind) -> Option<SyntaxNodeRef<'a>> {
+        self.children_of_kind(kind).next()
+    }
+    /// Returns the direct children of the given kind.
+    pub fn children_of_kind(self, kind: SyntaxKind) -> impl Iterator<Item = SyntaxNodeRef<'a>> {
+        self.children().filter(move |child| child.kind() == kind)
+    }
+    /// Returns the leftmost leaf of this subtree, which is `self` for a leaf.
+    /// Only a node without leaves, like an empty `ERROR`, has no first token.
+    pub fn first_token(self) -> Option<SyntaxNodeRef<'a>> {
+        if self.is_leaf() {
+            return Some(self);
+        }
+        self.children().find_map(|child| child.first_token())
+    }
+    /// Returns the rightmost leaf of this subtree, see `first_token`.
+    pub fn last_token(self) -> Option<SyntaxNodeRef<'a>> {
+        if self.is_leaf() {
+            return Some(self);
+        }
+        let last_child = self.last_child();
+        crate::algo::generate(last_child, |node| node.prev_sibling())
+            .find_map(|child| child.last_token())
+    }
+    /// Returns all descendants of the given kind, `self` included, in
+    /// preorder.
+    pub fn descendants_of_kind(self, kind: SyntaxKind) -> impl Iterator<Item = SyntaxNodeRef<'a>> {
+        self.descendants().filter(move |node| node.kind() == kind)
+    }
+    /// Returns the leaf at `offset`, or both neighbours if `offset` is
+    /// exactly between two leaves.
+    pub fn leaf_at_offset(self, offset: TextUnit) -> LeafAtOffset<SyntaxNodeRef<'a>> {
+        crate::algo::find_leaf_at_offset(self, offset)
+    }
+    /// Returns the leaf at `offset`, preferring the one on the `bias` side if
+    /// `offset` is between two leaves.
+    pub fn token_at_offset(self, offset: TextUnit, bias: Bias) -> Option<SyntaxNodeRef<'a>> {
+        crate::algo::find_token_at_offset(self, offset, bias)
+    }
+    /// Returns the smallest node in the subtree of `self` which contains
+    /// `range`. For a range spanning several children, this is their parent;
+    /// for an empty range between two leaves, the left one is preferred. If
+    /// `range` is not inside of `self`, this is `self`.
+    pub fn covering_node(self, range: TextRange) -> SyntaxNodeRef<'a> {
+        crate::algo::find_covering_node(self, range)
+    }
     pub fn siblings(self, direction: Direction) -> impl Iterator<Item = SyntaxNodeRef<'a>> {
         crate::algo::generate(Some(self), move |&node| match direction {
             Direction::Next => node.next_sibling(),
             Direction::Prev => node.prev_sibling(),
         })
     }
+    /// Like `siblings`, but skips whitespace and comments.
+    pub fn siblings_no_trivia(
+        self,
+        direction: Direction,
+    ) -> impl Iterator<Item = SyntaxNodeRef<'a>> {
+        self.siblings(direction).filter(|it| !it.kind().is_trivia())
+    }
     pub fn preorder(self) -> impl Iterator<Item = WalkEvent<SyntaxNodeRef<'a>>> {
         self.0.preorder().map(|event| match event {
             WalkEvent::Enter(n) => WalkEvent::Enter(SyntaxNode(n)),
             WalkEvent::Leave(n) => WalkEvent::Leave(SyntaxNode(n)),
         })
     }
+    /// Checks if `self` is a strict ancestor of `other`. Nodes from different
+    /// trees are never ancestors of each other.
+    pub fn is_ancestor_of(self, other: SyntaxNodeRef) -> bool {
+        other.ancestors().skip(1).any(|it| it == self)
+    }
+    /// Returns the direct child which contains `offset`, together with the
+    /// offset relative to the start of this child. If `offset` lies on the
+    /// boundary between two children, the right one is returned.
+    pub fn offset_of_child_containing(
+        self,
+        offset: TextUnit,
+    ) -> Option<(SyntaxNodeRef<'a>, TextUnit)> {
+        let child = self.children().find(|child| {
+            let range = child.range();
+            range.start() <= offset && offset < range.end()
+        })?;
+        Some((child, offset - child.range().start()))
+    }
+    /// Returns the first direct child which starts at or after `offset`. A
+    /// child starting exactly at `offset` is returned; a child containing
+    /// `offset` in its middle is skipped.
+    pub fn sibling_after(self, offset: TextUnit) -> Option<SyntaxNodeRef<'a>> {
+        self.children()
+            .find(|child| child.range().start() >= offset)
+    }
+    /// Parse errors located inside this node, ordered by start offset.
+    /// Errors at the very end of the node, like a missing `;`, are included.
+    pub fn parse_errors(self) -> Vec<SyntaxError> {
+        let range = self.range();
+        let errors = self.root_data();
+        let first = errors
+            .binary_search_by(|e| {
+                if e.range().start() < range.start() {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
+            .unwrap_err();
+        errors[first..]
+            .iter()
+            .take_while(|e| e.range().start() <= range.end())
+            .filter(|e| e.range().is_subrange(&range))
+            .cloned()
+            .collect()
+    }
+    /// Splits the text of a leaf at the (absolute) `offset`. Returns `None`
+    /// if this is not a leaf, or if `offset` is outside of the leaf or is not
+    /// on a char boundary.
+    pub fn split_at_offset(self, offset: TextUnit) -> Option<(&'a str, &'a str)> {
+        let text = self.leaf_text()?.as_str();
+        let range = self.range();
+        if offset < range.start() || offset > range.end() {
+            return None;
+        }
+        let idx = (offset - range.start()).to_usize();
+        if !text.is_char_boundary(idx) {
+            return None;
+        }
+        Some(text.split_at(idx))
+    }
 }
 
 impl<R: TreeRoot<RaTypes>> SyntaxNode<R> {
@@ -159,3 +295,426 @@ fn has_short_text(kind: SyntaxKind) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ra_text_edit::AtomTextEdit;
+
+    use crate::{ast, AstNode, Direction, SourceFileNode, SyntaxNodeRef, TextRange, TextUnit};
+    use crate::SyntaxKind::*;
+
+    #[test]
+    fn test_offset_of_child_containing() {
+        let file = SourceFileNode::parse("fn foo() { 92 }");
+        let fn_def = file.syntax().first_child().unwrap();
+        let check = |offset: u32, kind, relative: u32| {
+            let (child, rel) = fn_def
+                .offset_of_child_containing(TextUnit::from(offset))
+                .unwrap();
+            assert_eq!(child.kind(), kind);
+            assert_eq!(rel, TextUnit::from(relative));
+        };
+        check(0, FN_KW, 0);
+        check(1, FN_KW, 1);
+        check(2, WHITESPACE, 0);
+        check(4, NAME, 1);
+        check(6, PARAM_LIST, 0);
+        check(12, BLOCK, 3);
+        assert!(fn_def
+            .offset_of_child_containing(TextUnit::from(15))
+            .is_none());
+    }
+
+    #[test]
+    fn test_split_at_offset() {
+        let file = SourceFileNode::parse(r#"fn foo() { "hello" }"#);
+        let string = file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == STRING)
+            .unwrap();
+        assert_eq!(
+            string.split_at_offset(TextUnit::from(15)),
+            Some((r#""hel"#, r#"lo""#))
+        );
+        assert_eq!(
+            string.split_at_offset(TextUnit::from(11)),
+            Some(("", r#""hello""#))
+        );
+        assert_eq!(string.split_at_offset(TextUnit::from(10)), None);
+        assert_eq!(
+            string.parent().unwrap().split_at_offset(TextUnit::from(14)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let file = SourceFileNode::parse("mod m { fn foo() { 92 } }");
+        let literal = file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == INT_NUMBER)
+            .unwrap();
+        let kinds = literal.ancestors().map(|it| it.kind()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                INT_NUMBER,
+                LITERAL,
+                BLOCK,
+                FN_DEF,
+                ITEM_LIST,
+                MODULE,
+                SOURCE_FILE
+            ]
+        );
+        assert_eq!(file.syntax().ancestors().count(), 1);
+    }
+
+    #[test]
+    fn test_descendants_preorder() {
+        let file = SourceFileNode::parse("fn f(x: u32) {}");
+        let param_list = file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == PARAM_LIST)
+            .unwrap();
+        let kinds = param_list
+            .descendants()
+            .map(|it| it.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                PARAM_LIST,
+                L_PAREN,
+                PARAM,
+                BIND_PAT,
+                NAME,
+                IDENT,
+                COLON,
+                WHITESPACE,
+                PATH_TYPE,
+                PATH,
+                PATH_SEGMENT,
+                NAME_REF,
+                IDENT,
+                R_PAREN,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_descendants_of_kind() {
+        let file = SourceFileNode::parse(
+            "fn foo() { fn bar() {} } impl S { fn baz() {} } mod m { trait T { fn quux(); } }",
+        );
+        let names = file
+            .syntax()
+            .descendants_of_kind(FN_DEF)
+            .map(|it| {
+                let name = it.children().find(|it| it.kind() == NAME).unwrap();
+                name.text().to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["foo", "bar", "baz", "quux"]);
+        let fn_def = file.syntax().first_child().unwrap();
+        assert_eq!(fn_def.descendants_of_kind(FN_DEF).count(), 2);
+        assert_eq!(fn_def.descendants_of_kind(IMPL_ITEM).count(), 0);
+    }
+
+    #[test]
+    fn test_prev_sibling_and_reverse_siblings() {
+        let file = SourceFileNode::parse("fn foo() { 92 }");
+        let fn_def = file.syntax().first_child().unwrap();
+        let first = fn_def.first_child().unwrap();
+        assert_eq!(first.kind(), FN_KW);
+        assert!(first.prev_sibling().is_none());
+
+        let last = fn_def.last_child().unwrap();
+        let kinds = last
+            .siblings(Direction::Prev)
+            .map(|it| it.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![BLOCK, WHITESPACE, PARAM_LIST, NAME, WHITESPACE, FN_KW]
+        );
+        assert_eq!(last.prev_sibling().unwrap().kind(), WHITESPACE);
+
+        let int_number = fn_def
+            .descendants()
+            .find(|it| it.kind() == INT_NUMBER)
+            .unwrap();
+        assert!(int_number.prev_sibling().is_none());
+        assert!(int_number.next_sibling().is_none());
+        assert_eq!(int_number.siblings(Direction::Prev).count(), 1);
+        assert_eq!(int_number.siblings(Direction::Next).count(), 1);
+    }
+
+    #[test]
+    fn test_siblings_no_trivia() {
+        let file = SourceFileNode::parse("fn foo() { (a , b); }");
+        let a = file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == PATH_EXPR)
+            .unwrap();
+        let kinds = |direction| {
+            a.siblings_no_trivia(direction)
+                .map(|it| it.kind())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(Direction::Next),
+            vec![PATH_EXPR, COMMA, PATH_EXPR, R_PAREN]
+        );
+        assert_eq!(kinds(Direction::Prev), vec![PATH_EXPR, L_PAREN]);
+        let next = a.siblings_no_trivia(Direction::Next).nth(2).unwrap();
+        assert_eq!(next.text().to_string(), "b");
+    }
+
+    #[test]
+    fn test_is_ancestor_of() {
+        let file = SourceFileNode::parse("fn foo() { 92 } struct S;");
+        let fn_def = file.syntax().first_child().unwrap();
+        let literal = fn_def
+            .descendants()
+            .find(|it| it.kind() == INT_NUMBER)
+            .unwrap();
+        assert!(fn_def.is_ancestor_of(literal));
+        assert!(file.syntax().is_ancestor_of(literal));
+        assert!(!literal.is_ancestor_of(fn_def));
+        assert!(!fn_def.is_ancestor_of(fn_def));
+
+        let struct_def = file
+            .syntax()
+            .children()
+            .find(|it| it.kind() == STRUCT_DEF)
+            .unwrap();
+        assert!(!fn_def.is_ancestor_of(struct_def));
+        assert!(!struct_def.is_ancestor_of(fn_def));
+
+        let other_file = SourceFileNode::parse("fn foo() { 92 } struct S;");
+        let other_literal = other_file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == INT_NUMBER)
+            .unwrap();
+        assert!(!fn_def.is_ancestor_of(other_literal));
+    }
+
+    #[test]
+    fn test_tokens() {
+        let file = SourceFileNode::parse(
+            "fn f() { // hi
+ 92 }",
+        );
+        let block = file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == BLOCK)
+            .unwrap();
+        let tokens = block
+            .tokens()
+            .map(|it| (it.kind(), it.range(), it.leaf_text().unwrap().as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                (L_CURLY, TextRange::from_to(7.into(), 8.into()), "{"),
+                (WHITESPACE, TextRange::from_to(8.into(), 9.into()), " "),
+                (COMMENT, TextRange::from_to(9.into(), 14.into()), "// hi"),
+                (WHITESPACE, TextRange::from_to(14.into(), 16.into()), "\n "),
+                (INT_NUMBER, TextRange::from_to(16.into(), 18.into()), "92"),
+                (WHITESPACE, TextRange::from_to(18.into(), 19.into()), " "),
+                (R_CURLY, TextRange::from_to(19.into(), 20.into()), "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leaf_at_offset() {
+        let file = SourceFileNode::parse("fn f() { ab.cd }");
+        let kinds = |offset: u32| {
+            file.syntax()
+                .leaf_at_offset(offset.into())
+                .map(|it| it.kind())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(10), vec![IDENT]);
+        // Right after the dot, as in completion.
+        assert_eq!(kinds(12), vec![DOT, IDENT]);
+        assert_eq!(kinds(16), vec![R_CURLY]);
+        let empty = SourceFileNode::parse("");
+        assert!(empty.syntax().leaf_at_offset(0.into()).next().is_none());
+    }
+
+    #[test]
+    fn test_last_ancestor_while() {
+        let file = SourceFileNode::parse("fn f() { let x = -(1 + 2) * 3; }");
+        let one = file
+            .syntax()
+            .descendants()
+            .find(|it| it.kind() == LITERAL)
+            .unwrap();
+        let is_expr = |node| ast::Expr::cast(node).is_some();
+        let expr = one.last_ancestor_while(is_expr).unwrap();
+        assert_eq!(expr.kind(), BIN_EXPR);
+        assert_eq!(expr.text().to_string(), "-(1 + 2) * 3");
+        assert_eq!(expr.parent().unwrap().kind(), LET_STMT);
+
+        let let_stmt = expr.parent().unwrap();
+        assert!(let_stmt.last_ancestor_while(is_expr).is_none());
+    }
+
+    #[test]
+    fn test_node_identity() {
+        let text = "fn f() { a + a }";
+        let file = SourceFileNode::parse(text);
+        let name_refs = file
+            .syntax()
+            .descendants()
+            .filter(|it| it.kind() == NAME_REF)
+            .collect::<Vec<_>>();
+        assert_eq!(name_refs.len(), 2);
+        assert_ne!(name_refs[0], name_refs[1]);
+
+        let bin_exprs = name_refs
+            .iter()
+            .map(|it| it.ancestors().find(|it| it.kind() == BIN_EXPR).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(bin_exprs[0], bin_exprs[1]);
+        let visited = bin_exprs.into_iter().collect::<HashSet<_>>();
+        assert_eq!(visited.len(), 1);
+        assert_eq!(file.syntax().owned(), file.syntax());
+
+        let other = SourceFileNode::parse(text);
+        assert_ne!(file.syntax(), other.syntax());
+    }
+
+    #[test]
+    fn test_first_and_last_token() {
+        let file = SourceFileNode::parse("fn foo(a: u32) { bar(1, a) }\nfn");
+        let fn_def = file.syntax().first_child().unwrap();
+        assert_eq!(fn_def.first_token().unwrap().kind(), FN_KW);
+        assert_eq!(fn_def.last_token().unwrap().kind(), R_CURLY);
+
+        let arg_list = fn_def
+            .descendants()
+            .find(|it| it.kind() == ARG_LIST)
+            .unwrap();
+        assert_eq!(arg_list.first_token().unwrap().kind(), L_PAREN);
+        assert_eq!(arg_list.last_token().unwrap().kind(), R_PAREN);
+
+        let leaf = arg_list.first_token().unwrap();
+        assert_eq!(leaf.first_token(), Some(leaf));
+        assert_eq!(leaf.last_token(), Some(leaf));
+
+        // The truncated function ends with an empty `ERROR` node.
+        let last_fn = file.syntax().last_child().unwrap();
+        let error = last_fn.last_child().unwrap();
+        assert_eq!(error.kind(), ERROR);
+        assert_eq!(error.first_token(), None);
+        assert_eq!(error.last_token(), None);
+        assert_eq!(last_fn.last_token().unwrap().kind(), FN_KW);
+    }
+
+    #[test]
+    fn test_child_of_kind() {
+        let file = SourceFileNode::parse("fn foo(a: u32, b: u32) {}");
+        let fn_def = file.syntax().first_child().unwrap();
+        assert_eq!(
+            fn_def.child_of_kind(NAME).unwrap().text().to_string(),
+            "foo"
+        );
+        assert!(fn_def.child_of_kind(RET_TYPE).is_none());
+
+        let params = fn_def
+            .child_of_kind(PARAM_LIST)
+            .unwrap()
+            .children_of_kind(PARAM)
+            .map(|it| it.text().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(params, vec!["a: u32", "b: u32"]);
+        // Only direct children are considered.
+        assert_eq!(fn_def.children_of_kind(PARAM).count(), 0);
+    }
+
+    #[test]
+    fn test_sibling_after() {
+        let file = SourceFileNode::parse("fn foo(a: u32, b: u32) {}");
+        let param_list = file
+            .syntax()
+            .descendants_of_kind(PARAM_LIST)
+            .next()
+            .unwrap();
+        let check = |offset: u32| {
+            param_list
+                .sibling_after(TextUnit::from(offset))
+                .map(|it| it.text().to_string())
+        };
+        // `(a: u32, b: u32)` starts at 6.
+        assert_eq!(check(0), Some("(".to_string()));
+        assert_eq!(check(7), Some("a: u32".to_string()));
+        assert_eq!(check(8), Some(",".to_string()));
+        assert_eq!(check(13), Some(",".to_string()));
+        assert_eq!(check(14), Some(" ".to_string()));
+        assert_eq!(check(21), Some(")".to_string()));
+        assert_eq!(check(22), None);
+        assert_eq!(check(100), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let file = SourceFileNode::parse("fn foo() { 1 2 }\nfn bar() { let x = 1 }");
+        // Reparsing the first block appends its errors after the one of
+        // `bar`.
+        let edit = AtomTextEdit::insert(TextUnit::from(11), "let y = 92 ".to_string());
+        let file = file.incremental_reparse(&edit).unwrap();
+        let fns = file.syntax().children().collect::<Vec<_>>();
+        let errors = |node: SyntaxNodeRef| {
+            node.parse_errors()
+                .into_iter()
+                .map(|it| (it.range(), it.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let range = |start: u32, end: u32| TextRange::from_to(start.into(), end.into());
+        assert_eq!(
+            errors(fns[0]),
+            vec![
+                (range(21, 21), "expected SEMI".to_string()),
+                (range(23, 23), "expected SEMI".to_string()),
+            ]
+        );
+        assert_eq!(
+            errors(fns[2]),
+            vec![(range(48, 48), "expected SEMI".to_string())]
+        );
+        assert_eq!(errors(file.syntax()).len(), 3);
+        assert!(errors(fns[2].first_child().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_covering_node() {
+        let file = SourceFileNode::parse("fn f() { foo(1, 2); }");
+        let kind = |start: u32, end: u32| {
+            file.syntax()
+                .covering_node(TextRange::from_to(start.into(), end.into()))
+                .kind()
+        };
+        assert_eq!(kind(9, 12), IDENT);
+        assert_eq!(kind(10, 11), IDENT);
+        // `1, 2` spans several arguments.
+        assert_eq!(kind(13, 17), ARG_LIST);
+        // `foo(`, crossing from the callee into the arguments.
+        assert_eq!(kind(9, 13), CALL_EXPR);
+        // A bare cursor inside a leaf and between two leaves.
+        assert_eq!(kind(10, 10), IDENT);
+        assert_eq!(kind(12, 12), IDENT);
+        assert_eq!(kind(0, 21), FN_DEF);
+    }
+}
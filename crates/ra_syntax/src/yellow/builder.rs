@@ -1,13 +1,46 @@
+use std::fmt;
+
 use crate::{
     parser_impl::Sink,
-    yellow::{GreenNode, RaTypes, SyntaxError},
+    yellow::{
+        syntax_error::{ParseError, SyntaxErrorKind},
+        GreenNode, RaTypes, SyntaxError,
+    },
     SmolStr, SyntaxKind,
+    SyntaxKind::{ERROR, SOURCE_FILE},
+    TextRange, TextUnit,
 };
 use rowan::GreenNodeBuilder;
 
+/// Describes how the `start_internal`/`finish_internal` calls of a tree
+/// producer didn't match up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BuilderError {
+    /// `finish_internal` was called while no node was open.
+    UnmatchedFinish,
+    /// These nodes were still open at the end, outermost first.
+    UnclosedNodes(Vec<SyntaxKind>),
+    /// The producer emitted this many top-level nodes instead of a single
+    /// root.
+    RootCount(usize),
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuilderError::UnmatchedFinish => write!(f, "finished a node which wasn't started"),
+            BuilderError::UnclosedNodes(kinds) => write!(f, "unclosed nodes: {:?}", kinds),
+            BuilderError::RootCount(count) => write!(f, "expected a single root, got {}", count),
+        }
+    }
+}
+
 pub(crate) struct GreenBuilder {
     errors: Vec<SyntaxError>,
     inner: GreenNodeBuilder<RaTypes>,
+    open: Vec<SyntaxKind>,
+    roots: usize,
+    unmatched_finish: bool,
 }
 
 impl GreenBuilder {
@@ -15,22 +48,54 @@ impl GreenBuilder {
         GreenBuilder {
             errors: Vec::new(),
             inner: GreenNodeBuilder::new(),
+            open: Vec::new(),
+            roots: 0,
+            unmatched_finish: false,
         }
     }
 }
 
+/// A tree for `text` which a parser failed to build: a single error leaf,
+/// so that at least the text stays intact.
+pub(crate) fn unparsed_tree(text: &str, err: &BuilderError) -> (GreenNode, Vec<SyntaxError>) {
+    let children = if text.is_empty() {
+        Vec::new()
+    } else {
+        vec![GreenNode::new_leaf(ERROR, text.into())]
+    };
+    let green = GreenNode::new_branch(SOURCE_FILE, children.into_boxed_slice());
+    let range = TextRange::offset_len(0.into(), TextUnit::of_str(text));
+    let error = SyntaxError::new(
+        SyntaxErrorKind::ParseError(ParseError(format!("unbalanced syntax tree: {}", err))),
+        range,
+    );
+    (green, vec![error])
+}
+
 impl Sink for GreenBuilder {
-    type Tree = (GreenNode, Vec<SyntaxError>);
+    type Tree = Result<(GreenNode, Vec<SyntaxError>), BuilderError>;
 
     fn leaf(&mut self, kind: SyntaxKind, text: SmolStr) {
+        if self.open.is_empty() {
+            self.roots += 1;
+        }
         self.inner.leaf(kind, text);
     }
 
     fn start_internal(&mut self, kind: SyntaxKind) {
+        self.open.push(kind);
         self.inner.start_internal(kind)
     }
 
     fn finish_internal(&mut self) {
+        if self.open.pop().is_none() {
+            // Don't let the inner builder pop a parent which isn't there.
+            self.unmatched_finish = true;
+            return;
+        }
+        if self.open.is_empty() {
+            self.roots += 1;
+        }
         self.inner.finish_internal();
     }
 
@@ -38,7 +103,91 @@ impl Sink for GreenBuilder {
         self.errors.push(error)
     }
 
-    fn finish(self) -> (GreenNode, Vec<SyntaxError>) {
-        (self.inner.finish(), self.errors)
+    /// Reports an unbalanced tree, which means that the producer has a bug.
+    fn finish(self) -> Result<(GreenNode, Vec<SyntaxError>), BuilderError> {
+        if self.unmatched_finish {
+            return Err(BuilderError::UnmatchedFinish);
+        }
+        if !self.open.is_empty() {
+            return Err(BuilderError::UnclosedNodes(self.open));
+        }
+        if self.roots != 1 {
+            return Err(BuilderError::RootCount(self.roots));
+        }
+        Ok((self.inner.finish(), self.errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SyntaxKind::*, SyntaxNode};
+
+    fn build(f: impl FnOnce(&mut GreenBuilder)) -> Result<GreenNode, BuilderError> {
+        let mut builder = GreenBuilder::new();
+        f(&mut builder);
+        builder.finish().map(|(green, _errors)| green)
+    }
+
+    #[test]
+    fn test_balanced() {
+        let green = build(|b| {
+            b.start_internal(SOURCE_FILE);
+            b.start_internal(FN_DEF);
+            b.leaf(FN_KW, "fn".into());
+            b.finish_internal();
+            b.finish_internal();
+        })
+        .unwrap();
+        assert_eq!(green.kind(), SOURCE_FILE);
+    }
+
+    #[test]
+    fn test_unclosed_node() {
+        let err = build(|b| {
+            b.start_internal(SOURCE_FILE);
+            b.start_internal(FN_DEF);
+            b.leaf(FN_KW, "fn".into());
+            b.start_internal(NAME);
+            b.finish_internal();
+        })
+        .unwrap_err();
+        assert_eq!(err, BuilderError::UnclosedNodes(vec![SOURCE_FILE, FN_DEF]));
+        assert_eq!(err.to_string(), "unclosed nodes: [SOURCE_FILE, FN_DEF]");
+    }
+
+    #[test]
+    fn test_unmatched_finish() {
+        let err = build(|b| {
+            b.start_internal(SOURCE_FILE);
+            b.finish_internal();
+            b.finish_internal();
+        })
+        .unwrap_err();
+        assert_eq!(err, BuilderError::UnmatchedFinish);
+    }
+
+    #[test]
+    fn test_several_roots() {
+        let err = build(|b| {
+            b.start_internal(SOURCE_FILE);
+            b.finish_internal();
+            b.leaf(WHITESPACE, " ".into());
+        })
+        .unwrap_err();
+        assert_eq!(err, BuilderError::RootCount(2));
+    }
+
+    #[test]
+    fn test_unparsed_tree_keeps_text() {
+        let err = BuilderError::UnclosedNodes(vec![SOURCE_FILE]);
+        let (green, errors) = unparsed_tree("fn foo() {}", &err);
+        let root = SyntaxNode::new(green, errors);
+        assert_eq!(root.kind(), SOURCE_FILE);
+        assert_eq!(root.text().to_string(), "fn foo() {}");
+        assert_eq!(
+            root.root_data()[0].to_string(),
+            "unbalanced syntax tree: unclosed nodes: [SOURCE_FILE]"
+        );
     }
 }
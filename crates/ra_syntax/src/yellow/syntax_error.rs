@@ -42,6 +42,15 @@ impl SyntaxError {
         self.location.clone()
     }
 
+    /// The span the error points at. Errors reported at an offset, like a
+    /// missing token, have an empty range.
+    pub fn range(&self) -> TextRange {
+        match self.location {
+            Location::Offset(offset) => TextRange::offset_len(offset, 0.into()),
+            Location::Range(range) => range,
+        }
+    }
+
     pub fn offset(&self) -> TextUnit {
         match self.location {
             Location::Offset(offset) => offset,
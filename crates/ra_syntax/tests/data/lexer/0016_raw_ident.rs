@@ -0,0 +1 @@
+r#match r#type r#"raw"#
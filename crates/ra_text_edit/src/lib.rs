@@ -27,3 +27,43 @@ impl AtomTextEdit {
         AtomTextEdit::replace(TextRange::offset_len(offset, 0.into()), text)
     }
 }
+
+/// Sorts `atoms` by their position in the text and checks that no two of
+/// them overlap. Returns the first pair of conflicting ranges if they do.
+pub fn sort_and_check_conflicts(atoms: &mut [AtomTextEdit]) -> Result<(), (TextRange, TextRange)> {
+    atoms.sort_by_key(|a| (a.delete.start(), a.delete.end()));
+    for (a1, a2) in atoms.iter().zip(atoms.iter().skip(1)) {
+        if a1.delete.end() > a2.delete.start() {
+            return Err((a1.delete, a2.delete));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_and_check_conflicts() {
+        let range = |start: u32, end: u32| TextRange::from_to(start.into(), end.into());
+        let mut atoms = vec![
+            AtomTextEdit::replace(range(10, 15), "foo".to_string()),
+            AtomTextEdit::insert(5.into(), "bar".to_string()),
+            AtomTextEdit::delete(range(0, 5)),
+        ];
+        assert_eq!(sort_and_check_conflicts(&mut atoms), Ok(()));
+        let ranges: Vec<_> = atoms.iter().map(|it| it.delete).collect();
+        assert_eq!(ranges, vec![range(0, 5), range(5, 5), range(10, 15)]);
+
+        let mut atoms = vec![
+            AtomTextEdit::replace(range(10, 15), "foo".to_string()),
+            AtomTextEdit::replace(range(12, 20), "bar".to_string()),
+            AtomTextEdit::delete(range(0, 5)),
+        ];
+        assert_eq!(
+            sort_and_check_conflicts(&mut atoms),
+            Err((range(10, 15), range(12, 20)))
+        );
+    }
+}
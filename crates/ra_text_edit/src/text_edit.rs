@@ -1,4 +1,4 @@
-use crate::AtomTextEdit;
+use crate::{AtomTextEdit, sort_and_check_conflicts};
 use crate::text_utils::contains_offset_nonstrict;
 use text_unit::{TextRange, TextUnit};
 
@@ -27,9 +27,8 @@ impl TextEditBuilder {
     }
     pub fn finish(self) -> TextEdit {
         let mut atoms = self.atoms;
-        atoms.sort_by_key(|a| (a.delete.start(), a.delete.end()));
-        for (a1, a2) in atoms.iter().zip(atoms.iter().skip(1)) {
-            assert!(a1.delete.end() <= a2.delete.start())
+        if let Err((r1, r2)) = sort_and_check_conflicts(&mut atoms) {
+            panic!("overlapping edits: {:?} and {:?}", r1, r2);
         }
         TextEdit { atoms }
     }
@@ -41,6 +40,10 @@ impl TextEditBuilder {
 }
 
 impl TextEdit {
+    pub fn as_atoms(&self) -> &[AtomTextEdit] {
+        &self.atoms
+    }
+
     pub fn into_atoms(self) -> Vec<AtomTextEdit> {
         self.atoms
     }
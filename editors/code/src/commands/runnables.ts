@@ -5,6 +5,7 @@ import { Server } from '../server';
 interface RunnablesParams {
     textDocument: lc.TextDocumentIdentifier;
     position?: lc.Position;
+    kind?: 'test' | 'bin' | 'bench';
 }
 
 interface Runnable {
@@ -13,6 +14,7 @@ interface Runnable {
     bin: string;
     args: string[];
     env: { [index: string]: string };
+    cwd?: string;
 }
 
 class RunnableQuickPick implements vscode.QuickPickItem {
@@ -45,7 +47,7 @@ function createTask(spec: Runnable): vscode.Task {
     };
 
     const execOption: vscode.ShellExecutionOptions = {
-        cwd: '.',
+        cwd: spec.cwd || '.',
         env: definition.env
     };
     const exec = new vscode.ShellExecution(definition.command, definition.args, execOption);
@@ -6,6 +6,7 @@ import { Server } from './server';
 export interface Decoration {
     range: lc.Range;
     tag: string;
+    modifiers?: string[];
 }
 
 export class Highlighter {